@@ -0,0 +1,1196 @@
+use chrono::{DateTime, Local};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::block;
+use crate::compare::SectorRange;
+use crate::ewfargs::{self, ArgsList, SourceKind};
+use crate::hash::{Digests, Hasher};
+use crate::image::{self, read_full, SplitReader};
+use crate::job::{self, JobState, Resumption};
+use crate::rescue::{self, Phase, RescueMap};
+use crate::tuning::{self, Benchmark, WriterPool};
+use crate::verify::{self, Verification};
+use crate::LoggingInfo;
+
+/// Somewhere the data read from the source is written to. Writers are written to from the
+/// threads of a `WriterPool`.
+pub trait ImageWriter: Send {
+    /// Append a block of data to the image.
+    fn write_chunk(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// Flush everything written so far to disk, so that it survives a power cut.
+    ///
+    /// # Returns
+    ///
+    /// The files written so far.
+    fn checkpoint(&mut self) -> io::Result<Vec<PathBuf>>;
+
+    /// Flush and close the image.
+    ///
+    /// # Arguments
+    ///
+    /// * `digests` - The digests of everything written, for writers that store them alongside the image.
+    ///
+    /// # Returns
+    ///
+    /// The files that make up the image.
+    fn finish(self: Box<Self>, digests: &Digests) -> io::Result<Vec<PathBuf>>;
+}
+
+/// Writes a raw image, either as a single `.dd` file or as numbered `.001`, `.002`... segments.
+/// Digests are written to sidecar files in the format used by `md5sum` and friends.
+pub struct RawWriter {
+    dir: PathBuf,
+    filename: String,
+    segment_size: Option<u64>,
+    current: Option<BufWriter<File>>,
+    current_len: u64,
+    files: Vec<PathBuf>,
+}
+
+impl RawWriter {
+    /// Returns a new `RawWriter`. No files are created until data is written.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to write the image to.
+    /// * `filename` - The filename of the image, without an extension.
+    /// * `segment_size` - The size of each segment in bytes, or `None` for a single file.
+    pub fn new(dir: &Path, filename: &str, segment_size: Option<u64>) -> RawWriter {
+        RawWriter {
+            dir: dir.to_path_buf(),
+            filename: filename.to_string(),
+            segment_size,
+            current: None,
+            current_len: 0,
+            files: Vec::new(),
+        }
+    }
+
+    /// Returns a `RawWriter` that carries on writing an interrupted image after its first `done`
+    /// bytes. Anything written past that point is discarded: the file `done` falls in is
+    /// truncated, and any later segments written since the job started are removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory the image is in.
+    /// * `filename` - The filename of the image, without an extension.
+    /// * `segment_size` - The size of each segment in bytes, or `None` for a single file.
+    /// * `files` - The files written before the interruption, in order.
+    /// * `done` - How many bytes of the image to keep.
+    /// * `started` - When the job started; later segments older than this weren't written by it.
+    pub fn resume(
+        dir: &Path,
+        filename: &str,
+        segment_size: Option<u64>,
+        files: &[PathBuf],
+        done: u64,
+        started: SystemTime,
+    ) -> io::Result<RawWriter> {
+        let mut writer = RawWriter::new(dir, filename, segment_size);
+        let mut before = 0;
+
+        for path in files {
+            let len = path.metadata()?.len();
+            let keep = (done - before).min(len);
+            let mut file = OpenOptions::new().write(true).open(path)?;
+            file.set_len(keep)?;
+            file.seek(SeekFrom::End(0))?;
+
+            writer.current = Some(BufWriter::new(file));
+            writer.current_len = keep;
+            writer.files.push(path.clone());
+            before += keep;
+            if before == done {
+                break;
+            }
+        }
+        if before < done {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The image is shorter than the job says was written",
+            ));
+        }
+
+        // Files started after the last checkpoint hold nothing worth keeping
+        let mut number = writer.files.len() + 1;
+        while segment_size.is_some() || number == 1 {
+            let stale = dir.join(writer.file_name(number));
+            let modified = match stale.metadata() {
+                Ok(metadata) => metadata.modified()?,
+                Err(_) => break,
+            };
+            if modified < started {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} was not written by this job", stale.display()),
+                ));
+            }
+            fs::remove_file(stale)?;
+            number += 1;
+        }
+
+        Ok(writer)
+    }
+
+    /// Returns the name of the file with the given number, counting from 1.
+    fn file_name(&self, number: usize) -> String {
+        match self.segment_size {
+            Some(_) => format!("{}.{:03}", self.filename, number),
+            None => format!("{}.dd", self.filename),
+        }
+    }
+
+    /// Close the current file, if any, and start the next one. Refuses to overwrite existing files.
+    fn next_file(&mut self) -> io::Result<()> {
+        if let Some(mut current) = self.current.take() {
+            current.flush()?;
+        }
+
+        let path = self.dir.join(self.file_name(self.files.len() + 1));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        self.current = Some(BufWriter::new(file));
+        self.current_len = 0;
+        self.files.push(path);
+        Ok(())
+    }
+
+    /// Write one sidecar file containing `digest`.
+    fn write_sidecar(&self, extension: &str, digest: &str) -> io::Result<()> {
+        // A split image has no single file to name, so name stdin instead; the sidecar can
+        // then be checked with e.g. `cat image.0* | md5sum -c image.md5`
+        let name = match self.segment_size {
+            Some(_) => "-".to_string(),
+            None => format!("{}.dd", self.filename),
+        };
+        let path = self.dir.join(format!("{}.{}", self.filename, extension));
+        let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        writeln!(file, "{}  {}", digest, name)
+    }
+}
+
+impl ImageWriter for RawWriter {
+    fn write_chunk(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            let full = match self.segment_size {
+                Some(size) => self.current_len >= size,
+                None => false,
+            };
+            if self.current.is_none() || full {
+                self.next_file()?;
+            }
+
+            let room = match self.segment_size {
+                Some(size) => (size - self.current_len) as usize,
+                None => data.len(),
+            };
+            let len = room.min(data.len());
+
+            self.current.as_mut().unwrap().write_all(&data[..len])?;
+            self.current_len += len as u64;
+            data = &data[len..];
+        }
+        Ok(())
+    }
+
+    fn checkpoint(&mut self) -> io::Result<Vec<PathBuf>> {
+        if let Some(ref mut current) = self.current {
+            current.flush()?;
+            current.get_ref().sync_all()?;
+        }
+        Ok(self.files.clone())
+    }
+
+    fn finish(mut self: Box<Self>, digests: &Digests) -> io::Result<Vec<PathBuf>> {
+        // An empty source still produces an (empty) image
+        if self.files.is_empty() {
+            self.next_file()?;
+        }
+        if let Some(mut current) = self.current.take() {
+            current.flush()?;
+            current.get_ref().sync_all()?;
+        }
+
+        self.write_sidecar("md5", &digests.md5)?;
+        if let Some(ref sha1) = digests.sha1 {
+            self.write_sidecar("sha1", sha1)?;
+        }
+        if let Some(ref sha256) = digests.sha256 {
+            self.write_sidecar("sha256", sha256)?;
+        }
+
+        Ok(self.files.clone())
+    }
+}
+
+/// Writes an EWF image by streaming the data into ewfacquirestream.
+pub struct EwfWriter {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    first_segment: PathBuf,
+}
+
+impl EwfWriter {
+    /// Start ewfacquirestream.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments describing the image.
+    /// * `dir` - The directory to write the image to.
+    pub fn new(args: &ArgsList, dir: &Path) -> io::Result<EwfWriter> {
+        let filename = args.target_filename.clone().unwrap_or_default();
+        let target = dir.join(&filename);
+
+        let mut child = Command::new("ewfacquirestream")
+            .args(args.ewfacquirestream_args(&target.to_string_lossy()))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take();
+
+        Ok(EwfWriter {
+            child,
+            stdin,
+            first_segment: dir.join(format!("{}.E01", filename)),
+        })
+    }
+}
+
+impl EwfWriter {
+    /// Remove the segments of an EWF image that an interrupted job started writing to `dir`, so
+    /// that the image can be written again from the start.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` if a segment was last modified before the job started, so may belong to another
+    ///   image, or a segment could not be removed
+    /// - `Ok` with the segments that were removed
+    pub fn remove_partial(
+        dir: &Path,
+        filename: &str,
+        started: SystemTime,
+    ) -> io::Result<Vec<PathBuf>> {
+        let segments = image::ewf_segments(&dir.join(format!("{}.E01", filename)));
+        for segment in &segments {
+            if segment.metadata()?.modified()? < started {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} was not written by this job", segment.display()),
+                ));
+            }
+        }
+        for segment in &segments {
+            fs::remove_file(segment)?;
+        }
+        Ok(segments)
+    }
+}
+
+impl ImageWriter for EwfWriter {
+    fn write_chunk(&mut self, data: &[u8]) -> io::Result<()> {
+        self.stdin.as_mut().unwrap().write_all(data)
+    }
+
+    fn checkpoint(&mut self) -> io::Result<Vec<PathBuf>> {
+        // ewfacquirestream can't resume an image, so there's nothing useful to record
+        self.stdin.as_mut().unwrap().flush()?;
+        Ok(Vec::new())
+    }
+
+    fn finish(mut self: Box<Self>, _: &Digests) -> io::Result<Vec<PathBuf>> {
+        // Closing stdin tells ewfacquirestream the stream is finished
+        drop(self.stdin.take());
+        let status = self.child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "ewfacquirestream failed ({})",
+                status
+            )));
+        }
+        Ok(vec![self.first_segment.clone()])
+    }
+}
+
+impl Drop for EwfWriter {
+    fn drop(&mut self) {
+        // If we never finished, don't let ewfacquirestream finalize a truncated image
+        if self.stdin.take().is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// A directory an image was written to, and the files written there.
+#[derive(Clone)]
+pub struct Destination {
+    /// The directory the image was written to.
+    pub dir: PathBuf,
+    /// The files that make up the image.
+    pub files: Vec<PathBuf>,
+    /// The result of reading this copy back, once it has been verified.
+    pub verification: Option<Verification>,
+}
+
+/// The files kept next to the first copy of the image when the source is read with the rescue engine.
+#[derive(Clone)]
+pub struct RescueFiles {
+    /// The raw copy of the source the rescue engine writes to as it reads.
+    pub copy: PathBuf,
+    /// The map of which areas of the source have been read.
+    pub map: PathBuf,
+    /// Whether an interrupted rescue was resumed from the map.
+    pub resumed: bool,
+}
+
+/// The outcome of a successful acquisition, or of hashing a source without imaging it.
+#[derive(Clone)]
+pub struct AcquisitionResult {
+    /// The size of the source in bytes.
+    pub source_size: u64,
+    /// The number of bytes read from the source and written to the image.
+    pub bytes_acquired: u64,
+    /// The digests of the data read from the source.
+    pub digests: Digests,
+    /// The sectors that could not be read and were zeroed in the image, numbered from the start
+    /// of the data read.
+    pub bad_sectors: Vec<SectorRange>,
+    /// When the acquisition started.
+    pub started: DateTime<Local>,
+    /// When the acquisition finished.
+    pub finished: DateTime<Local>,
+    /// Where the image was written.
+    pub destinations: Vec<Destination>,
+    /// The rescue engine's files, if the source was read with it.
+    pub rescue: Option<RescueFiles>,
+    /// Every time the acquisition was interrupted and resumed.
+    pub resumptions: Vec<Resumption>,
+    /// The read buffer size, writer threads and chunk size chosen by benchmarking, if the
+    /// acquisition was auto-tuned.
+    pub tuning: Option<Benchmark>,
+}
+
+/// Returns the size of an open file or block device in bytes.
+pub fn source_size(source: &mut File) -> io::Result<u64> {
+    let size = source.seek(SeekFrom::End(0))?;
+    source.seek(SeekFrom::Start(0))?;
+    Ok(size)
+}
+
+/// Returns the directories the image should be written to.
+pub fn target_dirs(args: &ArgsList) -> Vec<PathBuf> {
+    args.target_dirs.iter().map(PathBuf::from).collect()
+}
+
+/// The writers of every destination, fed from the same read of the source.
+type Writers = WriterPool<Box<dyn ImageWriter>>;
+
+/// Open a writer for every destination in `args`. Every writer is fed from the same read of the source.
+///
+/// If the job has already written part of the image, each raw writer carries on after the first
+/// `done` bytes of the files the job recorded for its destination.
+fn open_writers(
+    args: &ArgsList,
+    job: &JobState,
+    done: u64,
+) -> io::Result<Vec<Box<dyn ImageWriter>>> {
+    let filename = args.target_filename.clone().unwrap_or_default();
+    let segment_size = args.segment_size_bytes();
+    // Once the job has been checkpointed, its writers may have created files
+    let resuming = !job.segments.is_empty();
+    let mut writers = Vec::<Box<dyn ImageWriter>>::new();
+
+    for (index, dir) in target_dirs(args).into_iter().enumerate() {
+        if args.ewf_format.is_raw() && resuming {
+            let files = job.segments.get(index).cloned().unwrap_or_default();
+            writers.push(Box::new(RawWriter::resume(
+                &dir,
+                &filename,
+                segment_size,
+                &files,
+                done,
+                SystemTime::from(job.started),
+            )?));
+        } else if args.ewf_format.is_raw() {
+            writers.push(Box::new(RawWriter::new(&dir, &filename, segment_size)));
+        } else {
+            writers.push(Box::new(EwfWriter::new(args, &dir)?));
+        }
+    }
+
+    Ok(writers)
+}
+
+/// The size of the source, the bytes read from it, their digests, and the sectors that couldn't
+/// be read.
+struct SourceRead {
+    size: u64,
+    done: u64,
+    digests: Digests,
+    bad_sectors: Vec<SectorRange>,
+}
+
+/// How far reading a source has got, carried over when an interrupted job is resumed.
+struct ReadState {
+    /// The number of bytes read and written so far.
+    done: u64,
+    /// The running digests of the bytes read so far.
+    hasher: Hasher,
+    /// The sectors that couldn't be read so far.
+    bad_sectors: Vec<SectorRange>,
+}
+
+impl ReadState {
+    /// Returns the state of a read that hasn't started.
+    fn new(args: &ArgsList) -> ReadState {
+        ReadState {
+            done: 0,
+            hasher: Hasher::new(args.digest_type),
+            bad_sectors: Vec::new(),
+        }
+    }
+}
+
+/// How often the job state is saved while the image is written.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The job state file of an acquisition, kept up to date as the image is written.
+struct JobFile {
+    path: PathBuf,
+    state: JobState,
+    last_save: Instant,
+}
+
+impl JobFile {
+    /// Start the job for the acquisition described by `args`, saving its state next to the
+    /// first destination and adding it to the index of jobs on that filesystem.
+    fn create(args: &ArgsList, info: &LoggingInfo) -> io::Result<JobFile> {
+        let path = job::job_path(args)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No destinations selected"))?;
+        let state = JobState::new(args, info)?;
+        state.save(&path)?;
+        // Without the index the job just won't be offered for resuming at startup
+        let _ = job::register(&path);
+
+        Ok(JobFile {
+            path,
+            state,
+            last_save: Instant::now(),
+        })
+    }
+
+    /// Wait for every writer to write what it was given, flush it, and save how far the
+    /// acquisition has got.
+    fn checkpoint(&mut self, read: &ReadState, writers: &mut Writers) -> io::Result<()> {
+        let segments = writers.each(|writer: &mut Box<dyn ImageWriter>| writer.checkpoint())?;
+
+        self.state.bytes_done = read.done;
+        self.state.digests = read.hasher.clone().finish();
+        self.state.bad_sectors = read.bad_sectors.clone();
+        self.state.segments = segments;
+        self.state.save(&self.path)?;
+        self.last_save = Instant::now();
+        Ok(())
+    }
+
+    /// Remove the job once its image is complete, returning the times it was resumed.
+    fn finish(self) -> Vec<Resumption> {
+        // A leftover job file is only offered for resuming; it can't harm the finished image
+        let _ = job::remove(&self.path);
+        self.state.resumptions
+    }
+}
+
+/// Where `read_source` reads from.
+enum Source {
+    /// A drive or file, read at offsets so that read errors can be tolerated. Reading starts
+    /// `start` bytes in.
+    Block { device: File, start: u64 },
+    /// The media data of an existing image, read as a stream, and the digests stored in the
+    /// image, which what is read must match.
+    Stream {
+        reader: Box<dyn Read>,
+        stored: Option<Digests>,
+    },
+}
+
+/// Open the source described by `args`, returning it and the number of bytes to read from it.
+fn open_source(args: &ArgsList) -> io::Result<(Source, u64)> {
+    if let Some(bytes) = args.bytes_per_sector {
+        if !ewfargs::valid_sector_size(bytes) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} bytes is not a valid sector size", bytes),
+            ));
+        }
+    }
+    let source_path = args
+        .source_device
+        .clone()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No source selected"))?;
+
+    match args.source_kind {
+        SourceKind::Device | SourceKind::Other => {
+            let mut device = File::open(&source_path)?;
+            let size = source_size(&mut device)?;
+            match args.source_range {
+                Some(ref range) => {
+                    if !range.fits(size) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "The range to image is empty or extends past the end of the source",
+                        ));
+                    }
+                    let start = range.offset();
+                    Ok((Source::Block { device, start }, range.length()))
+                }
+                None => Ok((Source::Block { device, start: 0 }, size)),
+            }
+        }
+        SourceKind::Image => {
+            let (reader, size, stored) = image::open_image(Path::new(&source_path))?;
+            Ok((Source::Stream { reader, stored }, size))
+        }
+    }
+}
+
+/// Read `size` bytes of a source, carrying on from `state`, hashing it and feeding every writer.
+/// Unreadable sectors of a drive or file are handled as `args.error_policy()` describes. When
+/// there is a job, it is checkpointed every `CHECKPOINT_INTERVAL`.
+#[allow(clippy::too_many_arguments)]
+fn read_source<F>(
+    args: &ArgsList,
+    mut source: Source,
+    size: u64,
+    writers: &mut Writers,
+    mut state: ReadState,
+    mut job: Option<&mut JobFile>,
+    mut progress: F,
+    abort: &AtomicBool,
+) -> io::Result<SourceRead>
+where
+    F: FnMut(u64, u64),
+{
+    let policy = args.error_policy();
+    let mut buffer = vec![0_u8; args.read_buffer_size()];
+
+    // A stream can't be read at an offset, so skip what was read before the job was interrupted
+    if let Source::Stream { ref mut reader, .. } = source {
+        io::copy(&mut reader.by_ref().take(state.done), &mut io::sink())?;
+    }
+
+    loop {
+        if abort.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
+
+        let read = match source {
+            Source::Block { ref device, start } => {
+                let wanted = (size - state.done).min(buffer.len() as u64) as usize;
+                if wanted == 0 {
+                    break;
+                }
+                block::read_tolerant(
+                    device,
+                    &mut buffer[..wanted],
+                    start + state.done,
+                    start,
+                    &policy,
+                    &mut state.bad_sectors,
+                )?
+            }
+            Source::Stream { ref mut reader, .. } => match reader.read(&mut buffer) {
+                Ok(read) => read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            },
+        };
+        if read == 0 {
+            break;
+        }
+
+        state.hasher.update(&buffer[..read]);
+        writers.write(&buffer[..read])?;
+
+        state.done += read as u64;
+        progress(state.done, size);
+
+        if let Some(ref mut job) = job {
+            if job.last_save.elapsed() >= CHECKPOINT_INTERVAL {
+                job.checkpoint(&state, writers)?;
+            }
+        }
+    }
+
+    let digests = state.hasher.finish();
+    if let Source::Stream { ref stored, .. } = source {
+        check_stream(
+            &args.source_device.clone().unwrap_or_default(),
+            state.done,
+            size,
+            &digests,
+            stored,
+        )?;
+    }
+
+    Ok(SourceRead {
+        size,
+        done: state.done,
+        digests,
+        bad_sectors: state.bad_sectors,
+    })
+}
+
+/// Check that the media data read from an existing image is complete and matches the digests
+/// stored in the image.
+///
+/// # Returns
+///
+/// - `Err` if fewer or more than `size` bytes were read, or a digest differs from the stored one
+/// - `Ok` otherwise
+fn check_stream(
+    image: &str,
+    done: u64,
+    size: u64,
+    digests: &Digests,
+    stored: &Option<Digests>,
+) -> io::Result<()> {
+    if done != size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "{} ended after {} of its {} bytes of media data",
+                image, done, size
+            ),
+        ));
+    }
+
+    match stored {
+        Some(stored) if !stored.matches(digests) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "The media data read from {} does not match the digests stored in it \
+                 (stored MD5 {}, read MD5 {})",
+                image, stored.md5, digests.md5
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Benchmark the source and destinations of an auto-tuned acquisition, as `tuning::benchmark`
+/// and `tuning::benchmark_chunks` do, returning the fastest settings.
+fn auto_tune(
+    args: &ArgsList,
+    source: &Source,
+    size: u64,
+    abort: &AtomicBool,
+) -> io::Result<Benchmark> {
+    let block = match source {
+        Source::Block { device, start } => Some((device, *start, size)),
+        Source::Stream { .. } => None,
+    };
+    let mut benchmark = tuning::benchmark(
+        block,
+        &target_dirs(args),
+        args.bytes_per_sector.unwrap_or(512) as u64,
+        abort,
+    )?;
+    tuning::benchmark_chunks(&mut benchmark, args, block, abort)?;
+    Ok(benchmark)
+}
+
+/// Write the image of `source` to every destination, carrying on from `state` and keeping the
+/// job file up to date. An auto-tuned acquisition is benchmarked first, and the settings chosen
+/// are saved with the job, so that a resumed job carries on with them rather than benchmarking
+/// again.
+///
+/// # Returns
+///
+/// What was read, where each copy of the image was written, and the benchmark if there was one.
+fn write_image<F>(
+    args: &ArgsList,
+    (source, size): (Source, u64),
+    state: ReadState,
+    job: &mut JobFile,
+    progress: F,
+    abort: &AtomicBool,
+) -> io::Result<(SourceRead, Vec<Destination>, Option<Benchmark>)>
+where
+    F: FnMut(u64, u64),
+{
+    if args.auto_tune && job.state.tuning.is_none() {
+        job.state.tuning = Some(auto_tune(args, &source, size, abort)?);
+    }
+    let benchmark = job.state.tuning.clone();
+    let args = &match benchmark {
+        Some(ref benchmark) => benchmark.apply(args),
+        None => args.clone(),
+    };
+
+    let mut writers = WriterPool::new(
+        open_writers(args, &job.state, state.done)?,
+        args.writer_thread_count(),
+        |writer, data| writer.write_chunk(data),
+    );
+    job.checkpoint(&state, &mut writers)?;
+
+    let read = read_source(
+        args,
+        source,
+        size,
+        &mut writers,
+        state,
+        Some(job),
+        progress,
+        abort,
+    )?;
+
+    let mut destinations = Vec::new();
+    for (writer, dir) in writers.into_inner()?.into_iter().zip(target_dirs(args)) {
+        destinations.push(Destination {
+            files: writer.finish(&read.digests)?,
+            dir,
+            verification: None,
+        });
+    }
+    Ok((read, destinations, benchmark))
+}
+
+/// Image the source device described by `args`.
+///
+/// A job state file is kept next to the first destination while the image is written, so that
+/// an interrupted acquisition can be carried on with `resume`. It is removed once the image is
+/// complete.
+///
+/// # Arguments
+///
+/// * `args` - The arguments describing the source and the image to write.
+/// * `info` - The information about the source and host, saved in the job state.
+/// * `progress` - Called after every chunk with the number of bytes read so far and the size of the source.
+/// * `abort` - Checked after every chunk; when set, the acquisition stops with `ErrorKind::Interrupted`.
+///
+/// # Returns
+///
+/// - `Err` if the source could not be read, an image could not be written, or the acquisition was aborted
+/// - `Ok` with the digests of the source and the files written
+pub fn acquire<F>(
+    args: &ArgsList,
+    info: &LoggingInfo,
+    progress: F,
+    abort: &AtomicBool,
+) -> io::Result<AcquisitionResult>
+where
+    F: FnMut(u64, u64),
+{
+    if args.target_dirs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No destinations selected",
+        ));
+    }
+
+    let source = open_source(args)?;
+    let mut job = JobFile::create(args, info)?;
+    let (read, destinations, tuning) = write_image(
+        args,
+        source,
+        ReadState::new(args),
+        &mut job,
+        progress,
+        abort,
+    )?;
+
+    Ok(AcquisitionResult {
+        source_size: read.size,
+        bytes_acquired: read.done,
+        digests: read.digests,
+        bad_sectors: read.bad_sectors,
+        started: job.state.started,
+        finished: Local::now(),
+        destinations,
+        rescue: None,
+        resumptions: job.finish(),
+        tuning,
+    })
+}
+
+/// Returns where the rescue engine keeps its copy of the source and its map: next to the first
+/// copy of the image, named after it.
+pub fn rescue_paths(args: &ArgsList) -> Option<(PathBuf, PathBuf)> {
+    let dir = target_dirs(args).into_iter().next()?;
+    let filename = args.target_filename.clone().unwrap_or_default();
+    Some((
+        dir.join(format!("{}.rescue", filename)),
+        dir.join(format!("{}.map", filename)),
+    ))
+}
+
+/// Returns the first of the rescue engine's files for a rescue acquisition that already exists.
+/// A new rescue never adopts them, since they may be from another source.
+pub fn existing_rescue_file(args: &ArgsList) -> Option<PathBuf> {
+    if !args.rescue {
+        return None;
+    }
+    let (copy, map) = rescue_paths(args)?;
+    vec![copy, map].into_iter().find(|path| path.exists())
+}
+
+/// Image the source described by `args` with the multi-pass rescue engine, for failing drives.
+///
+/// The source is first rescued into a raw copy next to the first destination, keeping a map of
+/// what has been read, and which source it is of, so that an interrupted rescue resumes where it
+/// left off when its job is resumed. Once every pass has finished, the copy is written to every
+/// destination as `acquire` would write the source. Sectors that still couldn't be read are
+/// zeroed in the image. A job state file is kept as it is by `acquire`.
+///
+/// # Arguments
+///
+/// * `args` - The arguments describing the source and the image to write.
+/// * `info` - The information about the source and host, saved in the job state.
+/// * `phase` - Called when the rescue moves to a new phase or pass, and with `Phase::Finished` once the image is being written.
+/// * `progress` - Called with the number of bytes done so far and the total, first while rescuing and then while writing the image.
+/// * `abort` - When set, the rescue stops with `ErrorKind::Interrupted`, saving its map so that it can be resumed.
+///
+/// # Returns
+///
+/// - `Err` if the source isn't a drive or file, the rescued copy or map already exists, the
+///   rescue was aborted, or an image could not be written
+/// - `Ok` with the digests of the rescued data and the files written
+pub fn rescue<P, F>(
+    args: &ArgsList,
+    info: &LoggingInfo,
+    phase: P,
+    progress: F,
+    abort: &AtomicBool,
+) -> io::Result<AcquisitionResult>
+where
+    P: FnMut(Phase, u32),
+    F: FnMut(u64, u64),
+{
+    if let Some(path) = existing_rescue_file(args) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} already exists and may be from another source; move it out of the way",
+                path.display()
+            ),
+        ));
+    }
+    let job = JobFile::create(args, info)?;
+    rescue_job(job, ReadState::new(args), false, phase, progress, abort)
+}
+
+/// Run the rescue engine for a job, then write the rescued copy to every destination, carrying
+/// on from `state`. Only a job being resumed carries on from its map, which must be of the
+/// job's source.
+fn rescue_job<P, F>(
+    mut job: JobFile,
+    state: ReadState,
+    resuming: bool,
+    mut phase: P,
+    mut progress: F,
+    abort: &AtomicBool,
+) -> io::Result<AcquisitionResult>
+where
+    P: FnMut(Phase, u32),
+    F: FnMut(u64, u64),
+{
+    let args = job.state.args.clone();
+    let (copy_path, map_path) = rescue_paths(&args)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No destinations selected"))?;
+    let (device, start, size) = match open_source(&args)? {
+        (Source::Block { device, start }, size) => (device, start, size),
+        (Source::Stream { .. }, _) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Only a drive or file can be rescued",
+            ))
+        }
+    };
+
+    let source = job.state.source.label();
+    let resumed = resuming && map_path.exists();
+    let mut map = if resumed {
+        let map = RescueMap::load(&map_path)?;
+        if map.size() != size || map.source.as_deref() != Some(source.as_str()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "The rescue map {} is not of this job's source ({})",
+                    map_path.display(),
+                    source
+                ),
+            ));
+        }
+        map
+    } else {
+        let mut map = RescueMap::new(size);
+        map.source = Some(source);
+        map
+    };
+
+    let copy = rescue::open_output(&copy_path, size)?;
+    let mut current = None;
+    rescue::rescue(
+        &device,
+        start,
+        &copy,
+        &mut map,
+        &map_path,
+        &args.rescue_options(),
+        |map| {
+            if current != Some((map.phase, map.pass)) {
+                current = Some((map.phase, map.pass));
+                phase(map.phase, map.pass);
+            }
+            let finished = map.bytes_with(rescue::BlockStatus::Finished);
+            progress(finished, size);
+        },
+        abort,
+    )?;
+    copy.sync_all()?;
+    phase(Phase::Finished, 1);
+
+    let source = Source::Block {
+        device: File::open(&copy_path)?,
+        start: 0,
+    };
+    let (read, destinations, tuning) =
+        write_image(&args, (source, size), state, &mut job, progress, abort)?;
+    let sector_size = args.bytes_per_sector.unwrap_or(512) as u64;
+
+    Ok(AcquisitionResult {
+        source_size: read.size,
+        bytes_acquired: read.done,
+        digests: read.digests,
+        bad_sectors: map.unread_sectors(sector_size),
+        started: job.state.started,
+        finished: Local::now(),
+        destinations,
+        rescue: Some(RescueFiles {
+            copy: copy_path,
+            map: map_path,
+            resumed,
+        }),
+        resumptions: job.finish(),
+        tuning,
+    })
+}
+
+/// Rebuild the running digests of an interrupted job by reading back what was written to its
+/// first destination, and check them against the digests saved with the job.
+fn rebuild_read_state<F>(
+    state: &JobState,
+    progress: &mut F,
+    abort: &AtomicBool,
+) -> io::Result<ReadState>
+where
+    F: FnMut(u64, u64),
+{
+    let mut read = ReadState {
+        done: 0,
+        hasher: Hasher::new(state.args.digest_type),
+        bad_sectors: state.bad_sectors.clone(),
+    };
+    if state.bytes_done == 0 {
+        return Ok(read);
+    }
+
+    let mut image = SplitReader::new(state.segments.first().cloned().unwrap_or_default());
+    let mut buffer = vec![0_u8; state.args.chunk_size()];
+    while read.done < state.bytes_done {
+        if abort.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
+
+        let wanted = (state.bytes_done - read.done).min(buffer.len() as u64) as usize;
+        let got = read_full(&mut image, &mut buffer[..wanted])?;
+        if got == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The image is shorter than the job says was written",
+            ));
+        }
+        read.hasher.update(&buffer[..got]);
+        read.done += got as u64;
+        progress(read.done, state.bytes_done);
+    }
+
+    if !read.hasher.clone().finish().matches(&state.digests) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The image written so far doesn't match the digests saved with the job",
+        ));
+    }
+    Ok(read)
+}
+
+/// Carry on with an interrupted acquisition from the last point its job state was saved.
+///
+/// The digests can't be saved mid-stream, so the image written so far is read back to rebuild
+/// them, and checked against the digests saved with the job; then reading the source carries on
+/// where the job left off. A rescue resumes from its map.
+///
+/// # Arguments
+///
+/// * `path` - The job state file.
+/// * `state` - The job state loaded from `path`, with its source found again by `JobState::locate_source`.
+/// * `phase` - Called as `rescue` calls it for a rescue job; otherwise called with `Phase::Finished` once the image is being written.
+/// * `progress` - Called with the number of bytes done so far and the total, first while the image written so far is read back and then as `acquire` calls it.
+/// * `abort` - When set, the acquisition stops with `ErrorKind::Interrupted`; it can be resumed again later.
+///
+/// # Returns
+///
+/// - `Err` if the job can't be resumed, the source can't be found, the image written so far
+///   doesn't match the job, or the acquisition fails
+/// - `Ok` with the result of the whole acquisition
+pub fn resume<P, F>(
+    path: &Path,
+    mut state: JobState,
+    mut phase: P,
+    mut progress: F,
+    abort: &AtomicBool,
+) -> io::Result<AcquisitionResult>
+where
+    P: FnMut(Phase, u32),
+    F: FnMut(u64, u64),
+{
+    if !state.resumable() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "ewfacquirestream can't append to an EWF image, so this job can't be resumed",
+        ));
+    }
+    state.resumptions.push(Resumption {
+        at: Local::now(),
+        bytes_done: state.bytes_done,
+    });
+
+    // An EWF image can't be appended to, so it is written again from the rescued copy
+    if !state.args.ewf_format.is_raw() {
+        let filename = state.args.target_filename.clone().unwrap_or_default();
+        for dir in target_dirs(&state.args) {
+            EwfWriter::remove_partial(&dir, &filename, SystemTime::from(state.started))?;
+        }
+        state.bytes_done = 0;
+        state.digests = Digests::default();
+        state.segments = Vec::new();
+    }
+    state.save(path)?;
+
+    let read = rebuild_read_state(&state, &mut progress, abort)?;
+    let mut job = JobFile {
+        path: path.to_path_buf(),
+        state,
+        last_save: Instant::now(),
+    };
+    let args = job.state.args.clone();
+    if args.rescue {
+        return rescue_job(job, read, true, phase, progress, abort);
+    }
+
+    let source = open_source(&args)?;
+    phase(Phase::Finished, 1);
+    let (read, destinations, tuning) = write_image(&args, source, read, &mut job, progress, abort)?;
+
+    Ok(AcquisitionResult {
+        source_size: read.size,
+        bytes_acquired: read.done,
+        digests: read.digests,
+        bad_sectors: read.bad_sectors,
+        started: job.state.started,
+        finished: Local::now(),
+        destinations,
+        rescue: None,
+        resumptions: job.finish(),
+        tuning,
+    })
+}
+
+/// Read the source device described by `args` from start to end and hash it, without writing an image.
+///
+/// # Arguments
+///
+/// * `args` - The arguments describing the source and the digests to calculate.
+/// * `progress` - Called after every chunk with the number of bytes read so far and the size of the source.
+/// * `abort` - Checked after every chunk; when set, hashing stops with `ErrorKind::Interrupted`.
+///
+/// # Returns
+///
+/// - `Err` if the source could not be read or hashing was aborted
+/// - `Ok` with the digests of the source; the result has no destinations
+pub fn hash_source<F>(
+    args: &ArgsList,
+    progress: F,
+    abort: &AtomicBool,
+) -> io::Result<AcquisitionResult>
+where
+    F: FnMut(u64, u64),
+{
+    let started = Local::now();
+    let (source, size) = open_source(args)?;
+    let read = read_source(
+        args,
+        source,
+        size,
+        &mut WriterPool::new(Vec::new(), 1, |writer, data| writer.write_chunk(data)),
+        ReadState::new(args),
+        None,
+        progress,
+        abort,
+    )?;
+
+    Ok(AcquisitionResult {
+        source_size: read.size,
+        bytes_acquired: read.done,
+        digests: read.digests,
+        bad_sectors: read.bad_sectors,
+        started,
+        finished: Local::now(),
+        destinations: Vec::new(),
+        rescue: None,
+        resumptions: Vec::new(),
+        tuning: None,
+    })
+}
+
+/// Verify every copy of the image independently, recording the result in each `Destination`.
+///
+/// # Arguments
+///
+/// * `args` - The arguments the acquisition was run with.
+/// * `result` - The result of the acquisition to verify.
+/// * `progress` - Called with the index of the copy being verified, and its progress as done and total.
+/// * `abort` - When set, verification stops with `ErrorKind::Interrupted`.
+pub fn verify<F>(
+    args: &ArgsList,
+    result: &mut AcquisitionResult,
+    mut progress: F,
+    abort: &AtomicBool,
+) -> io::Result<()>
+where
+    F: FnMut(usize, u64, u64),
+{
+    for (index, destination) in result.destinations.iter_mut().enumerate() {
+        let verification = verify::verify_destination(
+            args,
+            destination,
+            &result.digests,
+            |done, total| progress(index, done, total),
+            abort,
+        )?;
+        destination.verification = Some(verification);
+    }
+    Ok(())
+}
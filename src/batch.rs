@@ -0,0 +1,202 @@
+use chrono::{DateTime, Local};
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use crate::acquire::{self, AcquisitionResult};
+use crate::ewfargs::ArgsList;
+use crate::job::JobState;
+use crate::report;
+use crate::rescue::Phase;
+use crate::LoggingInfo;
+
+/// A source queued for imaging as part of a batch, such as one of several drives from a seizure.
+#[derive(Clone)]
+pub struct BatchItem {
+    /// The arguments of the acquisition. Every item in a batch shares its case fields.
+    pub args: ArgsList,
+    /// The information about the source and host gathered when the item was queued.
+    pub info: LoggingInfo,
+    /// The result of imaging the source, once it has been run.
+    pub result: Option<Result<AcquisitionResult, String>>,
+}
+
+impl BatchItem {
+    /// Returns an item that hasn't been run yet.
+    pub fn new(args: ArgsList, info: LoggingInfo) -> BatchItem {
+        BatchItem {
+            args,
+            info,
+            result: None,
+        }
+    }
+}
+
+/// Returns what identifies where a destination directory is stored: its filesystem if it can be
+/// found, or else the directory itself.
+fn destination_key(dir: &str) -> String {
+    match fs::metadata(dir) {
+        Ok(metadata) => format!("dev:{}", metadata.dev()),
+        Err(_) => format!("dir:{}", dir),
+    }
+}
+
+/// Split a batch into lanes that can run in parallel. Sources that write to the same filesystem,
+/// or that are the same source, share a lane and are imaged one after the other, since running
+/// them together would only make them fight over the same disk.
+///
+/// # Returns
+///
+/// The indices of the items in each lane, in the order they were queued.
+pub fn lanes(items: &[ArgsList]) -> Vec<Vec<usize>> {
+    let keys: Vec<Vec<String>> = items
+        .iter()
+        .map(|args| {
+            let mut keys: Vec<String> = args
+                .target_dirs
+                .iter()
+                .map(|dir| destination_key(dir))
+                .collect();
+            keys.push(format!(
+                "source:{}",
+                args.source_device.clone().unwrap_or_default()
+            ));
+            keys
+        })
+        .collect();
+
+    let mut lanes: Vec<(Vec<usize>, Vec<String>)> = Vec::new();
+    for (index, item_keys) in keys.into_iter().enumerate() {
+        let mut lane = (vec![index], item_keys);
+        // Merge every lane this item shares a key with, which may join lanes that were separate
+        let mut remaining = Vec::new();
+        for other in lanes {
+            if other.1.iter().any(|key| lane.1.contains(key)) {
+                lane.0.extend(other.0);
+                lane.1.extend(other.1);
+            } else {
+                remaining.push(other);
+            }
+        }
+        lane.0.sort_unstable();
+        remaining.push(lane);
+        lanes = remaining;
+    }
+
+    let mut lanes: Vec<Vec<usize>> = lanes.into_iter().map(|(indices, _)| indices).collect();
+    lanes.sort_by_key(|lane| lane[0]);
+    lanes
+}
+
+/// Run an acquisition from start to finish: image the source, or carry on with an interrupted
+/// acquisition, verify every copy, and write the report next to each.
+///
+/// # Arguments
+///
+/// * `args` - The arguments of the acquisition.
+/// * `info` - The information about the source and host, for the report.
+/// * `resume` - The job state file and state of an interrupted acquisition to resume, if any.
+/// * `phase` - Called when a rescue moves on to another phase or pass.
+/// * `progress` - Called with the bytes read so far and the size of the source.
+/// * `verifying` - Called with the index of the copy being verified, the bytes verified so far
+///   and the size of the copy.
+/// * `abort` - Checked regularly; the acquisition stops with an error once it is set.
+///
+/// # Returns
+///
+/// - `Err` if the acquisition, the verification, or writing a report failed
+/// - `Ok` with the result of the acquisition, including the verification of every copy
+pub fn run_one<P, F, V>(
+    args: &ArgsList,
+    info: &LoggingInfo,
+    resume: Option<(PathBuf, JobState)>,
+    phase: P,
+    progress: F,
+    verifying: V,
+    abort: &AtomicBool,
+) -> io::Result<AcquisitionResult>
+where
+    P: FnMut(Phase, u32),
+    F: FnMut(u64, u64),
+    V: FnMut(usize, u64, u64),
+{
+    let mut result = match resume {
+        Some((path, state)) => acquire::resume(&path, state, phase, progress, abort),
+        None if args.rescue => acquire::rescue(args, info, phase, progress, abort),
+        None => acquire::acquire(args, info, progress, abort),
+    }?;
+
+    acquire::verify(args, &mut result, verifying, abort)?;
+
+    let text = report::acquisition_report(args, info, &result);
+    for destination in &result.destinations {
+        report::write_report(&report::report_path(&destination.dir, args), &text)?;
+    }
+    Ok(result)
+}
+
+/// Returns every directory any item in a batch is imaged to, in the order they first appear.
+fn report_dirs(items: &[BatchItem]) -> Vec<&String> {
+    let mut dirs: Vec<&String> = Vec::new();
+    for item in items {
+        for dir in &item.args.target_dirs {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs
+}
+
+/// Returns the case number a batch's case report is named after.
+fn case_number(items: &[BatchItem]) -> String {
+    items
+        .first()
+        .and_then(|item| item.args.case_number.clone())
+        .unwrap_or_default()
+}
+
+/// Returns the first report running a batch would write that already exists: the report of any
+/// of its items, or the case report of a batch started at `started`. Reports are never
+/// replaced, so the batch shouldn't be started while one of them is in the way.
+pub fn existing_report(items: &[BatchItem], started: DateTime<Local>) -> Option<PathBuf> {
+    let case_number = case_number(items);
+    items
+        .iter()
+        .find_map(|item| report::existing_report(&item.args))
+        .or_else(|| {
+            report_dirs(items)
+                .into_iter()
+                .map(|dir| report::case_report_path(Path::new(dir), &case_number, started))
+                .find(|path| path.exists())
+        })
+}
+
+/// Write the combined report for a batch to every directory any of its items was imaged to.
+///
+/// # Arguments
+///
+/// * `items` - Every item in the batch, with its result.
+/// * `started` - When the batch was started, which names the report.
+///
+/// # Returns
+///
+/// - `Err` if a report couldn't be written
+/// - `Ok` with the paths the report was written to
+pub fn write_case_report(
+    items: &[BatchItem],
+    started: DateTime<Local>,
+) -> io::Result<Vec<PathBuf>> {
+    let case_number = case_number(items);
+    let text = report::case_report(items, started);
+
+    let mut written = Vec::new();
+    for dir in report_dirs(items) {
+        let path = report::case_report_path(Path::new(dir), &case_number, started);
+        report::write_report(&path, &text)?;
+        written.push(path);
+    }
+    Ok(written)
+}
@@ -0,0 +1,384 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+
+use crate::compare::{push_range, SectorRange};
+
+/// A source that can be read at any offset, such as a drive or a file.
+pub trait BlockDevice {
+    /// Read into `buf` starting at `offset` bytes into the device, returning the number of bytes
+    /// read. A short read only happens at the end of the device.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+}
+
+impl BlockDevice for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match FileExt::read_at(self, &mut buf[filled..], offset + filled as u64) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(filled)
+    }
+}
+
+/// A device backed by a file that fails to read certain sectors, for exercising read error
+/// handling and the rescue engine without a failing drive.
+pub struct FaultyDevice {
+    file: File,
+    sector_size: u64,
+    bad_sectors: Vec<SectorRange>,
+    reads: Cell<u64>,
+}
+
+impl FaultyDevice {
+    /// Returns a device that reads `file` but fails any read touching a sector in `bad_sectors`.
+    pub fn new(file: File, sector_size: u64, bad_sectors: Vec<SectorRange>) -> FaultyDevice {
+        FaultyDevice {
+            file,
+            sector_size: sector_size.max(1),
+            bad_sectors,
+            reads: Cell::new(0),
+        }
+    }
+
+    /// Returns how many reads have been attempted, including failed ones.
+    pub fn reads(&self) -> u64 {
+        self.reads.get()
+    }
+}
+
+impl BlockDevice for FaultyDevice {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.reads.set(self.reads.get() + 1);
+        if !buf.is_empty() {
+            let first = offset / self.sector_size;
+            let last = (offset + buf.len() as u64 - 1) / self.sector_size;
+            if self
+                .bad_sectors
+                .iter()
+                .any(|range| range.start <= last && range.end >= first)
+            {
+                return Err(io::Error::from_raw_os_error(5));
+            }
+        }
+        BlockDevice::read_at(&self.file, buf, offset)
+    }
+}
+
+/// How to handle sectors that can't be read.
+#[derive(Clone, Copy)]
+pub struct ErrorPolicy {
+    /// The size of a sector in bytes.
+    pub sector_size: u64,
+    /// How many times to retry a failed read before giving up on it.
+    pub retries: u32,
+    /// How many sectors to give up on at once when a read fails.
+    pub granularity: u64,
+    /// When set, the whole granularity block around an error is zeroed, as EnCase does. Otherwise
+    /// the block is re-read sector by sector, so only the unreadable sectors are zeroed.
+    pub zero_block: bool,
+}
+
+/// Read with `retries` retries, returning the last error if every attempt fails.
+fn read_retrying<D: BlockDevice + ?Sized>(
+    device: &D,
+    buf: &mut [u8],
+    offset: u64,
+    retries: u32,
+) -> io::Result<usize> {
+    let mut attempt = 0;
+    loop {
+        match device.read_at(buf, offset) {
+            Ok(read) => return Ok(read),
+            Err(e) if attempt >= retries => return Err(e),
+            Err(_) => attempt += 1,
+        }
+    }
+}
+
+/// Read a block of the source, tolerating read errors according to `policy`.
+///
+/// Unreadable sectors are zeroed in `buf` and added to `bad_sectors`, numbered from `base`, the
+/// offset the sector numbers count from.
+///
+/// # Arguments
+///
+/// * `device` - The source to read.
+/// * `buf` - Where to read to; its length is the amount to read.
+/// * `offset` - The offset in bytes to read from.
+/// * `base` - The offset in bytes of sector 0, for numbering bad sectors.
+/// * `policy` - How to handle read errors.
+/// * `bad_sectors` - The sorted list of unreadable sectors, added to as errors are found.
+///
+/// # Returns
+///
+/// The number of bytes in `buf` that are part of the source, including zeroed sectors. This
+/// is only less than `buf.len()` at the end of the source.
+pub fn read_tolerant<D: BlockDevice + ?Sized>(
+    device: &D,
+    buf: &mut [u8],
+    offset: u64,
+    base: u64,
+    policy: &ErrorPolicy,
+    bad_sectors: &mut Vec<SectorRange>,
+) -> io::Result<usize> {
+    if let Ok(read) = read_retrying(device, buf, offset, policy.retries) {
+        return Ok(read);
+    }
+
+    let sector_size = policy.sector_size.max(1);
+    let block_size = (policy.granularity.max(1) * sector_size) as usize;
+    let mut filled = 0;
+
+    for block in buf.chunks_mut(block_size) {
+        let block_offset = offset + filled as u64;
+        let read = match read_retrying(device, block, block_offset, policy.retries) {
+            Ok(read) => read,
+            Err(_) if policy.zero_block => {
+                zero(block, block_offset, base, sector_size, bad_sectors);
+                block.len()
+            }
+            Err(_) => {
+                let mut read = 0;
+                for sector in block.chunks_mut(sector_size as usize) {
+                    let sector_offset = block_offset + read as u64;
+                    match read_retrying(device, sector, sector_offset, policy.retries) {
+                        Ok(0) => break,
+                        Ok(count) => read += count,
+                        Err(_) => {
+                            zero(sector, sector_offset, base, sector_size, bad_sectors);
+                            read += sector.len();
+                        }
+                    }
+                }
+                read
+            }
+        };
+
+        filled += read;
+        if read < block.len() {
+            break;
+        }
+    }
+
+    Ok(filled)
+}
+
+/// Zero an unreadable part of a block and record its sectors as bad.
+fn zero(
+    data: &mut [u8],
+    offset: u64,
+    base: u64,
+    sector_size: u64,
+    bad_sectors: &mut Vec<SectorRange>,
+) {
+    for byte in data.iter_mut() {
+        *byte = 0;
+    }
+    let first = (offset - base) / sector_size;
+    let last = (offset - base + data.len() as u64 - 1) / sector_size;
+    push_range(bad_sectors, first, last);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{faulty_device, TempDir};
+    use std::path::PathBuf;
+
+    const SECTOR: u64 = 512;
+
+    /// Returns a temporary directory holding a source of `sectors` sectors, and its path.
+    fn temp_source(name: &str, sectors: u64) -> (TempDir, PathBuf) {
+        let dir = TempDir::new(&format!("block-{}", name));
+        let path = dir.numbered_sectors("source", sectors, SECTOR);
+        (dir, path)
+    }
+
+    fn policy(retries: u32, granularity: u64, zero_block: bool) -> ErrorPolicy {
+        ErrorPolicy {
+            sector_size: SECTOR,
+            retries,
+            granularity,
+            zero_block,
+        }
+    }
+
+    /// Returns the ranges as pairs, for comparing.
+    fn pairs(ranges: &[SectorRange]) -> Vec<(u64, u64)> {
+        ranges
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    /// Returns the value each sector of `buf` is filled with, or `None` if it is mixed.
+    fn sectors(buf: &[u8]) -> Vec<Option<u8>> {
+        buf.chunks(SECTOR as usize)
+            .map(|sector| Some(sector[0]).filter(|first| sector.iter().all(|b| b == first)))
+            .collect()
+    }
+
+    #[test]
+    fn faulty_device_fails_only_reads_touching_bad_sectors() {
+        let (_dir, source) = temp_source("faulty", 8);
+        let device = faulty_device(&source, SECTOR, &[(3, 3)]);
+        let mut buf = vec![0_u8; 2 * SECTOR as usize];
+
+        assert_eq!(device.read_at(&mut buf, 0).unwrap(), buf.len());
+        assert_eq!(sectors(&buf), vec![Some(1), Some(2)]);
+        assert_eq!(
+            device
+                .read_at(&mut buf, 2 * SECTOR)
+                .unwrap_err()
+                .raw_os_error(),
+            Some(5)
+        );
+        assert!(device.read_at(&mut buf, 3 * SECTOR + 100).is_err());
+        assert!(device.read_at(&mut buf, 4 * SECTOR).is_ok());
+        assert_eq!(device.reads(), 4);
+    }
+
+    #[test]
+    fn reads_without_errors_and_stops_at_the_end() {
+        let (_dir, source) = temp_source("clean", 10);
+        let device = faulty_device(&source, SECTOR, &[]);
+        let mut buf = vec![0_u8; 16 * SECTOR as usize];
+        let mut bad = Vec::new();
+
+        let read = read_tolerant(&device, &mut buf, 0, 0, &policy(2, 4, false), &mut bad).unwrap();
+        assert_eq!(read, 10 * SECTOR as usize);
+        assert_eq!(sectors(&buf[..read])[9], Some(10));
+        assert!(bad.is_empty());
+        assert_eq!(device.reads(), 1);
+    }
+
+    #[test]
+    fn zeroes_only_unreadable_sectors() {
+        let (_dir, source) = temp_source("sectors", 8);
+        let device = faulty_device(&source, SECTOR, &[(5, 5)]);
+        let mut buf = vec![0xff_u8; 8 * SECTOR as usize];
+        let mut bad = Vec::new();
+
+        let read = read_tolerant(&device, &mut buf, 0, 0, &policy(0, 4, false), &mut bad).unwrap();
+        assert_eq!(read, buf.len());
+        assert_eq!(
+            sectors(&buf),
+            vec![
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4),
+                Some(5),
+                Some(0),
+                Some(7),
+                Some(8)
+            ]
+        );
+        assert_eq!(pairs(&bad), vec![(5, 5)]);
+    }
+
+    #[test]
+    fn zeroes_the_whole_granularity_block() {
+        let (_dir, source) = temp_source("block", 8);
+        let device = faulty_device(&source, SECTOR, &[(5, 5)]);
+        let mut buf = vec![0xff_u8; 8 * SECTOR as usize];
+        let mut bad = Vec::new();
+
+        read_tolerant(&device, &mut buf, 0, 0, &policy(0, 4, true), &mut bad).unwrap();
+        assert_eq!(
+            sectors(&buf),
+            vec![
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4),
+                Some(0),
+                Some(0),
+                Some(0),
+                Some(0)
+            ]
+        );
+        assert_eq!(pairs(&bad), vec![(4, 7)]);
+
+        // With a granularity of two, only the pair holding the bad sector is lost
+        let mut bad = Vec::new();
+        read_tolerant(&device, &mut buf, 0, 0, &policy(0, 2, true), &mut bad).unwrap();
+        assert_eq!(pairs(&bad), vec![(4, 5)]);
+        assert_eq!(sectors(&buf)[6], Some(7));
+    }
+
+    #[test]
+    fn retries_every_failed_read() {
+        let (_dir, source) = temp_source("retries", 8);
+
+        // The whole buffer, then the good block, then the bad block
+        let device = faulty_device(&source, SECTOR, &[(5, 5)]);
+        let mut buf = vec![0_u8; 8 * SECTOR as usize];
+        read_tolerant(
+            &device,
+            &mut buf,
+            0,
+            0,
+            &policy(0, 4, true),
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(device.reads(), 3);
+
+        // Every failed read is tried three times
+        let device = faulty_device(&source, SECTOR, &[(5, 5)]);
+        read_tolerant(
+            &device,
+            &mut buf,
+            0,
+            0,
+            &policy(2, 4, true),
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(device.reads(), 3 + 1 + 3);
+
+        // Re-reading the bad block sector by sector retries only the bad sector
+        let device = faulty_device(&source, SECTOR, &[(5, 5)]);
+        read_tolerant(
+            &device,
+            &mut buf,
+            0,
+            0,
+            &policy(2, 4, false),
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert_eq!(device.reads(), 3 + 1 + 3 + 1 + 3 + 2);
+    }
+
+    #[test]
+    fn numbers_bad_sectors_from_the_base() {
+        let (_dir, source) = temp_source("base", 16);
+        let device = faulty_device(&source, SECTOR, &[(9, 10)]);
+        let mut buf = vec![0_u8; 8 * SECTOR as usize];
+        let mut bad = Vec::new();
+
+        read_tolerant(
+            &device,
+            &mut buf,
+            6 * SECTOR,
+            4 * SECTOR,
+            &policy(0, 1, false),
+            &mut bad,
+        )
+        .unwrap();
+        assert_eq!(pairs(&bad), vec![(5, 6)]);
+        assert_eq!(sectors(&buf)[2], Some(9));
+        assert_eq!(sectors(&buf)[3], Some(0));
+        assert_eq!(sectors(&buf)[4], Some(0));
+        assert_eq!(sectors(&buf)[5], Some(12));
+    }
+}
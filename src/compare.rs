@@ -0,0 +1,145 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::acquire::AcquisitionResult;
+use crate::hash::Digests;
+use crate::image::read_full;
+
+/// A range of sectors, from `start` to `end` inclusive.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SectorRange {
+    /// The first sector in the range.
+    pub start: u64,
+    /// The last sector in the range.
+    pub end: u64,
+}
+
+impl SectorRange {
+    /// Returns the number of sectors in the range.
+    pub fn count(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+impl fmt::Display for SectorRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} - {} ({} sectors)",
+            self.start,
+            self.end,
+            self.count()
+        )
+    }
+}
+
+/// Add the sectors from `start` to `end` inclusive to a sorted list of ranges, merging them into
+/// the last range if they are adjacent to it.
+pub fn push_range(ranges: &mut Vec<SectorRange>, start: u64, end: u64) {
+    if let Some(last) = ranges.last_mut() {
+        if last.end + 1 >= start {
+            last.end = last.end.max(end);
+            return;
+        }
+    }
+    ranges.push(SectorRange { start, end });
+}
+
+/// The result of comparing a drive against an existing image.
+#[derive(Clone)]
+pub struct Comparison {
+    /// The first segment of the image compared against.
+    pub image: PathBuf,
+    /// The digests stored in the image.
+    pub stored: Digests,
+    /// The result of hashing the drive.
+    pub drive: AcquisitionResult,
+    /// The sectors that differ between the drive and the image, if they were compared.
+    pub differing: Option<Vec<SectorRange>>,
+}
+
+impl Comparison {
+    /// Returns true if the drive's digests match those stored in the image.
+    pub fn matches(&self) -> bool {
+        self.drive.digests.matches(&self.stored)
+    }
+}
+
+/// Compare two streams chunk by chunk, returning the sectors that differ. If one stream is longer
+/// than the other, every sector past the end of the shorter one differs.
+///
+/// # Arguments
+///
+/// * `a` - The first stream, usually the drive.
+/// * `b` - The second stream, usually the image's media data.
+/// * `bytes_per_sector` - The sector size used to report differing ranges.
+/// * `chunk_size` - How much to read from each stream at once; rounded up to a whole number of sectors.
+/// * `total` - The expected size of the streams, used only for `progress`.
+/// * `progress` - Called after every chunk with the bytes compared so far and `total`.
+/// * `abort` - When set, comparing stops and `ErrorKind::Interrupted` is returned.
+pub fn differing_ranges<A, B, F>(
+    a: &mut A,
+    b: &mut B,
+    bytes_per_sector: u64,
+    chunk_size: usize,
+    total: u64,
+    mut progress: F,
+    abort: &AtomicBool,
+) -> io::Result<Vec<SectorRange>>
+where
+    A: Read + ?Sized,
+    B: Read + ?Sized,
+    F: FnMut(u64, u64),
+{
+    let bytes_per_sector = bytes_per_sector.max(1);
+    let sector = bytes_per_sector as usize;
+    let chunk_size = chunk_size.max(1).div_ceil(sector) * sector;
+
+    let mut buffer_a = vec![0_u8; chunk_size];
+    let mut buffer_b = vec![0_u8; chunk_size];
+    let mut ranges = Vec::new();
+    let mut offset = 0_u64;
+
+    loop {
+        if abort.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
+
+        let read_a = read_full(a, &mut buffer_a)?;
+        let read_b = read_full(b, &mut buffer_b)?;
+        if read_a == 0 && read_b == 0 {
+            break;
+        }
+
+        // Only look at individual sectors once we know the chunk differs
+        let common = read_a.min(read_b);
+        if buffer_a[..common] != buffer_b[..common] {
+            for (index, (sector_a, sector_b)) in buffer_a[..common]
+                .chunks(sector)
+                .zip(buffer_b[..common].chunks(sector))
+                .enumerate()
+            {
+                if sector_a != sector_b {
+                    let number = offset / bytes_per_sector + index as u64;
+                    push_range(&mut ranges, number, number);
+                }
+            }
+        }
+
+        let longest = read_a.max(read_b);
+        if common < longest {
+            push_range(
+                &mut ranges,
+                (offset + common as u64) / bytes_per_sector,
+                (offset + longest as u64 - 1) / bytes_per_sector,
+            );
+        }
+
+        offset += longest as u64;
+        progress(offset, total);
+    }
+
+    Ok(ranges)
+}
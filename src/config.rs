@@ -0,0 +1,469 @@
+use chrono::Local;
+use json::{self, JsonValue};
+use regex::Regex;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::ewfargs::{ArgsList, CompressionType, DigestType, EwfFormat};
+use crate::template;
+use crate::LoggingInfo;
+
+/// Where the configuration is read from, unless the `GRIM_CONFIG` environment variable names
+/// another file.
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/grim/config.json";
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Returns an optional string setting.
+fn string(value: &JsonValue, name: &str) -> io::Result<Option<String>> {
+    match value {
+        JsonValue::Null => Ok(None),
+        value => value
+            .as_str()
+            .map(|value| Some(value.to_string()))
+            .ok_or_else(|| invalid(&format!("{} must be a string", name))),
+    }
+}
+
+/// The fields of the examiner information form that rules can be set for: their names in the
+/// configuration file, and their labels.
+pub const EXAMINER_FIELDS: &[(&str, &str)] = &[
+    ("examiner_name", "Examiner Name"),
+    ("case_number", "Case Number"),
+    ("evidence_number", "Evidence Number"),
+    ("description", "Description"),
+    ("notes", "Notes"),
+];
+
+/// Returns the value of the examiner information field `name` in `args`.
+fn examiner_field<'a>(args: &'a ArgsList, name: &str) -> Option<&'a str> {
+    match name {
+        "examiner_name" => args.examiner_name.as_deref(),
+        "case_number" => args.case_number.as_deref(),
+        "evidence_number" => args.evidence_number.as_deref(),
+        "description" => args.description.as_deref(),
+        _ => args.notes.as_deref(),
+    }
+}
+
+/// Returns an optional number setting.
+fn number(value: &JsonValue, name: &str) -> io::Result<Option<usize>> {
+    match value {
+        JsonValue::Null => Ok(None),
+        value => value
+            .as_usize()
+            .map(Some)
+            .ok_or_else(|| invalid(&format!("{} must be a whole number", name))),
+    }
+}
+
+/// The rules an agency sets for the value of a field, such as the format of its case numbers.
+#[derive(Clone, Default)]
+pub struct FieldRule {
+    /// The pattern the whole value must match.
+    pub pattern: Option<Regex>,
+    /// The most characters the value may have.
+    pub max_length: Option<usize>,
+    /// Describes what the value should look like, shown under the field and when the value is
+    /// rejected.
+    pub help: Option<String>,
+}
+
+impl FieldRule {
+    /// Parse a rule from the settings of a field.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The settings of the field.
+    /// * `prefix` - Where the settings are in the configuration, for error messages.
+    fn parse(value: &JsonValue, prefix: &str) -> io::Result<FieldRule> {
+        let setting = |setting: &str| format!("{}.{}", prefix, setting);
+        let pattern = match string(&value["pattern"], &setting("pattern"))? {
+            // The pattern has to match the whole value, not just part of it
+            Some(pattern) => Some(Regex::new(&format!("^(?:{})$", pattern)).map_err(|e| {
+                invalid(&format!(
+                    "{} is not a valid pattern: {}",
+                    setting("pattern"),
+                    e
+                ))
+            })?),
+            None => None,
+        };
+        Ok(FieldRule {
+            pattern,
+            max_length: number(&value["max_length"], &setting("max_length"))?,
+            help: string(&value["help"], &setting("help"))?,
+        })
+    }
+
+    /// Check a value against the rule. Empty values are left for the form to accept or reject,
+    /// since whether a field is required is not up to the rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - The label of the field, for the message.
+    /// * `value` - The value to check.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` with a message saying what the value should look like
+    /// - `Ok` if the value follows the rule
+    pub fn check(&self, label: &str, value: &str) -> Result<(), String> {
+        if value.is_empty() {
+            return Ok(());
+        }
+
+        let expected = match (&self.help, &self.pattern) {
+            (Some(help), _) => format!("\n\nExpected: {}", help),
+            (None, Some(pattern)) => {
+                // Show the pattern as it was configured, without what `parse` wrapped it in
+                let pattern = pattern.as_str();
+                let pattern = pattern[4..pattern.len() - 2].to_string();
+                format!("\n\nExpected a value matching {}", pattern)
+            }
+            (None, None) => String::new(),
+        };
+        if let Some(max_length) = self.max_length {
+            if value.chars().count() > max_length {
+                return Err(format!(
+                    "{} can be at most {} characters long.{}",
+                    label, max_length, expected
+                ));
+            }
+        }
+        if let Some(ref pattern) = self.pattern {
+            if !pattern.is_match(value) {
+                return Err(format!(
+                    "{} \"{}\" is not in the expected format.{}",
+                    label, value, expected
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A field an agency adds to the examiner information form, such as the seizure location or the
+/// exhibit bag number.
+#[derive(Clone)]
+pub struct CustomField {
+    /// The label of the field in the form and the report.
+    pub label: String,
+    /// Whether the field has to be filled in.
+    pub required: bool,
+    /// The rule the value must follow.
+    pub rule: FieldRule,
+}
+
+impl CustomField {
+    /// Parse the custom field at `index` in the configuration.
+    fn parse(value: &JsonValue, index: usize) -> io::Result<CustomField> {
+        let prefix = format!("custom_fields[{}]", index);
+        let label = string(&value["label"], &format!("{}.label", prefix))?
+            .filter(|label| !label.trim().is_empty())
+            .ok_or_else(|| invalid(&format!("{}.label must be set", prefix)))?;
+        let required = match value["required"] {
+            JsonValue::Null => false,
+            ref value => value
+                .as_bool()
+                .ok_or_else(|| invalid(&format!("{}.required must be true or false", prefix)))?,
+        };
+        Ok(CustomField {
+            label,
+            required,
+            rule: FieldRule::parse(value, &prefix)?,
+        })
+    }
+}
+
+/// An examiner's usual settings, chosen at the start of the imaging wizard so they don't have to
+/// be entered for every drive. Settings a profile leaves out keep their defaults.
+#[derive(Clone, Default)]
+pub struct Profile {
+    /// The name the profile is chosen by.
+    pub name: String,
+    /// The examiner's name.
+    pub examiner_name: Option<String>,
+    /// The directories to write a copy of each image to.
+    pub target_dirs: Vec<String>,
+    /// The image format.
+    pub ewf_format: Option<EwfFormat>,
+    /// The compression type.
+    pub compression_type: Option<CompressionType>,
+    /// The digests to calculate besides MD5.
+    pub digest_type: Option<DigestType>,
+    /// The size of each segment, such as `2GiB`.
+    pub segment_file_size: Option<String>,
+}
+
+impl Profile {
+    /// Parse the profile at `index` in the configuration.
+    fn parse(value: &JsonValue, index: usize) -> io::Result<Profile> {
+        let setting = |setting: &str| format!("profiles[{}].{}", index, setting);
+
+        let name = string(&value["name"], &setting("name"))?
+            .filter(|name| !name.trim().is_empty())
+            .ok_or_else(|| invalid(&format!("{} must be set", setting("name"))))?;
+
+        let mut target_dirs = Vec::new();
+        for dir in value["target_dirs"].members() {
+            target_dirs.push(
+                dir.as_str().map(String::from).ok_or_else(|| {
+                    invalid(&format!("{} must be strings", setting("target_dirs")))
+                })?,
+            );
+        }
+
+        let ewf_format = match string(&value["format"], &setting("format"))? {
+            Some(format) => Some(EwfFormat::from_arg(&format).ok_or_else(|| {
+                invalid(&format!("{} is not a format grim knows", setting("format")))
+            })?),
+            None => None,
+        };
+        let compression_type = match string(&value["compression"], &setting("compression"))? {
+            Some(compression) => {
+                Some(CompressionType::from_arg(&compression).ok_or_else(|| {
+                    invalid(&format!(
+                        "{} must be none, empty-block, fast or best",
+                        setting("compression")
+                    ))
+                })?)
+            }
+            None => None,
+        };
+
+        let digest_type = if value["digests"].is_null() {
+            None
+        } else {
+            let mut digest_type = DigestType::default();
+            for digest in value["digests"].members() {
+                match digest.as_str() {
+                    Some("md5") => (),
+                    Some("sha1") => digest_type |= DigestType::SHA1,
+                    Some("sha256") => digest_type |= DigestType::SHA256,
+                    _ => {
+                        return Err(invalid(&format!(
+                            "{} can only contain md5, sha1 and sha256",
+                            setting("digests")
+                        )))
+                    }
+                }
+            }
+            Some(digest_type)
+        };
+
+        let segment_file_size = string(&value["segment_size"], &setting("segment_size"))?;
+        if let Some(ref size) = segment_file_size {
+            convert_byte_size_string::convert_to_bytes(size).map_err(|_| {
+                invalid(&format!("{} is not a valid size", setting("segment_size")))
+            })?;
+        }
+
+        Ok(Profile {
+            name,
+            examiner_name: string(&value["examiner_name"], &setting("examiner_name"))?,
+            target_dirs,
+            ewf_format,
+            compression_type,
+            digest_type,
+            segment_file_size,
+        })
+    }
+
+    /// Fill in the settings of an acquisition from the profile. Settings the profile doesn't have
+    /// are left as they are.
+    pub fn apply(&self, args: &mut ArgsList) {
+        if self.examiner_name.is_some() {
+            args.examiner_name = self.examiner_name.clone();
+        }
+        if !self.target_dirs.is_empty() {
+            args.target_dirs = self.target_dirs.clone();
+        }
+        if let Some(ewf_format) = self.ewf_format {
+            args.ewf_format = ewf_format;
+        }
+        if let Some(compression_type) = self.compression_type {
+            args.compression_type = compression_type;
+        }
+        if let Some(digest_type) = self.digest_type {
+            args.digest_type = digest_type;
+        }
+        if self.segment_file_size.is_some() {
+            args.segment_file_size = self.segment_file_size.clone();
+        }
+    }
+}
+
+/// Returns the evidence number after `last`, by adding one to the number it ends with and keeping
+/// any leading zeros, such as `EV-010` after `EV-009`.
+///
+/// # Returns
+///
+/// `None` if `last` doesn't end with a number.
+pub fn next_evidence_number(last: &str) -> Option<String> {
+    let digits = last.len() - last.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return None;
+    }
+    let (prefix, number) = last.split_at(last.len() - digits);
+    let next = number.parse::<u128>().ok()? + 1;
+    Some(format!("{}{:0width$}", prefix, next, width = digits))
+}
+
+/// The settings read from the configuration file, so that common settings don't have to be
+/// entered for every acquisition.
+#[derive(Clone, Default)]
+pub struct Config {
+    /// The template used for the filename of an image unless the examiner types another, such as
+    /// `{case}_{evidence}_{serial}_{date}`.
+    pub filename_template: Option<String>,
+    /// The rules for the fields of the examiner information form, by their names in
+    /// `EXAMINER_FIELDS`.
+    pub fields: HashMap<String, FieldRule>,
+    /// Whether each acquisition's evidence number is filled in with the number after the last one
+    /// entered since grim started.
+    pub auto_increment_evidence: bool,
+    /// The fields added to the examiner information form, in the order they are shown.
+    pub custom_fields: Vec<CustomField>,
+    /// The examiner profiles to choose from when imaging, in the order they are listed.
+    pub profiles: Vec<Profile>,
+}
+
+impl Config {
+    /// Returns the path of the configuration file.
+    pub fn path() -> PathBuf {
+        env::var_os("GRIM_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH))
+    }
+
+    /// Parse a configuration file. Settings that are missing keep their defaults.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` with `ErrorKind::InvalidData` describing the first setting that isn't valid
+    /// - `Ok` with the configuration
+    pub fn parse(text: &str) -> io::Result<Config> {
+        let value =
+            json::parse(text).map_err(|e| invalid(&format!("Invalid configuration: {}", e)))?;
+        if !value.is_object() {
+            return Err(invalid("The configuration must be a JSON object"));
+        }
+
+        let filename_template = string(&value["filename_template"], "filename_template")?;
+        if let Some(ref filename_template) = filename_template {
+            template::expand(
+                filename_template,
+                &ArgsList::new(),
+                &LoggingInfo::new(),
+                Local::now(),
+            )
+            .map_err(|e| invalid(&format!("filename_template: {}", e)))?;
+        }
+
+        let mut fields = HashMap::new();
+        for (name, rule) in value["fields"].entries() {
+            if !EXAMINER_FIELDS.iter().any(|&(field, _)| field == name) {
+                return Err(invalid(&format!(
+                    "fields.{} is not a field rules can be set for",
+                    name
+                )));
+            }
+            fields.insert(
+                name.to_string(),
+                FieldRule::parse(rule, &format!("fields.{}", name))?,
+            );
+        }
+
+        let auto_increment_evidence = match value["auto_increment_evidence"] {
+            JsonValue::Null => false,
+            ref value => value
+                .as_bool()
+                .ok_or_else(|| invalid("auto_increment_evidence must be true or false"))?,
+        };
+
+        let mut custom_fields: Vec<CustomField> = Vec::new();
+        for (index, field) in value["custom_fields"].members().enumerate() {
+            let field = CustomField::parse(field, index)?;
+            if custom_fields
+                .iter()
+                .any(|other| other.label.eq_ignore_ascii_case(&field.label))
+            {
+                return Err(invalid(&format!(
+                    "There is more than one custom field labelled {}",
+                    field.label
+                )));
+            }
+            custom_fields.push(field);
+        }
+
+        let mut profiles: Vec<Profile> = Vec::new();
+        for (index, profile) in value["profiles"].members().enumerate() {
+            let profile = Profile::parse(profile, index)?;
+            if profiles.iter().any(|other| other.name == profile.name) {
+                return Err(invalid(&format!(
+                    "There is more than one profile named {}",
+                    profile.name
+                )));
+            }
+            profiles.push(profile);
+        }
+
+        Ok(Config {
+            filename_template,
+            fields,
+            auto_increment_evidence,
+            custom_fields,
+            profiles,
+        })
+    }
+
+    /// Returns the rule for the field `name`, if one is set.
+    pub fn field_rule(&self, name: &str) -> Option<&FieldRule> {
+        self.fields.get(name)
+    }
+
+    /// Check the examiner information and custom fields in `args` against the rules set for them.
+    /// Fields left empty are not checked, since whether they have to be filled in is up to the
+    /// form.
+    ///
+    /// # Returns
+    ///
+    /// A message for each value that doesn't follow its rule, in the order the fields are shown.
+    pub fn field_problems(&self, args: &ArgsList) -> Vec<String> {
+        let examiner_fields = EXAMINER_FIELDS.iter().filter_map(|&(name, label)| {
+            let rule = self.field_rule(name)?;
+            rule.check(label, examiner_field(args, name).unwrap_or_default())
+                .err()
+        });
+        let custom_fields = self.custom_fields.iter().filter_map(|field| {
+            let value = args
+                .custom_fields
+                .iter()
+                .find(|(label, _)| *label == field.label)
+                .map(|(_, value)| value.as_str())
+                .unwrap_or_default();
+            field.rule.check(&field.label, value).err()
+        });
+        examiner_fields.chain(custom_fields).collect()
+    }
+
+    /// Read the configuration file. A missing file is not an error; the defaults are used.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` if the file exists but can't be read or isn't valid
+    /// - `Ok` with the configuration
+    pub fn load() -> io::Result<Config> {
+        match fs::read_to_string(Config::path()) {
+            Ok(text) => Config::parse(&text),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use crate::compare::{push_range, SectorRange};
+use crate::hash::Digests;
+
+/// A section of ewfinfo output, such as "Acquiry information", and its fields.
+#[derive(Clone, Default)]
+pub struct Section {
+    /// The heading of the section.
+    pub heading: String,
+    /// The fields in the section, as label and value, in the order ewfinfo printed them.
+    pub fields: Vec<(String, String)>,
+}
+
+/// The information ewfinfo reported about an EWF image.
+#[derive(Clone, Default)]
+pub struct EwfInfo {
+    /// Every section ewfinfo printed, in order.
+    pub sections: Vec<Section>,
+}
+
+impl EwfInfo {
+    /// Returns the value of the first field with the given label in the given section.
+    pub fn get(&self, heading: &str, label: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .filter(|section| section.heading == heading)
+            .flat_map(|section| section.fields.iter())
+            .find(|(field, _)| field == label)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the digests stored in the image, or `None` if no MD5 is stored.
+    pub fn digests(&self) -> Option<Digests> {
+        let heading = "Digest hash information";
+        let hash = |label| {
+            self.get(heading, label)
+                .filter(|value| !value.is_empty() && *value != "N/A")
+                .map(str::to_lowercase)
+        };
+
+        hash("MD5").map(|md5| Digests {
+            md5,
+            sha1: hash("SHA1"),
+            sha256: hash("SHA256"),
+        })
+    }
+
+    /// Returns the number of bytes per sector in the image, if ewfinfo reported it.
+    pub fn bytes_per_sector(&self) -> Option<u64> {
+        self.get("Media information", "Bytes per sector")
+            .and_then(|value| value.parse().ok())
+    }
+
+    /// Returns the size of the image's media data in bytes, if ewfinfo reported it.
+    ///
+    /// ewfinfo reports it as `Media size: 1.0 GiB (1073741824 bytes)`.
+    pub fn media_size(&self) -> Option<u64> {
+        let value = self.get("Media information", "Media size")?;
+        let bytes = value.rsplit('(').next()?.trim_end_matches(')');
+        bytes.trim_end_matches("bytes").trim().parse().ok()
+    }
+
+    /// Returns the sectors that could not be read while the image was acquired.
+    ///
+    /// ewfinfo lists them as `at sector(s): 0 - 63 number: 64` under "Read errors during acquiry".
+    pub fn error_ranges(&self) -> Vec<SectorRange> {
+        let mut ranges = Vec::new();
+        for (label, value) in self
+            .sections
+            .iter()
+            .filter(|section| section.heading.starts_with("Read errors"))
+            .flat_map(|section| section.fields.iter())
+        {
+            if label != "at sector(s)" {
+                continue;
+            }
+            let sectors = value.split("number").next().unwrap_or_default();
+            let mut bounds = sectors.split('-').map(|bound| bound.trim().parse::<u64>());
+            if let (Some(Ok(start)), Some(Ok(end))) = (bounds.next(), bounds.next()) {
+                push_range(&mut ranges, start, end);
+            }
+        }
+        ranges
+    }
+}
+
+/// Parse the output of ewfinfo.
+///
+/// Headings are unindented lines; fields are indented `label: value` lines belonging to the
+/// heading above them. Anything before the first heading, such as the version banner, is ignored.
+pub fn parse_ewfinfo_output(output: &str) -> EwfInfo {
+    let mut info = EwfInfo::default();
+
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if !line.starts_with(char::is_whitespace) {
+            if !line.starts_with("ewfinfo") {
+                info.sections.push(Section {
+                    heading: line.trim().trim_end_matches(':').to_string(),
+                    fields: Vec::new(),
+                });
+            }
+            continue;
+        }
+
+        if let Some(section) = info.sections.last_mut() {
+            let line = line.trim();
+            match line.find(':') {
+                Some(index) => section.fields.push((
+                    line[..index].trim().to_string(),
+                    line[index + 1..].trim().to_string(),
+                )),
+                None => section.fields.push((line.to_string(), String::new())),
+            }
+        }
+    }
+
+    info
+}
+
+/// Run ewfinfo against an EWF image.
+///
+/// # Arguments
+///
+/// * `first_segment` - The first segment file of the image.
+///
+/// # Returns
+///
+/// - `Err` if ewfinfo could not be run or reported an error
+/// - `Ok` with the parsed output
+pub fn run_ewfinfo(first_segment: &Path) -> io::Result<EwfInfo> {
+    let output = Command::new("ewfinfo").arg(first_segment).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "ewfinfo failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(parse_ewfinfo_output(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
@@ -0,0 +1,432 @@
+use cursive::traits::Identifiable;
+use cursive::utils::Counter;
+use cursive::view::{Boxable, Scrollable};
+use cursive::views::{Dialog, LinearLayout, ProgressBar, SelectView, TextView};
+use cursive::Cursive;
+
+use chrono::Local;
+use std::cell::RefCell;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use grim_rust::batch::{self, BatchItem};
+use grim_rust::ewfargs::{ArgsList, SourceKind};
+use grim_rust::LoggingInfo;
+
+use crate::monitor;
+use crate::windows::{main_menu, rescue_status, select_source, ARGS, INFO};
+
+thread_local! {
+    /// The sources queued for imaging, in the order they were added.
+    static QUEUE: RefCell<Vec<BatchItem>> = const { RefCell::new(Vec::new()) };
+    /// The flag that stops the queue and the arguments of its acquisitions, while it is running.
+    static RUNNING: RefCell<Option<(Arc<AtomicBool>, Vec<ArgsList>)>> = const { RefCell::new(None) };
+}
+
+/// Returns the number of sources in the queue.
+pub fn queued() -> usize {
+    QUEUE.with(|queue| queue.borrow().len())
+}
+
+/// Returns true while the queue is being imaged.
+pub fn running() -> bool {
+    RUNNING.with(|running| running.borrow().is_some())
+}
+
+/// Returns the arguments of every acquisition in the queue while it is being imaged, including
+/// those that have finished or not yet started.
+pub fn running_args() -> Vec<ArgsList> {
+    RUNNING.with(|running| {
+        running
+            .borrow()
+            .as_ref()
+            .map(|(_, args)| args.clone())
+            .unwrap_or_default()
+    })
+}
+
+/// Stop the queue if it is being imaged, as "Cancel" on its dashboard does.
+pub fn stop() {
+    RUNNING.with(|running| {
+        if let Some((ref abort, _)) = *running.borrow() {
+            abort.store(true, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Describe a queued source for the queue and the dashboard.
+fn describe(item: &BatchItem) -> String {
+    let args = &item.args;
+    format!(
+        "{}: {} {} ({}) to {}",
+        args.evidence_number.clone().unwrap_or_default(),
+        args.source_device.clone().unwrap_or_default(),
+        item.info.drive_product.clone().unwrap_or_default(),
+        item.info.drive_serial.clone().unwrap_or_default(),
+        args.target_filename.clone().unwrap_or_default()
+    )
+}
+
+/// Returns why the acquisition in `args` can't join the queue, if it can't: every source in the
+/// queue must share its case number and examiner, and have its own evidence number, source and
+/// image files.
+fn conflict(queue: &[BatchItem], args: &ArgsList) -> Option<String> {
+    let first = &queue.first()?.args;
+    if args.case_number != first.case_number || args.examiner_name != first.examiner_name {
+        return Some(format!(
+            "Every drive in the queue shares the case number {} and the examiner {}.",
+            first.case_number.clone().unwrap_or_default(),
+            first.examiner_name.clone().unwrap_or_default()
+        ));
+    }
+
+    for item in queue {
+        let queued = &item.args;
+        if queued.evidence_number == args.evidence_number {
+            return Some(format!(
+                "Evidence number {} is already in the queue.",
+                args.evidence_number.clone().unwrap_or_default()
+            ));
+        }
+        if queued.source_device == args.source_device {
+            return Some(format!(
+                "{} is already in the queue.",
+                args.source_device.clone().unwrap_or_default()
+            ));
+        }
+        if queued.target_filename == args.target_filename
+            && queued
+                .target_dirs
+                .iter()
+                .any(|dir| args.target_dirs.contains(dir))
+        {
+            return Some(format!(
+                "Another drive in the queue is already imaged to {} in the same destination.",
+                args.target_filename.clone().unwrap_or_default()
+            ));
+        }
+    }
+    None
+}
+
+/// Add the acquisition entered in the wizard to the queue, then show the queue.
+pub fn add(s: &mut Cursive) {
+    let args = ARGS.with(|args| args.borrow().clone());
+    let info = INFO.with(|info| info.borrow().clone());
+
+    let conflict = QUEUE
+        .with(|queue| conflict(&queue.borrow(), &args))
+        .or_else(|| monitor::conflict(&args));
+    if let Some(conflict) = conflict {
+        s.add_layer(Dialog::info(conflict));
+        return;
+    }
+    QUEUE.with(|queue| queue.borrow_mut().push(BatchItem::new(args, info)));
+    show(s);
+}
+
+/// Start the wizard for another source, keeping everything but the evidence number, the
+/// description, the filename and the source from the last source queued. The filename template is
+/// kept, so the new evidence number fills it in.
+fn add_drive(s: &mut Cursive) {
+    let mut args = QUEUE
+        .with(|queue| queue.borrow().last().map(|item| item.args.clone()))
+        .unwrap_or_default();
+    args.source_device = None;
+    args.source_kind = SourceKind::default();
+    args.source_range = None;
+    args.evidence_number = None;
+    args.description = None;
+    args.target_filename = None;
+
+    ARGS.with(|current| current.replace(args));
+    INFO.with(|info| info.replace(LoggingInfo::new()));
+    select_source(s);
+}
+
+/// Remove the selected source from the queue.
+fn remove_selected(s: &mut Cursive) {
+    let selected = s
+        .call_on_id("Queue", |view: &mut SelectView<usize>| view.selection())
+        .and_then(|selection| selection);
+    if let Some(index) = selected {
+        QUEUE.with(|queue| queue.borrow_mut().remove(*index));
+        show(s);
+    }
+}
+
+/// Display the queue of sources to image.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+///
+/// # Buttons
+///
+/// * "Add drive" - Choose another source and enter its details, by calling `add_drive`.
+/// * "Remove" - Remove the selected source from the queue.
+/// * "Start" - Image every source in the queue, by calling `start`.
+/// * "Main menu" - Return to the main menu, keeping the queue.
+pub fn show(s: &mut Cursive) {
+    let (header, select) = QUEUE.with(|queue| {
+        let queue = queue.borrow();
+        let mut select = SelectView::<usize>::new();
+        for (index, item) in queue.iter().enumerate() {
+            select.add_item(describe(item), index);
+        }
+
+        let header = match queue.first() {
+            None => String::from(
+                "The queue is empty. Add each drive to image, then start the queue.\n\
+                 Drives can also be added with \"Add to queue\" in the technical options.",
+            ),
+            Some(first) => {
+                let lanes = batch::lanes(
+                    &queue
+                        .iter()
+                        .map(|item| item.args.clone())
+                        .collect::<Vec<_>>(),
+                );
+                format!(
+                    "Case {}, examiner {}.\n{} drives queued. Drives writing to the same disk \
+                     are imaged one after the other, so up to {} will run at a time.",
+                    first.args.case_number.clone().unwrap_or_default(),
+                    first.args.examiner_name.clone().unwrap_or_default(),
+                    queue.len(),
+                    lanes.len()
+                )
+            }
+        };
+        (header, select)
+    });
+
+    s.pop_layer();
+    let mut dialog = Dialog::around(
+        LinearLayout::vertical()
+            .child(TextView::new(header))
+            .child(select.with_id("Queue").scrollable().max_height(16)),
+    )
+    .title("Imaging queue")
+    .button("Add drive", add_drive);
+    if queued() > 0 {
+        dialog = dialog
+            .button("Remove", remove_selected)
+            .button("Start", start);
+    }
+    s.add_layer(dialog.button("Main menu", main_menu));
+}
+
+/// Return a function that sets the status of a source on the dashboard. The function can be sent
+/// to and called from a background thread.
+fn item_status_setter(s: &mut Cursive, index: usize, prefix: String) -> impl Fn(String) + Send {
+    let cb_sink = s.cb_sink().clone();
+    move |status: String| {
+        let text = format!("{}: {}", prefix, status);
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                s.call_on_id(&format!("Batch status {}", index), |view: &mut TextView| {
+                    view.set_content(text)
+                });
+            }))
+            .unwrap();
+    }
+}
+
+/// Image every source in the queue, running sources that write to different disks in parallel,
+/// then write the combined case report next to every image and display a summary.
+///
+/// # Buttons
+///
+/// * "Cancel" - Stop every acquisition; sources that haven't started are not imaged.
+fn start(s: &mut Cursive) {
+    if running() {
+        s.add_layer(Dialog::info("The queue is already being imaged."));
+        return;
+    }
+    let mut items = QUEUE.with(|queue| queue.borrow().clone());
+    if let Some(conflict) = items.iter().find_map(|item| monitor::conflict(&item.args)) {
+        s.add_layer(Dialog::info(format!(
+            "{}\n\nWait for the acquisition to finish, or remove the drive from the queue.",
+            conflict
+        )));
+        return;
+    }
+    let lanes = batch::lanes(
+        &items
+            .iter()
+            .map(|item| item.args.clone())
+            .collect::<Vec<_>>(),
+    );
+    let started = Local::now();
+    if let Some(path) = batch::existing_report(&items, started) {
+        s.add_layer(Dialog::info(format!(
+            "{} already exists, and reports are never replaced. Move it out of the way before \
+             starting the queue.",
+            path.display()
+        )));
+        return;
+    }
+    let abort = Arc::new(AtomicBool::new(false));
+    RUNNING.with(|running| {
+        running.replace(Some((
+            abort.clone(),
+            items.iter().map(|item| item.args.clone()).collect(),
+        )))
+    });
+
+    let mut dashboard = LinearLayout::vertical();
+    let mut counters = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let counter = Counter::new(0);
+        let prefix = item.args.evidence_number.clone().unwrap_or_default();
+        dashboard = dashboard
+            .child(
+                TextView::new(format!("{}: queued", prefix))
+                    .with_id(format!("Batch status {}", index)),
+            )
+            .child(
+                ProgressBar::new()
+                    .max(1000)
+                    .with_label(|value, (_, max)| {
+                        format!("{:.1} %", value as f64 * 100.0 / max as f64)
+                    })
+                    .with_value(counter.clone())
+                    .min_width(60),
+            );
+        counters.push(counter);
+    }
+
+    let mut handles = Vec::new();
+    for lane in lanes {
+        let work: Vec<_> = lane
+            .into_iter()
+            .map(|index| {
+                let item = &items[index];
+                let prefix = item.args.evidence_number.clone().unwrap_or_default();
+                (
+                    index,
+                    item.args.clone(),
+                    item.info.clone(),
+                    counters[index].clone(),
+                    item_status_setter(s, index, prefix),
+                )
+            })
+            .collect();
+        let abort = abort.clone();
+        handles.push(thread::spawn(move || {
+            let mut results = Vec::new();
+            for (index, args, info, counter, set_status) in work {
+                if abort.load(Ordering::Relaxed) {
+                    set_status(String::from("cancelled"));
+                    results.push((index, Err(String::from("Cancelled before it started"))));
+                    continue;
+                }
+
+                set_status(format!(
+                    "imaging {}",
+                    args.source_device.clone().unwrap_or_default()
+                ));
+                let copies = args.target_dirs.len();
+                let mut verifying = None;
+                let result = batch::run_one(
+                    &args,
+                    &info,
+                    None,
+                    |phase, pass| set_status(rescue_status(&args, phase, pass)),
+                    |done, size| counter.set((done * 1000 / size.max(1)) as usize),
+                    |index, done, total| {
+                        if verifying != Some(index) {
+                            verifying = Some(index);
+                            set_status(format!("verifying copy {} of {}", index + 1, copies));
+                        }
+                        counter.set((done * 1000 / total.max(1)) as usize);
+                    },
+                    &abort,
+                )
+                .map_err(|e| e.to_string());
+
+                set_status(match result {
+                    Ok(_) => String::from("done"),
+                    Err(ref e) => format!("FAILED: {}", e),
+                });
+                results.push((index, result));
+            }
+            results
+        }));
+    }
+
+    s.pop_layer();
+    s.add_layer(
+        Dialog::around(dashboard.scrollable().max_height(24))
+            .title("Imaging queue")
+            .button("Cancel", {
+                let abort = abort.clone();
+                move |_| abort.store(true, Ordering::Relaxed)
+            }),
+    );
+    s.set_fps(2);
+
+    let cb_sink = s.cb_sink().clone();
+    thread::spawn(move || {
+        for handle in handles {
+            for (index, result) in handle.join().unwrap_or_default() {
+                items[index].result = Some(result);
+            }
+        }
+        let reports = batch::write_case_report(&items, started);
+
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                RUNNING.with(|running| running.replace(None));
+                s.set_fps(0);
+                s.pop_layer();
+                finished(s, &items, reports);
+            }))
+            .unwrap();
+    });
+}
+
+/// Display the outcome of every source in the queue and where the case report was written, then
+/// empty the queue.
+///
+/// # Buttons
+///
+/// * "Main menu" - Return to the main menu.
+fn finished(s: &mut Cursive, items: &[BatchItem], reports: io::Result<Vec<PathBuf>>) {
+    let imaged = items
+        .iter()
+        .filter(|item| matches!(item.result, Some(Ok(_))))
+        .count();
+    let mut summary = format!("Imaged {} of {} drives.\n", imaged, items.len());
+    for item in items {
+        let outcome = match item.result {
+            Some(Ok(ref result)) => format!("MD5 {}", result.digests.md5),
+            Some(Err(ref e)) => format!("FAILED: {}", e),
+            None => String::from("not run"),
+        };
+        summary.push_str(&format!(
+            "\n{}: {}",
+            item.args.evidence_number.clone().unwrap_or_default(),
+            outcome
+        ));
+    }
+    match reports {
+        Ok(paths) => {
+            summary.push_str("\n\nCase report written to:");
+            for path in paths {
+                summary.push_str(&format!("\n{}", path.display()));
+            }
+        }
+        Err(e) => summary.push_str(&format!("\n\nThe case report couldn't be written: {}", e)),
+    }
+
+    QUEUE.with(|queue| queue.borrow_mut().clear());
+    s.add_layer(
+        Dialog::text(summary)
+            .title("Queue complete")
+            .button("Main menu", main_menu),
+    );
+}
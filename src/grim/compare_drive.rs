@@ -0,0 +1,285 @@
+use cursive::traits::Identifiable;
+use cursive::utils::Counter;
+use cursive::views::{Checkbox, Dialog, LinearLayout, ListView, TextView};
+use cursive::Cursive;
+
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::thread;
+
+use grim_rust::acquire;
+use grim_rust::compare::{self, Comparison};
+use grim_rust::ewfargs::DigestType;
+use grim_rust::ewfinfo;
+use grim_rust::image::EwfExportReader;
+use grim_rust::report;
+
+use crate::windows::{
+    digest_summary, extract_entrybox_optional, extract_entrybox_required, main_menu, new_entry_box,
+    progress_dialog, select_source_then, status_setter, ARGS, INFO,
+};
+
+// The image to compare against and whether to list differing sectors, kept between forms
+thread_local! {
+    static IMAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+    static FIND_SECTORS: Cell<bool> = const { Cell::new(true) };
+}
+
+/// Start the flow to compare a drive against an existing image.
+pub fn start(s: &mut Cursive) {
+    select_source_then(s, "Select a disk to compare", compare_options);
+}
+
+/// Submit the comparison options form
+fn compare_options_next(s: &mut Cursive) {
+    let success = ARGS.with(|args| -> bool {
+        let mut args = args.borrow_mut();
+        if extract_entrybox_required(s, "Examiner Name", &mut args.examiner_name).is_err() {
+            return false;
+        }
+        extract_entrybox_optional(s, "Case Number", &mut args.case_number);
+        extract_entrybox_optional(s, "Evidence Number", &mut args.evidence_number);
+
+        let mut image = None;
+        if extract_entrybox_required(s, "Image", &mut image).is_err() {
+            return false;
+        }
+        if !Path::new(image.as_ref().unwrap()).is_file() {
+            s.add_layer(Dialog::info(format!("{} does not exist.", image.unwrap())));
+            return false;
+        }
+        IMAGE.with(|cell| cell.replace(image));
+
+        let mut report_dir = None;
+        if extract_entrybox_required(s, "Report directory", &mut report_dir).is_err()
+            || extract_entrybox_required(s, "Report filename", &mut args.target_filename).is_err()
+        {
+            return false;
+        }
+        args.target_dirs = report_dir.into_iter().collect();
+        true
+    });
+
+    if !success {
+        return;
+    }
+
+    let find_sectors = s
+        .call_on_id("Find sectors", |view: &mut Checkbox| view.is_checked())
+        .unwrap();
+    FIND_SECTORS.with(|cell| cell.set(find_sectors));
+    compare_drive(s);
+}
+
+/// Display the form for choosing the image to compare against and where to write the report.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the source selection form.
+/// * "Next" - Start the comparison by calling `compare_drive`.
+pub fn compare_options(s: &mut Cursive) {
+    s.pop_layer();
+
+    let image = IMAGE.with(|cell| cell.borrow().clone());
+    let mut find_sectors = Checkbox::new();
+    if FIND_SECTORS.with(|cell| cell.get()) {
+        find_sectors = find_sectors.checked();
+    }
+
+    let fields = ARGS.with(|args| {
+        let args = args.borrow();
+        let next = |s: &mut Cursive, _: &str| compare_options_next(s);
+
+        ListView::new()
+            .child(
+                "Examiner Name",
+                new_entry_box("Examiner Name", 256, &args.examiner_name, next),
+            )
+            .child(
+                "Case Number",
+                new_entry_box("Case Number", 256, &args.case_number, next),
+            )
+            .child(
+                "Evidence Number",
+                new_entry_box("Evidence Number", 256, &args.evidence_number, next),
+            )
+            .child(
+                "Image (first segment)",
+                new_entry_box("Image", 4096, &image, next),
+            )
+            .child(
+                "List differing sectors?",
+                find_sectors.with_id("Find sectors"),
+            )
+            .child(
+                "Report directory",
+                new_entry_box(
+                    "Report directory",
+                    255,
+                    &args.target_dirs.first().cloned(),
+                    next,
+                ),
+            )
+            .child(
+                "Report filename (no extension)",
+                new_entry_box("Report filename", 255, &args.target_filename, next),
+            )
+    });
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "The drive is hashed with every digest stored in the image.\n\
+                     Differing sectors are only listed when the digests do not match.",
+                ))
+                .child(fields),
+        )
+        .padding((1, 1, 1, 0))
+        .title("Compare drive against image")
+        .button("Back", start)
+        .button("Next", compare_options_next),
+    );
+}
+
+/// Hash the drive and compare it against the image in the background, displaying the progress,
+/// then write the report and display the result.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+///
+/// # Buttons
+///
+/// * "Cancel" - Stop the comparison; no report is written.
+fn compare_drive(s: &mut Cursive) {
+    let mut args = ARGS.with(|args| args.borrow().clone());
+    let info = INFO.with(|info| info.borrow().clone());
+    let image = PathBuf::from(IMAGE.with(|cell| cell.borrow().clone()).unwrap());
+    let find_sectors = FIND_SECTORS.with(|cell| cell.get());
+
+    let counter = Counter::new(0);
+    let abort = Arc::new(AtomicBool::new(false));
+
+    s.pop_layer();
+    s.add_layer(progress_dialog(
+        "Comparing",
+        format!("Reading the digests stored in {}", image.display()),
+        counter.clone(),
+        abort.clone(),
+    ));
+    s.set_fps(2);
+
+    let set_status = status_setter(s);
+    let cb_sink = s.cb_sink().clone();
+    thread::spawn(move || {
+        let report_path = report::report_path(Path::new(&args.target_dirs[0]), &args);
+        let result = (|| -> io::Result<Comparison> {
+            let image_info = ewfinfo::run_ewfinfo(&image)?;
+            let stored = image_info.digests().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "The image does not contain an MD5 digest",
+                )
+            })?;
+
+            // Calculate every digest the image has, so they can all be compared
+            args.digest_type = DigestType::MD5;
+            if stored.sha1.is_some() {
+                args.digest_type |= DigestType::SHA1;
+            }
+            if stored.sha256.is_some() {
+                args.digest_type |= DigestType::SHA256;
+            }
+
+            set_status(format!(
+                "Hashing {}",
+                args.source_device.clone().unwrap_or_default()
+            ));
+            let drive = acquire::hash_source(
+                &args,
+                |done, size| counter.set((done * 1000 / size.max(1)) as usize),
+                &abort,
+            )?;
+
+            let mut comparison = Comparison {
+                image: image.clone(),
+                stored,
+                drive,
+                differing: None,
+            };
+
+            if find_sectors && !comparison.matches() {
+                set_status("Comparing the drive and the image sector by sector".to_string());
+                counter.set(0);
+                let mut source = File::open(args.source_device.clone().unwrap_or_default())?;
+                let mut media = EwfExportReader::open(&image)?;
+                comparison.differing = Some(compare::differing_ranges(
+                    &mut source,
+                    &mut media,
+                    image_info.bytes_per_sector().unwrap_or(512),
+                    args.chunk_size(),
+                    comparison.drive.source_size,
+                    |done, size| counter.set((done * 1000 / size.max(1)) as usize),
+                    &abort,
+                )?);
+            }
+
+            report::write_report(
+                &report_path,
+                &report::comparison_report(&args, &info, &comparison),
+            )?;
+            Ok(comparison)
+        })();
+
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                s.set_fps(0);
+                s.pop_layer();
+                match result {
+                    Ok(comparison) => {
+                        let verdict = if comparison.matches() {
+                            "The drive MATCHES the image."
+                        } else {
+                            "The drive DOES NOT MATCH the image."
+                        };
+                        let mut summary = format!(
+                            "{}\n\nDrive:\n{}\n\nImage:\n{}",
+                            verdict,
+                            digest_summary(&comparison.drive.digests),
+                            digest_summary(&comparison.stored)
+                        );
+                        if let Some(ref ranges) = comparison.differing {
+                            summary.push_str(&format!(
+                                "\n\n{} differing sector ranges; see the report.",
+                                ranges.len()
+                            ));
+                        }
+                        summary
+                            .push_str(&format!("\n\nReport written to {}", report_path.display()));
+                        s.add_layer(
+                            Dialog::text(summary)
+                                .title("Comparison complete")
+                                .button("Main menu", main_menu),
+                        );
+                    }
+                    Err(e) => {
+                        s.add_layer(
+                            Dialog::text(format!("Comparison failed: {}", e))
+                                .title("Error")
+                                .button("Main menu", main_menu),
+                        );
+                    }
+                }
+            }))
+            .unwrap();
+    });
+}
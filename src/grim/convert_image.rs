@@ -0,0 +1,80 @@
+use cursive::views::Dialog;
+use cursive::Cursive;
+
+use pretty_bytes::converter::convert as format_bytes;
+use std::path::{Path, PathBuf};
+
+use grim_rust::ewfargs::SourceKind;
+use grim_rust::ewfinfo;
+use grim_rust::image::{is_ewf_first_segment, is_image, raw_segments};
+
+use crate::file_browser::browse;
+use crate::windows::{examiner_info, ARGS, INFO};
+
+/// Start the flow to export an existing raw or EWF image to another format.
+pub fn start(s: &mut Cursive) {
+    browse(
+        s,
+        "Select an image to convert (.E01, .Ex01, .dd, .raw, .img or .001)",
+        is_image,
+        image_chosen,
+    );
+}
+
+/// Fill in `ARGS` from the case information stored in an EWF image, so it carries over to the
+/// converted image.
+///
+/// # Returns
+///
+/// - `Err` with a message if ewfinfo could not read the image
+/// - `Ok` with the size of the image's media data, if ewfinfo reported it
+fn read_ewf_header(image: &Path) -> Result<Option<u64>, String> {
+    let info = ewfinfo::run_ewfinfo(image)
+        .map_err(|e| format!("Can't read {}: {}", image.display(), e))?;
+
+    ARGS.with(|args| {
+        let mut args = args.borrow_mut();
+        let header = |label| {
+            info.get("Acquiry information", label)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        args.case_number = header("Case number");
+        args.evidence_number = header("Evidence number");
+        args.examiner_name = header("Examiner name");
+        args.description = header("Description");
+        args.notes = header("Notes");
+        args.bytes_per_sector = info.bytes_per_sector().map(|bytes| bytes as u32);
+    });
+
+    Ok(info.media_size())
+}
+
+/// Record the chosen image as the source and continue to the examiner information form.
+fn image_chosen(s: &mut Cursive, image: PathBuf) {
+    let size = if is_ewf_first_segment(&image) {
+        match read_ewf_header(&image) {
+            Ok(size) => size,
+            Err(e) => {
+                s.add_layer(Dialog::info(e));
+                return;
+            }
+        }
+    } else {
+        raw_segments(&image)
+            .iter()
+            .map(|file| file.metadata().map(|metadata| metadata.len()))
+            .sum::<Result<u64, _>>()
+            .ok()
+    };
+
+    INFO.with(|info| {
+        info.borrow_mut().drive_size = size.map(|size| format_bytes(size as f64));
+    });
+    ARGS.with(|args| {
+        let mut args = args.borrow_mut();
+        args.source_device = Some(image.display().to_string());
+        args.source_kind = SourceKind::Image;
+    });
+    examiner_info(s);
+}
@@ -0,0 +1,123 @@
+use cursive::traits::Identifiable;
+use cursive::view::{Boxable, Scrollable};
+use cursive::views::{Dialog, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::windows::main_menu;
+
+// The last directory browsed, so the next browser opens where the examiner left off
+thread_local! {
+    static LAST_DIR: RefCell<PathBuf> = RefCell::new(PathBuf::from("/"));
+}
+
+/// An entry in the file browser.
+#[derive(Clone)]
+enum Entry {
+    Dir(PathBuf),
+    File(PathBuf),
+}
+
+/// List the entries in `dir`: its parent, then subdirectories, then files accepted by `filter`,
+/// each sorted by name. Hidden entries are skipped.
+fn list_dir(dir: &Path, filter: fn(&Path) -> bool) -> std::io::Result<Vec<(String, Entry)>> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if name.starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            dirs.push((format!("{}/", name), Entry::Dir(path)));
+        } else if filter(&path) {
+            files.push((name, Entry::File(path)));
+        }
+    }
+
+    dirs.sort_by(|a, b| a.0.cmp(&b.0));
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut list = Vec::new();
+    if let Some(parent) = dir.parent() {
+        list.push(("../".to_string(), Entry::Dir(parent.to_path_buf())));
+    }
+    list.extend(dirs);
+    list.extend(files);
+    Ok(list)
+}
+
+/// Show the contents of `dir` in the browser.
+fn show_dir(s: &mut Cursive, dir: &Path, filter: fn(&Path) -> bool) {
+    let list = match list_dir(dir, filter) {
+        Ok(list) => list,
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("Can't open {}: {}", dir.display(), e)));
+            return;
+        }
+    };
+
+    LAST_DIR.with(|last| last.replace(dir.to_path_buf()));
+    s.call_on_id("Browser path", |view: &mut TextView| {
+        view.set_content(dir.display().to_string())
+    });
+    s.call_on_id("Browser", |view: &mut SelectView<Entry>| {
+        view.clear();
+        view.add_all(list);
+    });
+}
+
+/// Display a file browser, starting in the last directory browsed.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+/// * `title` - The title of the browser.
+/// * `filter` - Returns true for the files that can be chosen; other files are hidden.
+/// * `chosen` - Called with the file the user chose.
+///
+/// # Buttons
+///
+/// * "Cancel" - Return to the main menu.
+/// * Submit a directory to open it, or a file to choose it.
+pub fn browse(
+    s: &mut Cursive,
+    title: &str,
+    filter: fn(&Path) -> bool,
+    chosen: fn(&mut Cursive, PathBuf),
+) {
+    s.pop_layer();
+
+    let select = SelectView::<Entry>::new().on_submit(move |s, entry| match entry {
+        Entry::Dir(dir) => show_dir(s, dir, filter),
+        Entry::File(file) => chosen(s, file.clone()),
+    });
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new("").with_id("Browser path"))
+                .child(
+                    select
+                        .with_id("Browser")
+                        .scrollable()
+                        .min_width(60)
+                        .max_height(20),
+                ),
+        )
+        .title(title)
+        .button("Cancel", main_menu),
+    );
+
+    let dir = LAST_DIR.with(|last| last.borrow().clone());
+    show_dir(s, &dir, filter);
+}
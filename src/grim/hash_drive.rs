@@ -0,0 +1,177 @@
+use cursive::utils::Counter;
+use cursive::views::{Dialog, ListView};
+use cursive::Cursive;
+
+use pretty_bytes::converter::convert as format_bytes;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::thread;
+
+use grim_rust::acquire;
+use grim_rust::report;
+
+use crate::windows::{
+    digest_boxes, digest_summary, extract_digests, extract_entrybox_optional,
+    extract_entrybox_required, main_menu, new_entry_box, progress_dialog, select_source_then, ARGS,
+    INFO,
+};
+
+/// Start the flow to hash a drive without imaging it.
+pub fn start(s: &mut Cursive) {
+    select_source_then(s, "Select a disk to hash", hash_options);
+}
+
+/// Submit the hash options form
+fn hash_options_next(s: &mut Cursive) {
+    let success = ARGS.with(|args| -> bool {
+        let mut args = args.borrow_mut();
+        if extract_entrybox_required(s, "Examiner Name", &mut args.examiner_name).is_err() {
+            return false;
+        }
+        extract_entrybox_optional(s, "Case Number", &mut args.case_number);
+        extract_entrybox_optional(s, "Evidence Number", &mut args.evidence_number);
+
+        let mut report_dir = None;
+        if extract_entrybox_required(s, "Report directory", &mut report_dir).is_err()
+            || extract_entrybox_required(s, "Report filename", &mut args.target_filename).is_err()
+        {
+            return false;
+        }
+        args.target_dirs = report_dir.into_iter().collect();
+
+        args.digest_type = extract_digests(s);
+        true
+    });
+
+    if success {
+        hash_drive(s);
+    }
+}
+
+/// Display the form for choosing which digests to calculate and where to write the report.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the source selection form.
+/// * "Next" - Start hashing the drive by calling `hash_drive`.
+pub fn hash_options(s: &mut Cursive) {
+    s.pop_layer();
+
+    let fields = ARGS.with(|args| {
+        let args = args.borrow();
+        let next = |s: &mut Cursive, _: &str| hash_options_next(s);
+
+        ListView::new()
+            .child(
+                "Examiner Name",
+                new_entry_box("Examiner Name", 256, &args.examiner_name, next),
+            )
+            .child(
+                "Case Number",
+                new_entry_box("Case Number", 256, &args.case_number, next),
+            )
+            .child(
+                "Evidence Number",
+                new_entry_box("Evidence Number", 256, &args.evidence_number, next),
+            )
+            .child("Generate hashes", digest_boxes(args.digest_type))
+            .child(
+                "Report directory",
+                new_entry_box(
+                    "Report directory",
+                    255,
+                    &args.target_dirs.first().cloned(),
+                    next,
+                ),
+            )
+            .child(
+                "Report filename (no extension)",
+                new_entry_box("Report filename", 255, &args.target_filename, next),
+            )
+    });
+
+    s.add_layer(
+        Dialog::around(fields)
+            .padding((1, 1, 1, 0))
+            .title("Hash options")
+            .button("Back", start)
+            .button("Next", hash_options_next),
+    );
+}
+
+/// Hash the drive in the background, displaying its progress, then write the report and
+/// display the digests.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+///
+/// # Buttons
+///
+/// * "Cancel" - Stop hashing; no report is written.
+fn hash_drive(s: &mut Cursive) {
+    let args = ARGS.with(|args| args.borrow().clone());
+    let info = INFO.with(|info| info.borrow().clone());
+
+    let counter = Counter::new(0);
+    let abort = Arc::new(AtomicBool::new(false));
+
+    s.pop_layer();
+    s.add_layer(progress_dialog(
+        "Hashing",
+        format!("Hashing {}", args.source_device.clone().unwrap_or_default()),
+        counter.clone(),
+        abort.clone(),
+    ));
+    s.set_fps(2);
+
+    let cb_sink = s.cb_sink().clone();
+    thread::spawn(move || {
+        let report_path = report::report_path(Path::new(&args.target_dirs[0]), &args);
+        let result = acquire::hash_source(
+            &args,
+            |done, size| counter.set((done * 1000 / size.max(1)) as usize),
+            &abort,
+        )
+        .and_then(|result| {
+            report::write_report(&report_path, &report::hash_report(&args, &info, &result))?;
+            Ok(result)
+        });
+
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                s.set_fps(0);
+                s.pop_layer();
+                match result {
+                    Ok(result) => {
+                        let mut summary = format!(
+                            "Hashed {} from {}.\n\n{}",
+                            format_bytes(result.bytes_acquired as f64),
+                            args.source_device.clone().unwrap_or_default(),
+                            digest_summary(&result.digests)
+                        );
+                        summary
+                            .push_str(&format!("\n\nReport written to {}", report_path.display()));
+                        s.add_layer(
+                            Dialog::text(summary)
+                                .title("Hashing complete")
+                                .button("Main menu", main_menu),
+                        );
+                    }
+                    Err(e) => {
+                        s.add_layer(
+                            Dialog::text(format!("Hashing failed: {}", e))
+                                .title("Error")
+                                .button("Main menu", main_menu),
+                        );
+                    }
+                }
+            }))
+            .unwrap();
+    });
+}
@@ -0,0 +1,131 @@
+use cursive::view::{Boxable, Scrollable};
+use cursive::views::{Dialog, TextView};
+use cursive::Cursive;
+
+use std::path::{Path, PathBuf};
+
+use grim_rust::ewfinfo::{self, EwfInfo};
+use grim_rust::image::{ewf_segments, is_ewf_first_segment};
+
+use crate::file_browser::browse;
+use crate::windows::{digest_summary, main_menu};
+
+/// The fields shown for an image, grouped by ewfinfo section, as the label ewfinfo prints and the
+/// label to show.
+const FIELDS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Acquiry information",
+        &[
+            ("Case number", "Case number"),
+            ("Evidence number", "Evidence number"),
+            ("Examiner name", "Examiner name"),
+            ("Description", "Description"),
+            ("Notes", "Notes"),
+            ("Acquisition date", "Acquisition date"),
+            ("System date", "System date"),
+            ("Operating system used", "Acquiry OS"),
+            ("Software version used", "Acquiry software"),
+        ],
+    ),
+    (
+        "EWF information",
+        &[
+            ("File format", "File format"),
+            ("Sectors per chunk", "Sectors per chunk"),
+            ("Compression method", "Compression method"),
+            ("Compression level", "Compression level"),
+        ],
+    ),
+    (
+        "Media information",
+        &[
+            ("Media size", "Media size"),
+            ("Bytes per sector", "Bytes per sector"),
+            ("Number of sectors", "Number of sectors"),
+        ],
+    ),
+];
+
+/// Start the flow to show information about an existing image.
+pub fn start(s: &mut Cursive) {
+    browse(
+        s,
+        "Select the first segment of an image",
+        is_ewf_first_segment,
+        image_info,
+    );
+}
+
+/// Build the text describing an image.
+fn describe(image: &Path, info: &EwfInfo) -> String {
+    let mut text = format!("{}\n\n", image.display());
+
+    for (heading, fields) in FIELDS {
+        for (label, shown) in fields.iter() {
+            let value = match info.get(heading, label) {
+                Some(value) if !value.is_empty() => value,
+                _ => "(none)",
+            };
+            text.push_str(&format!("{:<20}{}\n", format!("{}:", shown), value));
+        }
+    }
+    text.push_str(&format!(
+        "{:<20}{}\n",
+        "Segment files:",
+        ewf_segments(image).len()
+    ));
+
+    text.push_str("\nStored hashes:\n");
+    match info.digests() {
+        Some(digests) => text.push_str(&digest_summary(&digests)),
+        None => text.push_str("(none)"),
+    }
+
+    text.push_str("\n\nAcquisition errors:\n");
+    let errors = info.error_ranges();
+    if errors.is_empty() {
+        text.push_str("(none)");
+    }
+    for range in errors {
+        text.push_str(&format!("{}\n", range));
+    }
+
+    text
+}
+
+/// Run ewfinfo against the chosen image and display what it reports.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+/// * `image` - The first segment of the image.
+///
+/// # Buttons
+///
+/// * "Another image" - Return to the file browser.
+/// * "Main menu" - Return to the main menu.
+fn image_info(s: &mut Cursive, image: PathBuf) {
+    let info = match ewfinfo::run_ewfinfo(&image) {
+        Ok(info) => info,
+        Err(e) => {
+            s.add_layer(Dialog::info(format!(
+                "Can't read {}: {}",
+                image.display(),
+                e
+            )));
+            return;
+        }
+    };
+
+    s.pop_layer();
+    s.add_layer(
+        Dialog::around(
+            TextView::new(describe(&image, &info))
+                .scrollable()
+                .max_height(30),
+        )
+        .title("Image information")
+        .button("Another image", start)
+        .button("Main menu", main_menu),
+    );
+}
@@ -1,7 +1,19 @@
 use cursive::views::Dialog;
 use cursive::Cursive;
-use users;
 
+use grim_rust::config::Config;
+
+mod batch_queue;
+mod compare_drive;
+mod convert_image;
+mod file_browser;
+mod hash_drive;
+mod image_info;
+mod monitor;
+mod partitions;
+mod prepared_job;
+mod resume_job;
+mod verify_image;
 mod windows;
 
 fn main() {
@@ -21,10 +33,22 @@ fn main() {
         welcome(&mut siv);
     }
 
+    match Config::load() {
+        Ok(config) => windows::CONFIG.with(|current| {
+            current.replace(config);
+        }),
+        Err(e) => siv.add_layer(Dialog::info(format!(
+            "The configuration in {} can't be used, so the defaults will be: {}",
+            Config::path().display(),
+            e
+        ))),
+    }
+
     siv.run();
 }
 
-/// Display the welcome window and continue to the main menu once the user continues
+/// Display the welcome window, and once the user continues, offer to resume any interrupted
+/// acquisitions before continuing to the main menu
 ///
 /// # Arguments
 ///
@@ -36,6 +60,6 @@ pub fn welcome(s: &mut Cursive) {
              Comments can be addressed to demize@unstable.systems.",
         )
         .title(format!("grim {}", env!("CARGO_PKG_VERSION")))
-        .button("Continue", windows::main_menu),
+        .button("Continue", resume_job::check),
     );
 }
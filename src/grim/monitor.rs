@@ -0,0 +1,457 @@
+use cursive::traits::Identifiable;
+use cursive::utils::Counter;
+use cursive::view::Boxable;
+use cursive::views::{Button, Dialog, LinearLayout, ProgressBar, TextView};
+use cursive::Cursive;
+
+use pretty_bytes::converter::convert as format_bytes;
+use std::cell::{Cell, RefCell};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use grim_rust::acquire::AcquisitionResult;
+use grim_rust::batch;
+use grim_rust::ewfargs::ArgsList;
+use grim_rust::job::JobState;
+use grim_rust::report;
+use grim_rust::LoggingInfo;
+
+use crate::batch_queue;
+use crate::windows::{choose_profile, digest_summary, main_menu, rescue_status, reset_wizard};
+
+/// An acquisition started from the wizard, running in the background or finished.
+struct RunningJob {
+    /// Identifies the job for as long as grim runs.
+    id: usize,
+    /// The arguments the acquisition was started with.
+    args: ArgsList,
+    /// What the acquisition is doing now, or its outcome once it has finished.
+    status: String,
+    /// The progress of the current step, in thousandths.
+    counter: Counter,
+    /// The bytes read from the source so far, and the size of the source.
+    read: Arc<(AtomicU64, AtomicU64)>,
+    /// When the acquisition started.
+    started: Instant,
+    /// Set to stop the acquisition.
+    abort: Arc<AtomicBool>,
+    /// Whether the acquisition has finished, successfully or not.
+    finished: bool,
+}
+
+impl RunningJob {
+    /// Returns the label of the job's tab.
+    fn title(&self) -> String {
+        format!(
+            "{}{}",
+            self.args.evidence_number.clone().unwrap_or_else(|| self
+                .args
+                .target_filename
+                .clone()
+                .unwrap_or_default()),
+            if self.finished { " (done)" } else { "" }
+        )
+    }
+}
+
+thread_local! {
+    /// Every acquisition started since grim started, until it is removed from the monitor.
+    static JOBS: RefCell<Vec<RunningJob>> = const { RefCell::new(Vec::new()) };
+    /// The job shown in the monitor.
+    static SHOWN: Cell<usize> = const { Cell::new(0) };
+    /// The ID of the next job started.
+    static NEXT_ID: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Returns the number of acquisitions still running.
+pub fn running() -> usize {
+    JOBS.with(|jobs| jobs.borrow().iter().filter(|job| !job.finished).count())
+}
+
+/// Returns the number of acquisitions in the monitor, running or finished.
+pub fn count() -> usize {
+    JOBS.with(|jobs| jobs.borrow().len())
+}
+
+/// Stop every running acquisition. Each stops at its next chunk, as if "Stop" had been pressed.
+pub fn stop_all() {
+    JOBS.with(|jobs| {
+        for job in jobs.borrow().iter().filter(|job| !job.finished) {
+            job.abort.store(true, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Returns why the acquisition in `args` can't run alongside the acquisition in `other`, if it
+/// can't: they may not read the same source or write the same image.
+fn clash(other: &ArgsList, args: &ArgsList) -> Option<String> {
+    if other.source_device == args.source_device {
+        Some(format!(
+            "{} is already being imaged.",
+            args.source_device.clone().unwrap_or_default()
+        ))
+    } else if other.target_filename == args.target_filename
+        && other
+            .target_dirs
+            .iter()
+            .any(|dir| args.target_dirs.contains(dir))
+    {
+        Some(format!(
+            "Another acquisition is already writing {} to the same destination.",
+            args.target_filename.clone().unwrap_or_default()
+        ))
+    } else {
+        None
+    }
+}
+
+/// Returns why the acquisition in `args` can't start alongside the running ones, if it can't:
+/// no two running acquisitions, whether started from the wizard or by the imaging queue, may
+/// read the same source or write the same image.
+pub fn conflict(args: &ArgsList) -> Option<String> {
+    let mut running: Vec<ArgsList> = JOBS.with(|jobs| {
+        jobs.borrow()
+            .iter()
+            .filter(|job| !job.finished)
+            .map(|job| job.args.clone())
+            .collect()
+    });
+    running.extend(batch_queue::running_args());
+    running.iter().find_map(|other| clash(other, args))
+}
+
+/// Returns the summary of a finished acquisition shown in the monitor.
+fn summary(args: &ArgsList, result: &AcquisitionResult) -> String {
+    let mut summary = format!(
+        "Acquired {} from {}.\n\n{}",
+        format_bytes(result.bytes_acquired as f64),
+        args.source_device.clone().unwrap_or_default(),
+        digest_summary(&result.digests)
+    );
+    summary.push_str("\n\nReports written to:");
+    for destination in &result.destinations {
+        let verified = match destination.verification {
+            Some(ref verification) if verification.matches => "verified",
+            _ => "VERIFICATION FAILED",
+        };
+        summary.push_str(&format!(
+            "\n{} ({})",
+            report::report_path(&destination.dir, args).display(),
+            verified
+        ));
+    }
+    summary
+}
+
+/// Record the status of a job, showing it if the job is shown in the monitor.
+fn record_status(s: &mut Cursive, id: usize, status: String) {
+    JOBS.with(|jobs| {
+        if let Some(job) = jobs.borrow_mut().iter_mut().find(|job| job.id == id) {
+            job.status = status.clone();
+        }
+    });
+    if SHOWN.with(Cell::get) == id {
+        s.call_on_id("Monitor status", |view: &mut TextView| {
+            view.set_content(status)
+        });
+    }
+}
+
+/// Return a function that sets the status of a job. The function can be sent to and called from a
+/// background thread.
+fn status_setter(s: &mut Cursive, id: usize) -> impl Fn(String) + Send {
+    let cb_sink = s.cb_sink().clone();
+    move |status: String| {
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                record_status(s, id, status)
+            }))
+            .unwrap();
+    }
+}
+
+/// Start an acquisition, or carry on with an interrupted one, in the background, then show it in
+/// the monitor. When it finishes, its report is written to every destination and its outcome is
+/// shown in the monitor.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+/// * `args` - The arguments of the acquisition.
+/// * `info` - The information about the source and host, for the report.
+/// * `resume` - The job state file and state of an interrupted acquisition to resume, if any.
+pub fn start(
+    s: &mut Cursive,
+    args: ArgsList,
+    info: LoggingInfo,
+    resume: Option<(PathBuf, JobState)>,
+) {
+    if let Some(conflict) = conflict(&args) {
+        s.add_layer(Dialog::info(conflict));
+        return;
+    }
+
+    let source = args.source_device.clone().unwrap_or_default();
+    let id = NEXT_ID.with(|next| next.replace(next.get() + 1));
+    let job = RunningJob {
+        id,
+        args: args.clone(),
+        status: match resume {
+            Some(_) => format!("Resuming {}: reading back the image written so far", source),
+            None => format!("Imaging {}", source),
+        },
+        counter: Counter::new(0),
+        read: Arc::new((AtomicU64::new(0), AtomicU64::new(0))),
+        started: Instant::now(),
+        abort: Arc::new(AtomicBool::new(false)),
+        finished: false,
+    };
+    let counter = job.counter.clone();
+    let read = job.read.clone();
+    let abort = job.abort.clone();
+    JOBS.with(|jobs| jobs.borrow_mut().push(job));
+
+    let set_status = status_setter(s, id);
+    let cb_sink = s.cb_sink().clone();
+    thread::spawn(move || {
+        let copies = args.target_dirs.len();
+        let mut verifying = None;
+        let result = batch::run_one(
+            &args,
+            &info,
+            resume,
+            |phase, pass| set_status(rescue_status(&args, phase, pass)),
+            |done, size| {
+                read.0.store(done, Ordering::Relaxed);
+                read.1.store(size, Ordering::Relaxed);
+                counter.set((done * 1000 / size.max(1)) as usize);
+            },
+            |index, done, total| {
+                if verifying != Some(index) {
+                    verifying = Some(index);
+                    set_status(format!("Verifying copy {} of {}", index + 1, copies));
+                }
+                counter.set((done * 1000 / total.max(1)) as usize);
+            },
+            &abort,
+        );
+
+        let status = match result {
+            Ok(ref result) => summary(&args, result),
+            Err(ref e) => format!("Acquisition failed: {}", e),
+        };
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                JOBS.with(|jobs| {
+                    if let Some(job) = jobs.borrow_mut().iter_mut().find(|job| job.id == id) {
+                        job.finished = true;
+                    }
+                });
+                record_status(s, id, status);
+                if running() == 0 {
+                    s.set_fps(0);
+                }
+            }))
+            .unwrap();
+    });
+
+    s.pop_layer();
+    show_job(s, id);
+}
+
+/// Returns the label of the progress bar of a job: how far along it is, and while the source is
+/// being read, how fast and how long is left.
+fn progress_label(
+    value: usize,
+    max: usize,
+    read: &(AtomicU64, AtomicU64),
+    started: Instant,
+) -> String {
+    let mut label = format!("{:.1} %", value as f64 * 100.0 / max as f64);
+    let done = read.0.load(Ordering::Relaxed);
+    let size = read.1.load(Ordering::Relaxed);
+    let elapsed = started.elapsed().as_secs_f64();
+    if done > 0 && done < size && elapsed > 0.0 {
+        let rate = done as f64 / elapsed;
+        let left = ((size - done) as f64 / rate) as u64;
+        label.push_str(&format!(
+            " at {}/s, {}:{:02} left",
+            format_bytes(rate),
+            left / 60,
+            left % 60
+        ));
+    }
+    label
+}
+
+/// Display the monitor, showing the job last shown, or a message if no acquisitions have been
+/// started.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+pub fn show(s: &mut Cursive) {
+    let shown = SHOWN.with(Cell::get);
+    let id = JOBS.with(|jobs| {
+        let jobs = jobs.borrow();
+        jobs.iter()
+            .find(|job| job.id == shown)
+            .or_else(|| jobs.first())
+            .map(|job| job.id)
+    });
+    match id {
+        Some(id) => {
+            s.pop_layer();
+            show_job(s, id);
+        }
+        None => s.add_layer(Dialog::info("No acquisitions have been started.")),
+    }
+}
+
+/// Display the monitor with a tab for every acquisition, showing the job with the given ID.
+///
+/// # Buttons
+///
+/// * A tab for each job - Show that job.
+/// * "New acquisition" - Set up another acquisition while the others carry on.
+/// * "Stop" - Stop the shown job, after asking to confirm; it can be resumed later.
+/// * "Remove" - Remove the shown job from the monitor once it has finished.
+/// * "Main menu" - Return to the main menu; the jobs carry on in the background.
+fn show_job(s: &mut Cursive, id: usize) {
+    SHOWN.with(|shown| shown.set(id));
+
+    let view = JOBS.with(|jobs| {
+        let jobs = jobs.borrow();
+        let job = jobs.iter().find(|job| job.id == id)?;
+
+        let mut tabs = LinearLayout::horizontal();
+        for other in jobs.iter() {
+            let other_id = other.id;
+            let title = if other_id == id {
+                format!("[{}]", other.title())
+            } else {
+                other.title()
+            };
+            tabs = tabs.child(Button::new(title, move |s| {
+                s.pop_layer();
+                show_job(s, other_id);
+            }));
+        }
+
+        let details = format!(
+            "Evidence {}: {} to {} in {}",
+            job.args.evidence_number.clone().unwrap_or_default(),
+            job.args.source_device.clone().unwrap_or_default(),
+            job.args.target_filename.clone().unwrap_or_default(),
+            job.args.target_dirs.join(", ")
+        );
+        let read = job.read.clone();
+        let started = job.started;
+        Some(
+            LinearLayout::vertical()
+                .child(tabs)
+                .child(TextView::new(details))
+                .child(TextView::new(job.status.clone()).with_id("Monitor status"))
+                .child(
+                    ProgressBar::new()
+                        .max(1000)
+                        .with_label(move |value, (_, max)| {
+                            progress_label(value, max, &read, started)
+                        })
+                        .with_value(job.counter.clone())
+                        .min_width(70),
+                ),
+        )
+    });
+    let view = match view {
+        Some(view) => view,
+        None => return main_menu(s),
+    };
+
+    s.add_layer(
+        Dialog::around(view)
+            .title("Acquisitions")
+            .button("New acquisition", new_acquisition)
+            .button("Stop", move |s| stop(s, id))
+            .button("Remove", move |s| remove(s, id))
+            .button("Main menu", main_menu),
+    );
+    if running() > 0 {
+        s.set_fps(2);
+    }
+}
+
+/// Start the wizard for another acquisition from the choice of profile, leaving the running ones
+/// alone.
+fn new_acquisition(s: &mut Cursive) {
+    reset_wizard();
+    choose_profile(s);
+}
+
+/// Ask to confirm stopping a running job, then stop it.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the monitor.
+/// * "Stop" - Stop the job; it stays in the monitor with its outcome.
+fn stop(s: &mut Cursive, id: usize) {
+    let abort = JOBS.with(|jobs| {
+        jobs.borrow()
+            .iter()
+            .find(|job| job.id == id && !job.finished)
+            .map(|job| job.abort.clone())
+    });
+    let abort = match abort {
+        Some(abort) => abort,
+        None => {
+            s.add_layer(Dialog::info("This acquisition has already finished."));
+            return;
+        }
+    };
+
+    s.add_layer(
+        Dialog::text(
+            "Stop this acquisition?\n\nNo report is written, but an acquisition to a raw image \
+             can be resumed later.",
+        )
+        .title("Stop acquisition")
+        .button("Back", |s| {
+            s.pop_layer();
+        })
+        .button("Stop", move |s| {
+            abort.store(true, Ordering::Relaxed);
+            s.pop_layer();
+        }),
+    );
+}
+
+/// Remove a finished job from the monitor, then show the next job or the main menu.
+fn remove(s: &mut Cursive, id: usize) {
+    let removed = JOBS.with(|jobs| {
+        let mut jobs = jobs.borrow_mut();
+        match jobs.iter().position(|job| job.id == id && job.finished) {
+            Some(index) => {
+                jobs.remove(index);
+                true
+            }
+            None => false,
+        }
+    });
+    if !removed {
+        s.add_layer(Dialog::info(
+            "This acquisition is still running. Stop it before removing it.",
+        ));
+        return;
+    }
+
+    match JOBS.with(|jobs| jobs.borrow().first().map(|job| job.id)) {
+        Some(next) => {
+            s.pop_layer();
+            show_job(s, next);
+        }
+        None => main_menu(s),
+    }
+}
@@ -0,0 +1,234 @@
+use cursive::view::{Boxable, Scrollable};
+use cursive::views::{Dialog, LinearLayout, ListView, SelectView, TextView};
+use cursive::Cursive;
+
+use pretty_bytes::converter::convert as format_bytes;
+use std::fs::File;
+use std::io;
+use std::thread;
+
+use grim_rust::acquire;
+use grim_rust::partition::{self, Partition, SourceRange};
+use grim_rust::sysinfo;
+
+use crate::windows::{
+    examiner_info, extract_entrybox_required, new_entry_box, select_source, ARGS,
+};
+
+/// Returns the selected source and the sector size set for it, if any.
+fn selected_source() -> (String, Option<u64>) {
+    ARGS.with(|args| {
+        let args = args.borrow();
+        (
+            args.source_device.clone().unwrap_or_default(),
+            args.bytes_per_sector.map(u64::from),
+        )
+    })
+}
+
+/// Record the part of the source to image, suggesting whether it is physical or logical, and
+/// continue to the examiner information form.
+fn use_range(s: &mut Cursive, range: &Option<SourceRange>) {
+    ARGS.with(|args| {
+        let mut args = args.borrow_mut();
+        args.source_range = range.clone();
+        args.media_flags = args.suggested_media_flags();
+    });
+    examiner_info(s);
+}
+
+/// Read the partition table and size of the selected source in the background, then display
+/// its partitions with `show_partitions`, so that a slow or failing drive doesn't freeze the
+/// interface.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+pub fn select_partition(s: &mut Cursive) {
+    let (device, sector_size) = selected_source();
+
+    s.pop_layer();
+    s.add_layer(Dialog::text("Reading partitions, please wait..."));
+
+    let cb_sink = s.cb_sink().clone();
+    thread::spawn(move || {
+        let sector_size = sector_size
+            .or_else(|| sysinfo::block_device_sector_size(&device))
+            .unwrap_or(512);
+        let partitions = File::open(&device)
+            .and_then(|mut source| partition::read_partitions(&mut source, sector_size));
+        let size = File::open(&device)
+            .and_then(|mut source| acquire::source_size(&mut source))
+            .ok();
+
+        let _ = cb_sink.send(Box::new(move |s: &mut Cursive| {
+            show_partitions(s, &device, sector_size, size, partitions);
+        }));
+    });
+}
+
+/// Display the partitions on the selected source, so a single partition or a range of sectors
+/// can be imaged instead of the whole source.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+/// * `device` - The path of the source.
+/// * `sector_size` - The source's logical sector size.
+/// * `size` - The size of the source in bytes, if it could be read.
+/// * `partitions` - The partitions found on the source.
+///
+/// # Buttons
+///
+/// * "Sector range" - Enter a range of sectors to image by calling `sector_range`.
+/// * "Back" - Return to the source selection form.
+/// * Submit the select view to image the whole source or the chosen partition.
+fn show_partitions(
+    s: &mut Cursive,
+    device: &str,
+    sector_size: u64,
+    size: Option<u64>,
+    partitions: io::Result<Vec<Partition>>,
+) {
+    s.pop_layer();
+
+    let mut select = SelectView::<Option<SourceRange>>::new().on_submit(use_range);
+    select.add_item(format!("Whole device ({})", device), None);
+
+    let message = match partitions {
+        Ok(ref partitions) if partitions.is_empty() => String::from("No partition table found."),
+        Ok(partitions) => {
+            for partition in partitions {
+                let range = SourceRange::from_partition(&partition, sector_size);
+                let mut text = format!(
+                    "{}: start {}, {} sectors ({})",
+                    partition,
+                    partition.start,
+                    partition.sectors,
+                    format_bytes(range.length() as f64)
+                );
+                if let Some(ref guid) = partition.guid {
+                    text.push_str(&format!(" {}", guid));
+                }
+                select.add_item(text, Some(range));
+            }
+            format!("Sector size: {} bytes", sector_size)
+        }
+        Err(e) => format!("Could not read the partition table: {}", e),
+    };
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(message))
+                .child(select.scrollable().max_height(20)),
+        )
+        .title("Select what to image")
+        .button("Sector range", move |s| sector_range(s, sector_size, size))
+        .button("Back", select_source),
+    );
+}
+
+/// Submit the sector range form
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+/// * `sector_size` - The source's logical sector size.
+/// * `size` - The size of the source in bytes, if it could be read.
+fn sector_range_next(s: &mut Cursive, sector_size: u64, size: Option<u64>) {
+    let mut start = None;
+    let mut sectors = None;
+    if extract_entrybox_required(s, "First sector", &mut start).is_err()
+        || extract_entrybox_required(s, "Sector count", &mut sectors).is_err()
+    {
+        return;
+    }
+
+    let start = match start.unwrap().parse::<u64>() {
+        Ok(start) => start,
+        Err(_) => {
+            s.add_layer(Dialog::info("The first sector must be a whole number."));
+            return;
+        }
+    };
+    let sectors = match sectors.unwrap().parse::<u64>() {
+        Ok(sectors) if sectors > 0 => sectors,
+        _ => {
+            s.add_layer(Dialog::info(
+                "The sector count must be a positive whole number.",
+            ));
+            return;
+        }
+    };
+
+    let range = SourceRange {
+        sector_size,
+        start,
+        sectors,
+        partition: None,
+    };
+    match size {
+        Some(size) if range.fits(size) => {}
+        Some(size) => {
+            s.add_layer(Dialog::info(format!(
+                "The source has {} sectors, so the range must end by sector {}.",
+                size / sector_size,
+                (size / sector_size).saturating_sub(1)
+            )));
+            return;
+        }
+        None => {
+            s.add_layer(Dialog::info(
+                "The size of the source couldn't be read, so a range of it can't be imaged.",
+            ));
+            return;
+        }
+    }
+
+    use_range(s, &Some(range));
+}
+
+/// Display the form for entering a range of sectors to image.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+/// * `sector_size` - The source's logical sector size.
+/// * `size` - The size of the source in bytes, if it could be read.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the partition list.
+/// * "Next" - Image the range by continuing to the examiner information form.
+fn sector_range(s: &mut Cursive, sector_size: u64, size: Option<u64>) {
+    let next = move |s: &mut Cursive, _: &str| sector_range_next(s, sector_size, size);
+
+    let mut description = format!("Sectors are {} bytes.", sector_size);
+    if let Some(size) = size {
+        description.push_str(&format!(" The source has {} sectors.", size / sector_size));
+    }
+
+    s.pop_layer();
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(description))
+                .child(
+                    ListView::new()
+                        .child(
+                            "First sector",
+                            new_entry_box("First sector", 20, &None, next),
+                        )
+                        .child(
+                            "Sector count",
+                            new_entry_box("Sector count", 20, &None, next),
+                        ),
+                ),
+        )
+        .padding((1, 1, 1, 0))
+        .title("Image a range of sectors")
+        .button("Back", select_partition)
+        .button("Next", move |s| sector_range_next(s, sector_size, size)),
+    );
+}
@@ -0,0 +1,326 @@
+use cursive::views::{Dialog, LinearLayout, ListView, TextView};
+use cursive::Cursive;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use grim_rust::ewfargs::{ArgsList, SourceKind};
+use grim_rust::job::{self, PreparedJob};
+use grim_rust::partition;
+use grim_rust::sysinfo;
+use grim_rust::LoggingInfo;
+
+use crate::file_browser::browse;
+use crate::partitions::select_partition;
+use crate::windows::{
+    examiner_info, extract_entrybox_required, main_menu, new_entry_box, select_source, use_source,
+    ARGS, CONFIG, INFO,
+};
+
+thread_local! {
+    /// The prepared job loaded into the wizard, if any, so the forms can mark the fields it set.
+    static PREPARED: RefCell<Option<PreparedJob>> = const { RefCell::new(None) };
+}
+
+/// The fields of a prepared job shown when it is loaded, by their names in the file, with the
+/// labels they have in the wizard.
+const FIELDS: &[(&str, &str)] = &[
+    ("examiner_name", "Examiner Name"),
+    ("case_number", "Case Number"),
+    ("evidence_number", "Evidence Number"),
+    ("description", "Description"),
+    ("notes", "Notes"),
+    ("custom_fields", "Custom fields"),
+    ("target_template", "Filename template"),
+    ("target_filename", "Filename"),
+    ("target_dirs", "Destinations"),
+    ("segment_file_size", "Segment size"),
+    ("ewf_format", "Target File Format"),
+    ("digests", "Generate hashes"),
+    ("compression_type", "Compression level"),
+    ("bytes_per_sector", "Bytes per sector"),
+    ("sectors_per_chunk", "Sectors to read at once"),
+    ("read_retries", "Read retries"),
+    ("error_granularity", "Error granularity"),
+    ("zero_whole_block", "Zero whole block"),
+    ("rescue", "Rescue mode"),
+    ("rescue_reverse", "Rescue backwards"),
+    ("media_type", "Media type"),
+    ("media_flags", "Media flags"),
+    ("read_buffer", "Read buffer"),
+    ("writer_threads", "Writer threads"),
+    ("auto_tune", "Auto-tune"),
+];
+
+/// Returns true if the field with the given name in a prepared job file was set by the loaded job.
+pub fn from_file(name: &str) -> bool {
+    PREPARED.with(|prepared| {
+        prepared
+            .borrow()
+            .as_ref()
+            .is_some_and(|job| job.is_from_file(name))
+    })
+}
+
+/// Returns the label for a wizard field, marked if its value was loaded from a prepared job.
+///
+/// # Arguments
+///
+/// * `label` - The label of the field.
+/// * `name` - The name of the field in a prepared job file.
+pub fn field_label(label: &str, name: &str) -> String {
+    if from_file(name) {
+        format!("{} (job file)", label)
+    } else {
+        label.to_string()
+    }
+}
+
+/// Forget the loaded prepared job, so the forms stop marking its fields.
+pub fn forget() {
+    PREPARED.with(|prepared| prepared.replace(None));
+}
+
+/// Returns true for files that could be prepared jobs.
+fn is_job_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+/// Start the flow to load a prepared job into the imaging wizard.
+pub fn start(s: &mut Cursive) {
+    browse(s, "Select a prepared job (.json)", is_job_file, load);
+}
+
+/// Load a prepared job, then show what it sets and any problems with it.
+///
+/// # Buttons
+///
+/// * "Cancel" - Return to the main menu.
+/// * "Continue" - Start the wizard with the job's fields filled in, by calling `continue_job`.
+fn load(s: &mut Cursive, path: PathBuf) {
+    let job = match PreparedJob::load(&path) {
+        Ok(job) => job,
+        Err(e) => {
+            s.add_layer(Dialog::info(format!(
+                "{} can't be loaded: {}",
+                path.display(),
+                e
+            )));
+            return;
+        }
+    };
+
+    let values = job::args_to_json(&job.args);
+    let mut text = format!("Loaded from {}:\n", path.display());
+    for (name, label) in FIELDS {
+        if job.is_from_file(name) {
+            let value = &values[*name];
+            let value = value
+                .as_str()
+                .map(String::from)
+                .unwrap_or_else(|| value.dump());
+            text.push_str(&format!("\n  {}: {}", label, value));
+        }
+    }
+
+    let located = job.source.as_ref().map(|source| source.locate());
+    match (&job.source, &located) {
+        (Some(source), Some(Ok(path))) => text.push_str(&format!(
+            "\n\nSource: {} ({} bytes{}) found at {}.",
+            source.path,
+            source.size,
+            source
+                .serial
+                .as_ref()
+                .map(|serial| format!(", serial number {}", serial))
+                .unwrap_or_default(),
+            path
+        )),
+        (Some(_), Some(Err(e))) => text.push_str(&format!(
+            "\n\nSource: not found. {}\nYou will be asked to choose the source.",
+            e
+        )),
+        _ => text.push_str("\n\nSource: not set. You will be asked to choose the source."),
+    }
+
+    let problems = CONFIG.with(|config| job.problems(&config.borrow()));
+    if !problems.is_empty() {
+        text.push_str("\n\nProblems to fix in the wizard:");
+        for problem in problems {
+            text.push_str(&format!("\n- {}", problem));
+        }
+    }
+    text.push_str("\n\nEvery field is checked again as you go through the wizard.");
+
+    let located = located.and_then(|located| located.ok());
+    PREPARED.with(|prepared| prepared.replace(Some(job)));
+    s.pop_layer();
+    s.add_layer(
+        Dialog::text(text)
+            .title("Prepared job")
+            .button("Cancel", main_menu)
+            .button("Continue", move |s| continue_job(s, located.clone())),
+    );
+}
+
+/// Fill in the wizard from the loaded job and start it, at the drive details if the job's source
+/// was found or at the list of sources if it wasn't.
+fn continue_job(s: &mut Cursive, located: Option<String>) {
+    let args = PREPARED.with(|prepared| prepared.borrow().as_ref().map(|job| job.args.clone()));
+    let mut args = match args {
+        Some(args) => args,
+        None => return main_menu(s),
+    };
+    args.source_device = None;
+    args.source_range = None;
+    ARGS.with(|current| current.replace(args.clone()));
+    INFO.with(|info| info.replace(LoggingInfo::new()));
+
+    let path = match located {
+        Some(path) => path,
+        None => return select_source(s),
+    };
+
+    let source = if args.source_kind == SourceKind::Device {
+        sysinfo::get_all_disks()
+            .ok()
+            .and_then(|disks| disks.into_iter().find(|disk| disk.logical_name == path))
+    } else {
+        sysinfo::describe_path(&path).ok()
+    };
+    match source {
+        Some(source) => {
+            if let Ok(host_serial) = sysinfo::get_pc_serial() {
+                INFO.with(|info| info.borrow_mut().host_serial = Some(host_serial));
+            }
+            use_source(s, &source, "Select a disk to image", restore_range);
+        }
+        None => select_source(s),
+    }
+}
+
+/// Check that the part of the source the job images is still there, then continue to the
+/// examiner information form. If the job images a partition that is no longer in the partition
+/// table, the examiner chooses what to image again.
+fn restore_range(s: &mut Cursive) {
+    let range = PREPARED.with(|prepared| {
+        prepared
+            .borrow()
+            .as_ref()
+            .and_then(|job| job.args.source_range.clone())
+    });
+    let range = match range {
+        Some(range) => range,
+        None => return examiner_info(s),
+    };
+
+    if let Some(ref expected) = range.partition {
+        let device = ARGS.with(|args| args.borrow().source_device.clone().unwrap_or_default());
+        let still_there = File::open(&device)
+            .and_then(|mut source| partition::read_partitions(&mut source, range.sector_size))
+            .map(|partitions| {
+                partitions.iter().any(|partition| {
+                    partition.number == expected.number
+                        && partition.start == expected.start
+                        && partition.sectors == expected.sectors
+                })
+            })
+            .unwrap_or(false);
+        if !still_there {
+            select_partition(s);
+            s.add_layer(Dialog::info(format!(
+                "Partition {} from the job is no longer on the source. Choose what to image.",
+                expected
+            )));
+            return;
+        }
+    }
+
+    ARGS.with(|args| args.borrow_mut().source_range = Some(range));
+    examiner_info(s);
+}
+
+/// Returns where a job prepared from `args` is saved unless the examiner chooses otherwise: next
+/// to the first copy of the image.
+fn default_path(args: &ArgsList) -> String {
+    let dir = args.target_dirs.first().cloned().unwrap_or_default();
+    Path::new(&dir)
+        .join(format!(
+            "{}.json",
+            args.target_filename.clone().unwrap_or_default()
+        ))
+        .display()
+        .to_string()
+}
+
+/// Submit the save job form
+fn save_next(s: &mut Cursive) {
+    let mut path = None;
+    if extract_entrybox_required(s, "Job file", &mut path).is_err() {
+        return;
+    }
+    let path = path.unwrap_or_default();
+
+    let args = ARGS.with(|args| args.borrow().clone());
+    let info = INFO.with(|info| info.borrow().clone());
+    let job = PreparedJob::new(&args, &info);
+    let mut text = match job.save(Path::new(&path)) {
+        Ok(()) => format!("Job saved to {}.", path),
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("The job can't be saved: {}", e)));
+            return;
+        }
+    };
+    if job.source.is_none() {
+        text.push_str(
+            "\n\nThe source couldn't be identified, so it will have to be chosen when the job \
+             is loaded.",
+        );
+    }
+
+    s.pop_layer();
+    s.add_layer(
+        Dialog::text(text)
+            .title("Job saved")
+            .button("Back", |s| {
+                s.pop_layer();
+            })
+            .button("Main menu", main_menu),
+    );
+}
+
+/// Display the form for saving the acquisition entered so far as a prepared job, to be loaded
+/// into the wizard later.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the technical options.
+/// * "Save" - Save the job, then offer to return to the technical options or the main menu.
+pub fn save(s: &mut Cursive) {
+    let path = ARGS.with(|args| default_path(&args.borrow()));
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "The job holds everything entered so far, and identifies the source by \
+                     its serial number and size.",
+                ))
+                .child(ListView::new().child(
+                    "Job file",
+                    new_entry_box("Job file", 4096, &Some(path), |s, _| save_next(s)),
+                )),
+        )
+        .padding((1, 1, 1, 0))
+        .title("Save job")
+        .button("Back", |s| {
+            s.pop_layer();
+        })
+        .button("Save", save_next),
+    );
+}
@@ -0,0 +1,197 @@
+use cursive::traits::Identifiable;
+use cursive::view::{Boxable, Scrollable};
+use cursive::views::{Dialog, SelectView};
+use cursive::Cursive;
+use pretty_bytes::converter::convert as format_bytes;
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use grim_rust::job::{self, JobState};
+
+use crate::monitor;
+use crate::windows::main_menu;
+
+thread_local! {
+    /// The interrupted jobs found at startup, with their state or why it couldn't be read.
+    static JOBS: RefCell<Vec<(PathBuf, Result<JobState, String>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Look for interrupted acquisitions and offer to resume them, or go straight to the main menu
+/// if there are none.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+///
+/// # Buttons
+///
+/// * "Resume" - Resume the selected job by calling `resume_selected`.
+/// * "Discard" - Forget the selected job, keeping its partial image.
+/// * "Later" - Continue to the main menu; the jobs will be offered again next time.
+pub fn check(s: &mut Cursive) {
+    let jobs: Vec<(PathBuf, Result<JobState, String>)> = job::find_interrupted()
+        .into_iter()
+        .map(|(path, state)| (path, state.map_err(|e| e.to_string())))
+        .collect();
+    if jobs.is_empty() {
+        main_menu(s);
+        return;
+    }
+
+    let mut select = SelectView::<usize>::new().on_submit(|s, _: &usize| resume_selected(s));
+    for (index, (path, state)) in jobs.iter().enumerate() {
+        select.add_item(describe(path, state), index);
+    }
+    JOBS.with(|list| *list.borrow_mut() = jobs);
+
+    s.pop_layer();
+    s.add_layer(
+        Dialog::around(
+            select
+                .with_id("Interrupted jobs")
+                .scrollable()
+                .max_height(12),
+        )
+        .title("Interrupted acquisitions")
+        .button("Resume", resume_selected)
+        .button("Discard", discard_selected)
+        .button("Later", main_menu),
+    );
+}
+
+/// Describe a job for the list of interrupted jobs.
+fn describe(path: &Path, state: &Result<JobState, String>) -> String {
+    match state {
+        Ok(state) => {
+            let done = state.bytes_done * 100 / state.source.size.max(1);
+            format!(
+                "{} ({} / {}): {}% of {} written, in {}",
+                state.args.target_filename.clone().unwrap_or_default(),
+                state.args.case_number.clone().unwrap_or_default(),
+                state.args.evidence_number.clone().unwrap_or_default(),
+                done,
+                format_bytes(state.source.size as f64),
+                path.parent().unwrap_or(path).display()
+            )
+        }
+        Err(e) => format!("{}: can't be read ({})", path.display(), e),
+    }
+}
+
+/// Returns the selected job.
+fn selected(s: &mut Cursive) -> Option<(PathBuf, Result<JobState, String>)> {
+    let index = s
+        .call_on_id("Interrupted jobs", |view: &mut SelectView<usize>| {
+            view.selection()
+        })
+        .and_then(|selection| selection)?;
+    JOBS.with(|list| list.borrow().get(*index).cloned())
+}
+
+/// Check that the selected job can be resumed and that its source is attached, then ask to
+/// confirm resuming it.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the list of interrupted jobs.
+/// * "Resume" - Resume the job in the background by calling `monitor::start`.
+fn resume_selected(s: &mut Cursive) {
+    let (path, state) = match selected(s) {
+        Some(job) => job,
+        None => return,
+    };
+    let mut state = match state {
+        Ok(state) => state,
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("The job can't be read: {}", e)));
+            return;
+        }
+    };
+
+    if !state.resumable() {
+        s.add_layer(Dialog::info(format!(
+            "ewfacquirestream can't append to an EWF image, so this acquisition can't be \
+             resumed. Discard the job, remove the partial image in {} and image the source again.",
+            path.parent().unwrap_or(&path).display()
+        )));
+        return;
+    }
+
+    if let Err(e) = state.locate_source() {
+        s.add_layer(Dialog::info(format!(
+            "The source of this job can't be found.\n\n{}",
+            e
+        )));
+        return;
+    }
+
+    let how = if state.args.ewf_format.is_raw() {
+        String::from("The image written so far will be read back to rebuild its digests first.")
+    } else {
+        format!(
+            "The rescue carries on from its map. ewfacquirestream can't append to an EWF image, \
+             so any {}.E01 segments already written to the destinations will be removed and \
+             the image written again from the rescued copy.",
+            state.args.target_filename.clone().unwrap_or_default()
+        )
+    };
+    let text = format!(
+        "Resume imaging {} ({}, serial number {}) from {} of {}?\n\n{}",
+        state.args.source_device.clone().unwrap_or_default(),
+        state.info.drive_product.clone().unwrap_or_default(),
+        state
+            .source
+            .serial
+            .clone()
+            .unwrap_or_else(|| String::from("unknown")),
+        format_bytes(state.bytes_done as f64),
+        format_bytes(state.source.size as f64),
+        how
+    );
+    s.add_layer(
+        Dialog::text(text)
+            .title("Resume acquisition")
+            .button("Back", |s| {
+                s.pop_layer();
+            })
+            .button("Resume", move |s| {
+                s.pop_layer();
+                let args = state.args.clone();
+                let info = state.info.clone();
+                monitor::start(s, args, info, Some((path.clone(), state.clone())));
+            }),
+    );
+}
+
+/// Ask to confirm discarding the selected job, then remove its job file and show the remaining jobs.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the list of interrupted jobs.
+/// * "Discard" - Remove the job file; the partial image is kept.
+fn discard_selected(s: &mut Cursive) {
+    let path = match selected(s) {
+        Some((path, _)) => path,
+        None => return,
+    };
+
+    s.add_layer(
+        Dialog::text(format!(
+            "Discard {}?\n\nThe acquisition won't be offered for resuming again. The partial \
+             image is not removed.",
+            path.display()
+        ))
+        .title("Discard job")
+        .button("Back", |s| {
+            s.pop_layer();
+        })
+        .button("Discard", move |s| {
+            s.pop_layer();
+            match job::remove(&path) {
+                Ok(()) => check(s),
+                Err(e) => s.add_layer(Dialog::info(format!("The job can't be removed: {}", e))),
+            }
+        }),
+    );
+}
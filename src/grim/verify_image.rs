@@ -0,0 +1,239 @@
+use cursive::utils::Counter;
+use cursive::views::{Dialog, LinearLayout, ListView, TextView};
+use cursive::Cursive;
+
+use chrono::Local;
+use std::cell::RefCell;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::thread;
+
+use grim_rust::ewfargs::DigestType;
+use grim_rust::ewfinfo;
+use grim_rust::image::is_ewf_first_segment;
+use grim_rust::report;
+use grim_rust::verify::{self, ImageVerification};
+
+use crate::file_browser::browse;
+use crate::windows::{
+    digest_summary, extract_entrybox_optional, extract_entrybox_required, main_menu, new_entry_box,
+    progress_dialog, ARGS,
+};
+
+// The image being verified, kept between forms
+thread_local! {
+    static IMAGE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Start the flow to verify an existing image.
+pub fn start(s: &mut Cursive) {
+    browse(
+        s,
+        "Select the first segment of an image to verify",
+        is_ewf_first_segment,
+        image_chosen,
+    );
+}
+
+/// Remember the chosen image and suggest where to write the report.
+fn image_chosen(s: &mut Cursive, image: PathBuf) {
+    ARGS.with(|args| {
+        let mut args = args.borrow_mut();
+        if let Some(dir) = image.parent() {
+            args.target_dirs = vec![dir.display().to_string()];
+        }
+        if let Some(stem) = image.file_stem() {
+            args.target_filename = Some(format!("{}_verification", stem.to_string_lossy()));
+        }
+    });
+    IMAGE.with(|cell| cell.replace(Some(image)));
+    verify_options(s);
+}
+
+/// Submit the verification options form
+fn verify_options_next(s: &mut Cursive) {
+    let success = ARGS.with(|args| -> bool {
+        let mut args = args.borrow_mut();
+        if extract_entrybox_required(s, "Examiner Name", &mut args.examiner_name).is_err() {
+            return false;
+        }
+        extract_entrybox_optional(s, "Case Number", &mut args.case_number);
+        extract_entrybox_optional(s, "Evidence Number", &mut args.evidence_number);
+
+        let mut report_dir = None;
+        if extract_entrybox_required(s, "Report directory", &mut report_dir).is_err()
+            || extract_entrybox_required(s, "Report filename", &mut args.target_filename).is_err()
+        {
+            return false;
+        }
+        args.target_dirs = report_dir.into_iter().collect();
+        true
+    });
+
+    if success {
+        verify_image(s);
+    }
+}
+
+/// Display the form for entering the examiner's details and where to write the report.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the file browser.
+/// * "Next" - Start verifying the image by calling `verify_image`.
+fn verify_options(s: &mut Cursive) {
+    s.pop_layer();
+
+    let image = IMAGE.with(|cell| cell.borrow().clone()).unwrap();
+    let fields = ARGS.with(|args| {
+        let args = args.borrow();
+        let next = |s: &mut Cursive, _: &str| verify_options_next(s);
+
+        ListView::new()
+            .child(
+                "Examiner Name",
+                new_entry_box("Examiner Name", 256, &args.examiner_name, next),
+            )
+            .child(
+                "Case Number",
+                new_entry_box("Case Number", 256, &args.case_number, next),
+            )
+            .child(
+                "Evidence Number",
+                new_entry_box("Evidence Number", 256, &args.evidence_number, next),
+            )
+            .child(
+                "Report directory",
+                new_entry_box(
+                    "Report directory",
+                    255,
+                    &args.target_dirs.first().cloned(),
+                    next,
+                ),
+            )
+            .child(
+                "Report filename (no extension)",
+                new_entry_box("Report filename", 255, &args.target_filename, next),
+            )
+    });
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(format!(
+                    "Image: {}\nEvery digest stored in the image is recalculated.",
+                    image.display()
+                )))
+                .child(fields),
+        )
+        .padding((1, 1, 1, 0))
+        .title("Verify image")
+        .button("Back", start)
+        .button("Next", verify_options_next),
+    );
+}
+
+/// Verify the image with ewfverify in the background, displaying its progress, then write the
+/// report and display whether the image passed.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+///
+/// # Buttons
+///
+/// * "Cancel" - Stop verifying; no report is written.
+fn verify_image(s: &mut Cursive) {
+    let args = ARGS.with(|args| args.borrow().clone());
+    let image = IMAGE.with(|cell| cell.borrow().clone()).unwrap();
+
+    let counter = Counter::new(0);
+    let abort = Arc::new(AtomicBool::new(false));
+
+    s.pop_layer();
+    s.add_layer(progress_dialog(
+        "Verifying",
+        format!("Verifying {}", image.display()),
+        counter.clone(),
+        abort.clone(),
+    ));
+    s.set_fps(2);
+
+    let cb_sink = s.cb_sink().clone();
+    thread::spawn(move || {
+        let report_path = report::report_path(Path::new(&args.target_dirs[0]), &args);
+        let result = (|| -> io::Result<ImageVerification> {
+            // Recalculate every digest the image has, so they can all be checked
+            let mut digest_type = DigestType::MD5;
+            if let Some(stored) = ewfinfo::run_ewfinfo(&image)?.digests() {
+                if stored.sha1.is_some() {
+                    digest_type |= DigestType::SHA1;
+                }
+                if stored.sha256.is_some() {
+                    digest_type |= DigestType::SHA256;
+                }
+            }
+
+            let started = Local::now();
+            let output = verify::run_ewfverify(
+                &image,
+                digest_type,
+                |done, total| counter.set((done * 1000 / total.max(1)) as usize),
+                &abort,
+            )?;
+            let verification = ImageVerification {
+                image: image.clone(),
+                started,
+                finished: Local::now(),
+                output,
+            };
+
+            report::write_report(
+                &report_path,
+                &report::verification_report(&args, &verification),
+            )?;
+            Ok(verification)
+        })();
+
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                s.set_fps(0);
+                s.pop_layer();
+                match result {
+                    Ok(verification) => {
+                        let verdict = if verification.output.passed() {
+                            "The image PASSED verification."
+                        } else {
+                            "The image FAILED verification."
+                        };
+                        let summary = format!(
+                            "{}\n\nStored:\n{}\n\nCalculated:\n{}\n\nReport written to {}",
+                            verdict,
+                            digest_summary(&verification.output.stored),
+                            digest_summary(&verification.output.calculated),
+                            report_path.display()
+                        );
+                        s.add_layer(
+                            Dialog::text(summary)
+                                .title("Verification complete")
+                                .button("Main menu", main_menu),
+                        );
+                    }
+                    Err(e) => {
+                        s.add_layer(
+                            Dialog::text(format!("Verification failed: {}", e))
+                                .title("Error")
+                                .button("Main menu", main_menu),
+                        );
+                    }
+                }
+            }))
+            .unwrap();
+    });
+}
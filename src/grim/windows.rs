@@ -1,13 +1,19 @@
 use cursive::traits::Identifiable;
-use cursive::view::Boxable;
+use cursive::utils::Counter;
+use cursive::view::{Boxable, Scrollable};
 use cursive::views::{
-    BoxView, Checkbox, Dialog, EditView, IdView, LinearLayout, ListView, SelectView, TextView,
+    BoxView, Button, Checkbox, Dialog, EditView, IdView, LinearLayout, ListView, ProgressBar,
+    SelectView, TextArea, TextView,
 };
 use cursive::Cursive;
 
+use chrono::Local;
 use pretty_bytes::converter::convert as format_bytes;
 use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
 // For now, we just use this to validate the input
 // It isn't guaranteed to stop ewfacquirestream from crashing, but it should help
@@ -15,26 +21,51 @@ use std::thread;
 use convert_byte_size_string::convert_to_bytes;
 
 extern crate grim_rust;
+use grim_rust::acquire;
+use grim_rust::config::{next_evidence_number, Config, Profile};
 use grim_rust::ewfargs;
-use grim_rust::ewfargs::ArgsList;
+use grim_rust::ewfargs::{ArgsList, MediaFlags, MediaType, SourceKind};
+use grim_rust::hash::Digests;
+use grim_rust::hpa;
+use grim_rust::report;
+use grim_rust::rescue::Phase;
+use grim_rust::smart;
 use grim_rust::sysinfo;
+use grim_rust::template;
+use grim_rust::tuning;
 use grim_rust::LoggingInfo;
 
+use crate::batch_queue;
+use crate::compare_drive;
+use crate::convert_image;
+use crate::hash_drive;
+use crate::image_info;
+use crate::monitor;
+use crate::partitions::select_partition;
+use crate::prepared_job::{self, field_label};
+use crate::verify_image;
+
 // Some things need to both be mutable and available to all our forms, so thread
-// local storage is the ideal solution
+// local storage is the ideal solution. These hold the acquisition being set up in the wizard
+// only: each acquisition takes its own copy when it starts, so another can be set up while it runs.
 thread_local! {
-    static ARGS: RefCell<ArgsList> = RefCell::new(ArgsList::new());
-    static INFO: RefCell<LoggingInfo> = RefCell::new(LoggingInfo::new());
+    pub static ARGS: RefCell<ArgsList> = RefCell::new(ArgsList::new());
+    pub static INFO: RefCell<LoggingInfo> = RefCell::new(LoggingInfo::new());
+    /// The settings read from the configuration file when grim started.
+    pub static CONFIG: RefCell<Config> = RefCell::new(Config::default());
+    /// The evidence number of the last acquisition set up since grim started, which the next one
+    /// follows on from when the configuration asks for it.
+    static LAST_EVIDENCE: RefCell<Option<String>> = const { RefCell::new(None) };
 }
 
 // This could be expanded later, but for now is just used when the field is blank
-enum ExtractionError {
+pub enum ExtractionError {
     Blank,
 }
 
 /// Return an IdView containing an Edit view, with the ID `name`.
 /// Please use this to generate all text inputs, as it makes the code much cleaner.
-fn new_entry_box<F>(
+pub fn new_entry_box<F>(
     name: &str,
     max_size: usize,
     default: &Option<String>,
@@ -73,7 +104,7 @@ where
 /// # Panics
 ///
 /// Panics when the entry box cannot be found.
-fn extract_entrybox_required(
+pub fn extract_entrybox_required(
     s: &mut Cursive,
     from: &str,
     to: &mut Option<String>,
@@ -87,7 +118,33 @@ fn extract_entrybox_required(
             s.add_layer(Dialog::info(format!("{} is required.", from)));
             Err(ExtractionError::Blank)
         }
-        None => panic!(format!("Can't find element with ID {}", from)),
+        None => panic!("Can't find element with ID {}", from),
+    }
+}
+
+/// Return an IdView containing a text area for several lines of text, with the ID `name`. Long
+/// lines wrap, and the area scrolls once it holds more lines than it shows.
+pub fn new_text_area(name: &str, default: &Option<String>) -> BoxView<IdView<TextArea>> {
+    TextArea::new()
+        .content(default.clone().unwrap_or_default())
+        .with_id(name)
+        .fixed_size((45, 4))
+}
+
+/// Extract the text from the text area with the given ID into the Option specified. Trailing
+/// blank lines and spaces are dropped; the line breaks between lines are kept.
+///
+/// # Panics
+///
+/// Panics when the text area cannot be found.
+pub fn extract_text_area(s: &mut Cursive, from: &str, to: &mut Option<String>) {
+    match s.call_on_id(from, |view: &mut TextArea| {
+        view.get_content().trim_end().to_string()
+    }) {
+        Some(value) => {
+            to.replace(value);
+        }
+        None => panic!("Can't find element with ID {}", from),
     }
 }
 
@@ -102,7 +159,7 @@ fn extract_entrybox_required(
 /// # Panics
 ///
 /// Panics when the entry box cannot be found.
-fn extract_entrybox_optional(s: &mut Cursive, from: &str, to: &mut Option<String>) {
+pub fn extract_entrybox_optional(s: &mut Cursive, from: &str, to: &mut Option<String>) {
     match s.call_on_id(from, |view: &mut EditView| view.get_content()) {
         Some(ref value) if !(*value).is_empty() => {
             to.replace((**value).clone());
@@ -110,7 +167,118 @@ fn extract_entrybox_optional(s: &mut Cursive, from: &str, to: &mut Option<String
         Some(_) => {
             to.replace("".to_string());
         }
-        None => panic!(format!("Can't find element with ID {}", from)),
+        None => panic!("Can't find element with ID {}", from),
+    }
+}
+
+/// Return a row of checkboxes for choosing digests, checked according to `digest_type`.
+/// Read the selection back with `extract_digests`.
+pub fn digest_boxes(digest_type: ewfargs::DigestType) -> LinearLayout {
+    let mut sha1_box = Checkbox::new();
+    let mut sha256_box = Checkbox::new();
+
+    // Check the boxes for any digests that are already specified
+    if (digest_type & (ewfargs::DigestType::SHA1)) == ewfargs::DigestType::SHA1 {
+        sha1_box = sha1_box.checked();
+    }
+    if (digest_type & (ewfargs::DigestType::SHA256)) == ewfargs::DigestType::SHA256 {
+        sha256_box = sha256_box.checked();
+    }
+
+    LinearLayout::horizontal()
+        .child(Checkbox::new().checked().disabled())
+        .child(TextView::new("MD5 (required) "))
+        .child(sha1_box.with_id("SHA1"))
+        .child(TextView::new("SHA1   "))
+        .child(sha256_box.with_id("SHA256"))
+        .child(TextView::new("SHA256"))
+}
+
+/// Extract the digests chosen in the checkboxes created by `digest_boxes`.
+///
+/// # Panics
+///
+/// Panics when the checkboxes cannot be found.
+pub fn extract_digests(s: &mut Cursive) -> ewfargs::DigestType {
+    let mut hashes = ewfargs::DigestType::MD5;
+
+    hashes |= s
+        .call_on_id("SHA1", |view: &mut Checkbox| {
+            if view.is_checked() {
+                ewfargs::DigestType::SHA1
+            } else {
+                ewfargs::DigestType::MD5
+            }
+        })
+        .unwrap();
+    hashes |= s
+        .call_on_id("SHA256", |view: &mut Checkbox| {
+            if view.is_checked() {
+                ewfargs::DigestType::SHA256
+            } else {
+                ewfargs::DigestType::MD5
+            }
+        })
+        .unwrap();
+
+    hashes
+}
+
+/// Format digests for display, one per line.
+pub fn digest_summary(digests: &Digests) -> String {
+    let mut summary = format!("MD5:    {}", digests.md5);
+    if let Some(ref sha1) = digests.sha1 {
+        summary.push_str(&format!("\nSHA1:   {}", sha1));
+    }
+    if let Some(ref sha256) = digests.sha256 {
+        summary.push_str(&format!("\nSHA256: {}", sha256));
+    }
+    summary
+}
+
+/// Return a dialog showing the progress of a long-running task.
+///
+/// # Arguments
+///
+/// * `title` - The title of the dialog.
+/// * `status` - The initial status text; it can be changed later through the ID "Progress status".
+/// * `counter` - The progress of the task, in thousandths.
+/// * `abort` - Set when the user presses "Cancel".
+pub fn progress_dialog(
+    title: &str,
+    status: String,
+    counter: Counter,
+    abort: Arc<AtomicBool>,
+) -> Dialog {
+    Dialog::around(
+        LinearLayout::vertical()
+            .child(TextView::new(status).with_id("Progress status"))
+            .child(
+                ProgressBar::new()
+                    .max(1000)
+                    .with_label(|value, (_, max)| {
+                        format!("{:.1} %", value as f64 * 100.0 / max as f64)
+                    })
+                    .with_value(counter)
+                    .min_width(50),
+            ),
+    )
+    .title(title)
+    .button("Cancel", move |_| abort.store(true, Ordering::Relaxed))
+}
+
+/// Return a function that sets the status text of the dialog created by `progress_dialog`.
+/// The function can be sent to and called from a background thread.
+pub fn status_setter(s: &mut Cursive) -> impl Fn(String) + Send {
+    let cb_sink = s.cb_sink().clone();
+    move |status: String| {
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                s.call_on_id("Progress status", |view: &mut TextView| {
+                    view.set_content(status)
+                });
+            }))
+            .unwrap();
     }
 }
 
@@ -122,13 +290,29 @@ fn extract_entrybox_optional(s: &mut Cursive, from: &str, to: &mut Option<String
 ///
 /// # Buttons
 ///
-/// * "Image" - Start the flow to image a hard drive by running `examiner_info`.
+/// * "Image" - Start the flow to image a hard drive by running `choose_profile`.
+/// * "Load" - Load a prepared job into the imaging flow.
+/// * "Queue" - Show the queue of drives to image one after another or side by side.
+/// * "Monitor" - Show the acquisitions running in the background.
+/// * "Hash" - Start the flow to hash a hard drive without imaging it.
+/// * "Compare" - Start the flow to compare a hard drive against an existing image.
+/// * "Verify" - Start the flow to verify an existing image.
+/// * "Info" - Show the metadata stored in an existing image.
+/// * "Convert" - Start the flow to export an existing image to another format.
 /// * "Options" - Start the flow to edit the application options; currently unimplemented.
 /// * "Exit" - Exit the application.
 pub fn main_menu(s: &mut Cursive) {
     /// Describes possible options for the user to choose in the menu.
     enum MenuOptions {
         Image,
+        Load,
+        Queue,
+        Monitor,
+        Hash,
+        Compare,
+        Verify,
+        Info,
+        Convert,
         Settings,
         Exit,
     }
@@ -141,30 +325,165 @@ pub fn main_menu(s: &mut Cursive) {
     fn on_submit(s: &mut Cursive, selection: &MenuOptions) {
         match selection {
             MenuOptions::Image => {
-                INFO.with(|info| {
-                    info.replace(LoggingInfo::new());
-                });
-                ARGS.with(|args| {
-                    args.replace(ArgsList::new());
-                });
-                select_source(s);
+                reset_wizard();
+                choose_profile(s);
+            }
+            MenuOptions::Load => prepared_job::start(s),
+            MenuOptions::Queue => batch_queue::show(s),
+            MenuOptions::Monitor => monitor::show(s),
+            MenuOptions::Hash => {
+                reset_wizard();
+                hash_drive::start(s);
+            }
+            MenuOptions::Compare => {
+                reset_wizard();
+                compare_drive::start(s);
+            }
+            MenuOptions::Verify => {
+                reset_wizard();
+                verify_image::start(s);
+            }
+            MenuOptions::Info => image_info::start(s),
+            MenuOptions::Convert => {
+                reset_wizard();
+                convert_image::start(s);
             }
             MenuOptions::Settings => (), // Settings page to come later
-            MenuOptions::Exit => s.quit(),
+            MenuOptions::Exit => exit(s),
         }
     }
 
+    prepared_job::forget();
     s.pop_layer();
 
     let mut select = SelectView::<MenuOptions>::new().on_submit(on_submit);
 
     select.add_item("Image:    Image a hard drive", MenuOptions::Image);
+    select.add_item("Load:     Load a prepared job", MenuOptions::Load);
+    match batch_queue::queued() {
+        0 => select.add_item("Queue:    Image several drives", MenuOptions::Queue),
+        queued => select.add_item(
+            format!("Queue:    Image several drives ({} queued)", queued),
+            MenuOptions::Queue,
+        ),
+    }
+    select.add_item(
+        format!(
+            "Monitor:  Running acquisitions ({} running, {} finished)",
+            monitor::running(),
+            monitor::count() - monitor::running()
+        ),
+        MenuOptions::Monitor,
+    );
+    select.add_item("Hash:     Hash a hard drive", MenuOptions::Hash);
+    select.add_item(
+        "Compare:  Compare a hard drive to an image",
+        MenuOptions::Compare,
+    );
+    select.add_item("Verify:   Verify an image", MenuOptions::Verify);
+    select.add_item("Info:     Show an image's information", MenuOptions::Info);
+    select.add_item("Convert:  Export or convert an image", MenuOptions::Convert);
     select.add_item("Settings: (unavailable)", MenuOptions::Settings);
     select.add_item("Exit:     Exit grim", MenuOptions::Exit);
 
     s.add_layer(Dialog::around(select).title(format!("grim {}", env!("CARGO_PKG_VERSION"))));
 }
 
+/// Quit grim, first asking to confirm stopping any acquisitions that are still running.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the main menu.
+/// * "Stop and exit" - Stop every running acquisition, then quit once they have stopped.
+fn exit(s: &mut Cursive) {
+    let running = monitor::running() + if batch_queue::running() { 1 } else { 0 };
+    if running == 0 {
+        s.quit();
+        return;
+    }
+
+    s.add_layer(
+        Dialog::text(format!(
+            "{} still running. Stop {} and exit?\n\nNo reports are written for stopped \
+             acquisitions, but acquisitions to raw images and rescues can be resumed later.",
+            if running == 1 {
+                String::from("An acquisition is")
+            } else {
+                format!("{} acquisitions are", running)
+            },
+            if running == 1 { "it" } else { "them" }
+        ))
+        .title("Exit grim")
+        .button("Back", |s| {
+            s.pop_layer();
+        })
+        .button("Stop and exit", |s| {
+            monitor::stop_all();
+            batch_queue::stop();
+            s.pop_layer();
+            s.add_layer(Dialog::text("Stopping acquisitions, please wait..."));
+            quit_when_stopped(s);
+        }),
+    );
+}
+
+/// Quit grim once every acquisition has stopped, so that each can save its state for resuming.
+fn quit_when_stopped(s: &mut Cursive) {
+    if monitor::running() == 0 && !batch_queue::running() {
+        s.quit();
+        return;
+    }
+
+    let cb_sink = s.cb_sink().clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        let _ = cb_sink.send(Box::new(quit_when_stopped));
+    });
+}
+
+/// Forget everything entered in the wizard, so that the next workflow starts from the defaults.
+pub fn reset_wizard() {
+    INFO.with(|info| info.replace(LoggingInfo::new()));
+    ARGS.with(|args| args.replace(ArgsList::new()));
+}
+
+/// Display the examiner profiles from the configuration, so the examiner's usual settings can fill
+/// in the wizard. Goes straight to `select_source` if the configuration has no profiles.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+///
+/// # Buttons
+///
+/// * "Cancel" - Return to the main menu.
+/// * Submit the select view to fill in the profile's settings and continue to `select_source`.
+pub fn choose_profile(s: &mut Cursive) {
+    let profiles = CONFIG.with(|config| config.borrow().profiles.clone());
+    if profiles.is_empty() {
+        select_source(s);
+        return;
+    }
+
+    let mut select = SelectView::<Option<Profile>>::new().on_submit(|s, profile| {
+        if let Some(profile) = profile {
+            ARGS.with(|args| profile.apply(&mut args.borrow_mut()));
+        }
+        select_source(s);
+    });
+    for profile in profiles {
+        select.add_item(profile.name.clone(), Some(profile));
+    }
+    select.add_item("No profile", None);
+
+    s.pop_layer();
+    s.add_layer(
+        Dialog::around(select)
+            .title("Choose a profile")
+            .button("Cancel", main_menu),
+    );
+}
+
 /// Display the form for selecting a drive to image.
 ///
 /// # Arguments
@@ -174,21 +493,298 @@ pub fn main_menu(s: &mut Cursive) {
 /// # Buttons
 ///
 /// * "Cancel" - Return to the main menu.
-/// * Submit the select view to continue to the examiner information form.
+/// * Submit the select view to continue to the partition selection form.
 pub fn select_source(s: &mut Cursive) {
-    fn on_submit(s: &mut Cursive, selection: &sysinfo::HdInfo) {
+    select_source_then(s, "Select a disk to image", select_partition);
+}
+
+/// Record the chosen source in `ARGS` and `INFO`, then show the drive details for a physical
+/// drive or continue straight to `next` for any other source.
+pub fn use_source(
+    s: &mut Cursive,
+    selection: &sysinfo::HdInfo,
+    title: &'static str,
+    next: fn(&mut Cursive),
+) {
+    INFO.with(|info| {
+        let mut info = info.borrow_mut();
+        info.drive_product = Some(selection.product.clone());
+        info.drive_serial = Some(selection.serial.clone());
+        info.drive_size = Some(format!("{} {}", selection.size, selection.units));
+    });
+    ARGS.with(|args| {
+        let mut args = args.borrow_mut();
+        args.source_device = Some(selection.logical_name.clone());
+        args.source_range = None;
+        if selection.physical {
+            args.source_kind = SourceKind::Device;
+        } else {
+            args.source_kind = SourceKind::Other;
+            args.bytes_per_sector = selection.sector_size.map(|size| size as u32);
+        }
+        // Suggest the media from the source, unless a prepared job already says what it is
+        if !prepared_job::from_file("media_type") {
+            args.media_type = sysinfo::suggest_media_type(&selection.logical_name);
+        }
+        if !prepared_job::from_file("media_flags") {
+            args.media_flags = args.suggested_media_flags();
+        }
+    });
+
+    if selection.physical {
+        drive_details(s, title, next);
+    } else {
         INFO.with(|info| {
             let mut info = info.borrow_mut();
-            info.drive_product = Some(selection.product.clone());
-            info.drive_serial = Some(selection.serial.clone());
+            info.hidden_areas = None;
+            info.smart = None;
         });
-        ARGS.with(|args| {
-            let mut args = args.borrow_mut();
-            args.source_device = Some(selection.logical_name.clone());
-        });
-        examiner_info(s);
+        next(s);
+    }
+}
+
+/// Temporarily remove the HPA from the selected drive, after asking the examiner to confirm.
+fn remove_hpa(s: &mut Cursive, next: fn(&mut Cursive)) {
+    let device = ARGS.with(|args| args.borrow().source_device.clone().unwrap_or_default());
+    let native = INFO.with(|info| {
+        info.borrow()
+            .hidden_areas
+            .as_ref()
+            .and_then(|hidden| hidden.max_sectors)
+            .map(|max| max.native)
+    });
+    let native = match native {
+        Some(native) => native,
+        None => return,
+    };
+
+    s.add_layer(
+        Dialog::text(format!(
+            "This sends a volatile SET MAX ADDRESS command to {}, making all {} native \
+             sectors visible until the drive is next power cycled.\n\n\
+             The drive's contents are not changed, but the command is a write to the drive's \
+             configuration and will be recorded in the report.",
+            device, native
+        ))
+        .title("Remove HPA?")
+        .button("Cancel", |s| {
+            s.pop_layer();
+        })
+        .button("Remove HPA", move |s| {
+            s.pop_layer();
+            match hpa::remove_hpa(&device, native) {
+                Ok(()) => {
+                    INFO.with(|info| {
+                        if let Some(ref mut hidden) = info.borrow_mut().hidden_areas {
+                            hidden.hpa_removed = true;
+                        }
+                    });
+                    next(s);
+                }
+                Err(e) => s.add_layer(Dialog::info(format!("Could not remove the HPA: {}", e))),
+            }
+        }),
+    );
+}
+
+/// Read the SMART data of a drive, treating any error as no data being available.
+fn read_smart_data(device: &str) -> Option<smart::SmartSnapshot> {
+    smart::read_smart(device).ok().and_then(|smart| smart)
+}
+
+/// Read the details of the selected drive in the background, then display them with
+/// `show_drive_details`. hdparm and smartctl can take a long time to give up on a failing drive,
+/// so a message is shown while they run.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+/// * `title` - The title of the disk list to return to.
+/// * `next` - The form to continue to.
+fn drive_details(s: &mut Cursive, title: &'static str, next: fn(&mut Cursive)) {
+    let device = ARGS.with(|args| args.borrow().source_device.clone().unwrap_or_default());
+
+    s.pop_layer();
+    s.add_layer(Dialog::text("Reading drive details, please wait..."));
+
+    let cb_sink = s.cb_sink().clone();
+    thread::spawn(move || {
+        let hidden = hpa::detect(&device);
+        let sector_size = sysinfo::block_device_sector_size(&device);
+        let smart = read_smart_data(&device);
+
+        let _ = cb_sink.send(Box::new(move |s: &mut Cursive| {
+            show_drive_details(s, &device, hidden, sector_size, smart, title, next);
+        }));
+    });
+}
+
+/// Display the details of the selected drive, including any Host Protected Area or Device
+/// Configuration Overlay found on it and its SMART health, with a warning if it looks unhealthy.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+/// * `device` - The path of the drive.
+/// * `hidden` - The hidden areas found on the drive.
+/// * `sector_size` - The drive's sector size, if it could be read.
+/// * `smart` - The drive's SMART data, if it reported any.
+/// * `title` - The title of the disk list to return to.
+/// * `next` - The form to continue to.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the list of disks.
+/// * "Remove HPA" - Only shown if an HPA was found; temporarily remove it, then continue to `next`.
+/// * "Continue" - Continue to `next` without changing the drive.
+fn show_drive_details(
+    s: &mut Cursive,
+    device: &str,
+    hidden: hpa::HiddenAreas,
+    sector_size: Option<u64>,
+    smart: Option<smart::SmartSnapshot>,
+    title: &'static str,
+    next: fn(&mut Cursive),
+) {
+    let sector_size = sector_size
+        .map(|size| format!("{} bytes", size))
+        .unwrap_or_else(|| String::from("unknown"));
+
+    let mut text = INFO.with(|info| {
+        let info = info.borrow();
+        format!(
+            "Device:       {}\nProduct:      {}\nSerial:       {}\nSize:         {}\n\
+             Sector size:  {}\n\nHPA: {}\nDCO: {}",
+            device,
+            info.drive_product.clone().unwrap_or_default(),
+            info.drive_serial.clone().unwrap_or_default(),
+            info.drive_size.clone().unwrap_or_default(),
+            sector_size,
+            hidden.hpa_summary(),
+            hidden.dco_summary()
+        )
+    });
+    if hidden.has_hpa() {
+        text.push_str("\n\nThe sectors hidden by the HPA will not be imaged unless it is removed.");
+    }
+    if hidden.has_dco() {
+        text.push_str(
+            "\n\nA DCO can only be removed permanently, so grim will not remove it. \
+             The sectors it hides will not be imaged.",
+        );
+    }
+
+    match smart {
+        Some(ref smart) => {
+            let count = |value: Option<u64>| {
+                value
+                    .map(|value| value.to_string())
+                    .unwrap_or_else(|| String::from("unknown"))
+            };
+            text.push_str(&format!(
+                "\n\nSMART health: {}\nPower-on hours: {}\n\
+                 Reallocated sectors: {}\nPending sectors: {}",
+                smart.health(),
+                count(smart.power_on_hours),
+                count(smart.reallocated_sectors),
+                count(smart.pending_sectors)
+            ));
+            if smart.looks_unhealthy() {
+                text.push_str("\n\nWARNING: this drive looks unhealthy.");
+                for warning in smart.warnings() {
+                    text.push_str(&format!("\n- {}", warning));
+                }
+                text.push_str(
+                    "\n\nImaging a failing drive can finish it off. Consider turning on rescue \
+                     mode in the technical options: it copies the readable areas first, skips \
+                     bad areas quickly rather than retrying them, and comes back to them in \
+                     later passes.",
+                );
+            }
+        }
+        None => text.push_str("\n\nSMART: no data available"),
+    }
+
+    let has_hpa = hidden.has_hpa();
+    INFO.with(|info| {
+        let mut info = info.borrow_mut();
+        info.hidden_areas = Some(hidden);
+        info.smart = smart;
+    });
+
+    s.pop_layer();
+    let mut dialog = Dialog::text(text)
+        .title("Drive details")
+        .button("Back", move |s| select_source_then(s, title, next));
+    if has_hpa {
+        dialog = dialog.button("Remove HPA", move |s| remove_hpa(s, next));
     }
+    s.add_layer(dialog.button("Continue", next));
+}
+
+/// Submit the other source form
+fn other_source_next(s: &mut Cursive, title: &'static str, next: fn(&mut Cursive)) {
+    let mut path = None;
+    if extract_entrybox_required(s, "Source path", &mut path).is_err() {
+        return;
+    }
+
+    match sysinfo::describe_path(&path.unwrap()) {
+        Ok(source) => use_source(s, &source, title, next),
+        Err(e) => s.add_layer(Dialog::info(e.to_string())),
+    }
+}
 
+/// Display the form for choosing a source that isn't in the list of disks, such as a file, a loop
+/// device, an md or LVM volume, or a partition.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+/// * `title` - The title of the disk list to return to.
+/// * `next` - The form to continue to once a source is chosen.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the list of disks.
+/// * "Next" - Record the source in `ARGS` and `INFO` and continue to `next`.
+fn other_source(s: &mut Cursive, title: &'static str, next: fn(&mut Cursive)) {
+    s.pop_layer();
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "Enter the path of a block device or file.\n\
+                     It will be recorded as a non-physical source.",
+                ))
+                .child(ListView::new().child(
+                    "Source path",
+                    new_entry_box("Source path", 4096, &None, move |s, _| {
+                        other_source_next(s, title, next)
+                    }),
+                )),
+        )
+        .padding((1, 1, 1, 0))
+        .title("Choose other source")
+        .button("Back", move |s| select_source_then(s, title, next))
+        .button("Next", move |s| other_source_next(s, title, next)),
+    );
+}
+
+/// Display the form for selecting a source drive, for any workflow that needs one.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+/// * `title` - The title of the form.
+/// * `next` - The form to continue to once a drive is selected.
+///
+/// # Buttons
+///
+/// * "Other source" - Choose a file or block device that isn't listed, by calling `other_source`.
+/// * "Cancel" - Return to the main menu.
+/// * Submit the select view to record the drive in `ARGS` and `INFO` and continue to `next`.
+pub fn select_source_then(s: &mut Cursive, title: &'static str, next: fn(&mut Cursive)) {
     // We want to display a message while getting the list of hard drives, which
     // might take a while
     s.pop_layer();
@@ -206,7 +802,6 @@ pub fn select_source(s: &mut Cursive) {
                 .send(Box::new(move |s: &mut Cursive| {
                     s.pop_layer();
                     s.add_layer(Dialog::text(e.to_string()).button("Exit", Cursive::quit));
-                    return;
                 }))
                 .unwrap();
             return;
@@ -238,11 +833,13 @@ pub fn select_source(s: &mut Cursive) {
                     s.add_layer(
                         Dialog::text("ERROR: No disks found! Are you running as root?")
                             .title("Error")
+                            .button("Other source", move |s| other_source(s, title, next))
                             .button("Ok", main_menu),
                     );
                     return;
                 }
-                let mut select = SelectView::<sysinfo::HdInfo>::new().on_submit(on_submit);
+                let mut select = SelectView::<sysinfo::HdInfo>::new()
+                    .on_submit(move |s, selection| use_source(s, selection, title, next));
 
                 for disk in disks {
                     select.add_item(disk.0, disk.1);
@@ -251,7 +848,8 @@ pub fn select_source(s: &mut Cursive) {
                 s.pop_layer();
                 s.add_layer(
                     Dialog::around(select)
-                        .title("Select a disk to image")
+                        .title(title)
+                        .button("Other source", move |s| other_source(s, title, next))
                         .button("Cancel", main_menu),
                 );
             }))
@@ -259,28 +857,130 @@ pub fn select_source(s: &mut Cursive) {
     });
 }
 
+/// Return to wherever the source was chosen: the list of disks, or the image browser when
+/// converting an existing image.
+fn back_to_source(s: &mut Cursive) {
+    let source_kind = ARGS.with(|args| args.borrow().source_kind);
+    match source_kind {
+        SourceKind::Device | SourceKind::Other => select_partition(s),
+        SourceKind::Image => convert_image::start(s),
+    }
+}
+
+/// The fields of the examiner information form: the ID of each entry box, its name in job and
+/// configuration files, the most characters it takes unless the configuration sets a length,
+/// whether it is required, and whether it takes several lines of text.
+const EXAMINER_FIELDS: &[(&str, &str, usize, bool, bool)] = &[
+    ("Examiner Name", "examiner_name", 256, true, false),
+    ("Case Number", "case_number", 256, true, false),
+    ("Evidence Number", "evidence_number", 256, true, false),
+    ("Description", "description", 1024, false, true),
+    ("Notes", "notes", 4096, false, true),
+];
+
+/// Returns the field of `args` holding the examiner information field `name`.
+fn examiner_field<'a>(args: &'a mut ArgsList, name: &str) -> &'a mut Option<String> {
+    match name {
+        "examiner_name" => &mut args.examiner_name,
+        "case_number" => &mut args.case_number,
+        "evidence_number" => &mut args.evidence_number,
+        "description" => &mut args.description,
+        _ => &mut args.notes,
+    }
+}
+
+/// Returns the ID of the entry box for the custom field at `index` in the configuration.
+fn custom_field_id(index: usize) -> String {
+    format!("Custom field {}", index)
+}
+
 /// Submit the examiner info form
 fn examiner_info_next(s: &mut Cursive, _: &str) {
     let success = ARGS.with(|args| -> bool {
         let mut args = args.borrow_mut();
-        if extract_entrybox_required(s, "Examiner Name", &mut args.examiner_name).is_err()
-            || extract_entrybox_required(s, "Case Number", &mut args.case_number).is_err()
-            || extract_entrybox_required(s, "Evidence Number", &mut args.evidence_number).is_err()
-        {
-            return false;
+        for &(id, name, _, required, multi_line) in EXAMINER_FIELDS {
+            let field = examiner_field(&mut args, name);
+            if multi_line {
+                extract_text_area(s, id, field);
+            } else if required {
+                if extract_entrybox_required(s, id, field).is_err() {
+                    return false;
+                }
+            } else {
+                extract_entrybox_optional(s, id, field);
+            }
         }
-        extract_entrybox_optional(s, "Description", &mut args.description);
-        extract_entrybox_optional(s, "Notes", &mut args.notes);
         true
     });
 
     if !success {
         return;
     };
+
+    // Text areas can't limit what is typed into them, so check their length here
+    let problem = ARGS.with(|args| {
+        let mut args = args.borrow_mut();
+        CONFIG.with(|config| {
+            let config = config.borrow();
+            EXAMINER_FIELDS
+                .iter()
+                .filter(|&&(_, _, _, _, multi_line)| multi_line)
+                .find_map(|&(id, name, max_size, _, _)| {
+                    let value = examiner_field(&mut args, name).clone().unwrap_or_default();
+                    let max_size = config
+                        .field_rule(name)
+                        .and_then(|rule| rule.max_length)
+                        .unwrap_or(max_size);
+                    if value.chars().count() > max_size {
+                        Some(format!(
+                            "{} can be at most {} characters long.",
+                            id, max_size
+                        ))
+                    } else {
+                        None
+                    }
+                })
+        })
+    });
+    if let Some(problem) = problem {
+        s.add_layer(Dialog::info(problem));
+        return;
+    }
+
+    let custom_fields = CONFIG.with(|config| config.borrow().custom_fields.clone());
+    let mut values = Vec::new();
+    for (index, field) in custom_fields.iter().enumerate() {
+        let value = s
+            .call_on_id(&custom_field_id(index), |view: &mut EditView| {
+                view.get_content()
+            })
+            .map(|value| (*value).clone())
+            .unwrap_or_default();
+        if field.required && value.is_empty() {
+            s.add_layer(Dialog::info(format!("{} is required.", field.label)));
+            return;
+        }
+        values.push((field.label.clone(), value));
+    }
+    ARGS.with(|args| args.borrow_mut().custom_fields = values);
+
+    // Check the fields against the agency's rules, if the configuration sets any
+    let problem =
+        ARGS.with(|args| CONFIG.with(|config| config.borrow().field_problems(&args.borrow())));
+    if let Some(problem) = problem.into_iter().next() {
+        s.add_layer(Dialog::info(problem));
+        return;
+    }
+
+    let evidence_number = ARGS.with(|args| args.borrow().evidence_number.clone());
+    LAST_EVIDENCE.with(|last| last.replace(evidence_number));
     target_info(s);
 }
 
-/// Display the form for entering examiner and case information.
+/// Display the form for entering examiner and case information, followed by the custom fields from
+/// the configuration. Fields the configuration has help for show it underneath, and the evidence
+/// number is filled in with the one after the last one entered if the configuration asks for it.
+/// The description and notes take several lines, which wrap and scroll.
 ///
 /// # Arguments
 ///
@@ -288,83 +988,175 @@ fn examiner_info_next(s: &mut Cursive, _: &str) {
 ///
 /// # Buttons
 ///
-/// * "Back" - Return to the source selection form.
-/// * "Next" - Move on to the information required by libewf by calling `technical_options`.
+/// * "Back" - Return to the source selection form, or to the image browser when converting.
+/// * "Next" - Move on to the target information form by calling `target_info`.
 pub fn examiner_info(s: &mut Cursive) {
     s.pop_layer();
 
-    let fields = ARGS.with(|args| {
-        let args = args.borrow();
-        ListView::new()
-            .child(
-                "Examiner Name",
-                new_entry_box(
-                    "Examiner Name",
-                    256,
-                    &args.examiner_name,
-                    examiner_info_next,
-                ),
-            )
-            .child(
-                "Case Number",
-                new_entry_box("Case Number", 256, &args.case_number, examiner_info_next),
-            )
-            .child(
-                "Evidence Number",
+    let config = CONFIG.with(|config| config.borrow().clone());
+    let mut args = ARGS.with(|args| args.borrow().clone());
+    if config.auto_increment_evidence && args.evidence_number.is_none() {
+        args.evidence_number = LAST_EVIDENCE.with(|last| {
+            last.borrow()
+                .as_ref()
+                .and_then(|last| next_evidence_number(last))
+        });
+    }
+
+    let mut fields = ListView::new();
+    for &(id, name, max_size, _, multi_line) in EXAMINER_FIELDS {
+        let rule = config.field_rule(name);
+        let label = field_label(id, name);
+        let value = examiner_field(&mut args, name);
+        if multi_line {
+            fields = fields.child(&label, new_text_area(id, value));
+        } else {
+            fields = fields.child(
+                &label,
                 new_entry_box(
-                    "Evidence Number",
-                    256,
-                    &args.evidence_number,
+                    id,
+                    rule.and_then(|rule| rule.max_length).unwrap_or(max_size),
+                    value,
                     examiner_info_next,
                 ),
-            )
-            .child(
-                "Description",
-                new_entry_box("Description", 256, &args.description, examiner_info_next),
-            )
-            .child(
-                "Notes",
-                new_entry_box("Notes", 1024, &args.notes, examiner_info_next),
-            )
-    });
+            );
+        }
+        if let Some(help) = rule.and_then(|rule| rule.help.clone()) {
+            fields = fields.child("", TextView::new(help));
+        }
+    }
+    for (index, field) in config.custom_fields.iter().enumerate() {
+        let value = args
+            .custom_fields
+            .iter()
+            .find(|(label, _)| *label == field.label)
+            .map(|(_, value)| value.clone());
+        fields = fields.child(
+            &field_label(&field.label, "custom_fields"),
+            new_entry_box(
+                &custom_field_id(index),
+                field.rule.max_length.unwrap_or(256),
+                &value,
+                examiner_info_next,
+            ),
+        );
+        if let Some(ref help) = field.rule.help {
+            fields = fields.child("", TextView::new(help.clone()));
+        }
+    }
 
     s.add_layer(
         Dialog::around(fields)
             .padding((1, 1, 1, 0))
             .title("Examiner information")
-            .button("Back", select_source)
+            .button("Back", back_to_source)
             .button("Next", |s| examiner_info_next(s, "")),
     );
 }
 
+/// Add the directory typed into the new destination entry box to the list of destinations.
+///
+/// # Return values
+///
+/// Returns false, after displaying an infobox, if the directory has already been added.
+fn add_destination(s: &mut Cursive) -> bool {
+    let dir = s
+        .call_on_id("New destination", |view: &mut EditView| view.get_content())
+        .unwrap();
+    if dir.is_empty() {
+        return true;
+    }
+
+    let duplicate = s
+        .call_on_id("Destinations", |view: &mut SelectView<String>| {
+            view.iter().any(|(_, existing)| *existing == *dir)
+        })
+        .unwrap();
+    if duplicate {
+        s.add_layer(Dialog::info(format!("{} is already a destination.", dir)));
+        return false;
+    }
+
+    s.call_on_id("Destinations", |view: &mut SelectView<String>| {
+        view.add_item((*dir).clone(), (*dir).clone())
+    });
+    s.call_on_id("New destination", |view: &mut EditView| {
+        view.set_content("")
+    });
+    true
+}
+
+/// Remove the selected directory from the list of destinations.
+fn remove_destination(s: &mut Cursive) {
+    let callback = s
+        .call_on_id("Destinations", |view: &mut SelectView<String>| {
+            view.selected_id().map(|id| view.remove_item(id))
+        })
+        .unwrap();
+    if let Some(callback) = callback {
+        callback(s);
+    }
+}
+
+/// Returns the filename a filename template typed into the target info form will be saved as, or
+/// why it can't be used.
+fn filename_preview(text: &str, args: &ArgsList) -> String {
+    INFO.with(
+        |info| match template::filename(text, args, &info.borrow(), Local::now()) {
+            Ok(filename) => filename,
+            Err(e) => e,
+        },
+    )
+}
+
+/// Show what the filename typed into the target info form will be saved as.
+fn update_filename_preview(s: &mut Cursive, text: &str) {
+    let preview = ARGS.with(|args| filename_preview(text, &args.borrow()));
+    s.call_on_id("Filename preview", |view: &mut TextView| {
+        view.set_content(preview)
+    });
+}
+
 /// Submit the target info form
 fn target_info_next(s: &mut Cursive) {
+    // Anything left in the new destination box was meant to be added
+    if !add_destination(s) {
+        return;
+    }
+
     let success = ARGS.with(|args| -> bool {
         let mut args = args.borrow_mut();
-        if extract_entrybox_required(s, "Filename", &mut args.target_filename).is_err()
-            || extract_entrybox_required(s, "Target directory", &mut args.target_dir).is_err()
-        {
+        let mut filename = None;
+        if extract_entrybox_required(s, "Filename", &mut filename).is_err() {
             return false;
         }
-
-        let secondary_enabled = s
-            .call_on_id("Two copies", |view: &mut Checkbox| -> bool {
-                view.is_checked()
-            })
-            .unwrap();
-
-        if secondary_enabled {
-            if extract_entrybox_required(
-                s,
-                "Secondary target directory",
-                &mut args.secondary_target_dir,
+        // Keep the filename as typed, so going back shows the placeholders rather than what they
+        // were filled in with, and later changes to the case fields are picked up
+        args.target_template = filename;
+        let filename = INFO.with(|info| {
+            template::filename(
+                args.target_template.as_deref().unwrap_or_default(),
+                &args,
+                &info.borrow(),
+                Local::now(),
             )
-            .is_err()
-            {
+        });
+        match filename {
+            Ok(filename) => args.target_filename = Some(filename),
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Invalid filename: {}", e)));
                 return false;
             }
-        } else {
-            args.secondary_target_dir = None;
+        }
+
+        args.target_dirs = s
+            .call_on_id("Destinations", |view: &mut SelectView<String>| {
+                view.iter().map(|(_, dir)| dir.clone()).collect()
+            })
+            .unwrap();
+        if args.target_dirs.is_empty() {
+            s.add_layer(Dialog::info("At least one destination is required."));
+            return false;
         }
 
         // Extract the value from the select boxes
@@ -385,29 +1177,7 @@ fn target_info_next(s: &mut Cursive) {
             )
             .unwrap();
 
-        // Extract the values from the checkboxes
-        let mut hashes = ewfargs::DigestType::MD5;
-
-        hashes |= s
-            .call_on_id("SHA1", |view: &mut Checkbox| {
-                if view.is_checked() {
-                    ewfargs::DigestType::SHA1
-                } else {
-                    ewfargs::DigestType::MD5
-                }
-            })
-            .unwrap();
-        hashes |= s
-            .call_on_id("SHA256", |view: &mut Checkbox| {
-                if view.is_checked() {
-                    ewfargs::DigestType::SHA256
-                } else {
-                    ewfargs::DigestType::MD5
-                }
-            })
-            .unwrap();
-
-        args.digest_type = hashes;
+        args.digest_type = extract_digests(s);
 
         let segment = s
             .call_on_id("Segment", |view: &mut Checkbox| view.is_checked())
@@ -435,8 +1205,45 @@ fn target_info_next(s: &mut Cursive) {
         true
     });
     if success {
+        header_warnings(s);
+    }
+}
+
+/// Warn about any case fields the chosen image format can't store as they were entered, showing
+/// what it will store instead, before moving on to `technical_options`.
+///
+/// # Buttons
+///
+/// * "Back" - Return to the target information form to change the format.
+/// * "Continue" - Keep the format and continue to `technical_options`.
+fn header_warnings(s: &mut Cursive) {
+    let (format, warnings) = ARGS.with(|args| {
+        let args = args.borrow();
+        (args.ewf_format, args.header_warnings())
+    });
+    if warnings.is_empty() {
         technical_options(s);
+        return;
     }
+
+    let text = format!(
+        "{} images keep the case fields in ASCII only, so tools reading the image will see \
+         \"?\" in place of some characters. The fields are passed to ewfacquirestream as \
+         entered, and the report keeps them as entered.\n\n{}",
+        format,
+        warnings.join("\n\n")
+    );
+    s.add_layer(
+        Dialog::around(TextView::new(text).scrollable().max_height(20))
+            .title("Image header")
+            .button("Back", |s| {
+                s.pop_layer();
+            })
+            .button("Continue", |s| {
+                s.pop_layer();
+                technical_options(s);
+            }),
+    );
 }
 
 /// Display the form for entering information about the target.
@@ -452,13 +1259,25 @@ fn target_info_next(s: &mut Cursive) {
 pub fn target_info(s: &mut Cursive) {
     s.pop_layer();
 
-    let mut two_copies = false;
     let mut segment = false;
     let fields = ARGS.with(|args| {
         let args = args.borrow();
 
-        two_copies = args.secondary_target_dir.is_some();
         segment = args.segment_file_size.is_some();
+        let filename = args
+            .target_template
+            .clone()
+            .or_else(|| args.target_filename.clone())
+            .or_else(|| CONFIG.with(|config| config.borrow().filename_template.clone()))
+            .unwrap_or_default();
+        let placeholders = format!(
+            "Placeholders: {}",
+            template::PLACEHOLDERS
+                .iter()
+                .map(|(name, _)| format!("{{{}}}", name))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
 
         let ewf_select = SelectView::<ewfargs::EwfFormat>::new()
             .popup()
@@ -473,27 +1292,11 @@ pub fn target_info(s: &mut Cursive) {
             .item("Linen6", ewfargs::EwfFormat::Linen6)
             .item("Linen7", ewfargs::EwfFormat::Linen7)
             .item("EwfX", ewfargs::EwfFormat::EwfX)
+            .item("Raw (dd)", ewfargs::EwfFormat::Raw)
             .selected(args.ewf_format as usize)
             .with_id("EwfFormat");
 
-        let mut sha1_box = Checkbox::new();
-        let mut sha256_box = Checkbox::new();
-
-        // Check the boxes for any digests that are already specified in args
-        if (args.digest_type & (ewfargs::DigestType::SHA1)) == ewfargs::DigestType::SHA1 {
-            sha1_box = sha1_box.checked();
-        }
-        if (args.digest_type & (ewfargs::DigestType::SHA256)) == ewfargs::DigestType::SHA256 {
-            sha256_box = sha256_box.checked();
-        }
-
-        let hash_boxes = LinearLayout::horizontal()
-            .child(Checkbox::new().checked().disabled())
-            .child(TextView::new("MD5 (required) "))
-            .child(sha1_box.with_id("SHA1"))
-            .child(TextView::new("SHA1   "))
-            .child(sha256_box.with_id("SHA256"))
-            .child(TextView::new("SHA256"));
+        let hash_boxes = digest_boxes(args.digest_type);
 
         let compression_select = SelectView::<ewfargs::CompressionType>::new()
             .popup()
@@ -506,38 +1309,47 @@ pub fn target_info(s: &mut Cursive) {
 
         ListView::new()
             .child(
-                "Filename (no extension)",
-                new_entry_box("Filename", 255, &args.target_filename, |s, _| {
-                    target_info_next(s)
-                }),
+                &field_label("Filename (no extension)", "target_filename"),
+                EditView::new()
+                    .on_edit(|s, text, _| update_filename_preview(s, text))
+                    .on_submit(|s, _| target_info_next(s))
+                    .max_content_width(255)
+                    .content(filename.clone())
+                    .with_id("Filename")
+                    .min_width(45),
             )
             .child(
-                "Target directory",
-                new_entry_box("Target directory", 255, &args.target_dir, |s, _| {
-                    target_info_next(s)
-                }),
+                "Saved as",
+                TextView::new(filename_preview(&filename, &args)).with_id("Filename preview"),
             )
+            .child("", TextView::new(placeholders))
             .child(
-                "Make two copies?",
-                Checkbox::new()
-                    .on_change(|s, checked| {
-                        s.call_on_id("Secondary target directory", |view: &mut EditView| {
-                            view.set_enabled(checked)
-                        });
-                    })
-                    .with_id("Two copies"),
+                &field_label("Destinations", "target_dirs"),
+                SelectView::<String>::new()
+                    .with_all(
+                        args.target_dirs
+                            .iter()
+                            .map(|dir| (dir.clone(), dir.clone())),
+                    )
+                    .with_id("Destinations"),
             )
             .child(
-                "Secondary target directory",
-                new_entry_box(
-                    "Secondary target directory",
-                    255,
-                    &args.secondary_target_dir,
-                    |s, _| target_info_next(s),
-                ),
+                "New destination",
+                new_entry_box("New destination", 255, &None, |s, _| {
+                    add_destination(s);
+                }),
+            )
+            .child(
+                "",
+                LinearLayout::horizontal()
+                    .child(Button::new("Add destination", |s| {
+                        add_destination(s);
+                    }))
+                    .child(TextView::new(" "))
+                    .child(Button::new("Remove selected", remove_destination)),
             )
             .child(
-                "Split image into segments?",
+                &field_label("Split image into segments?", "segment_file_size"),
                 Checkbox::new()
                     .on_change(|s, checked| {
                         s.call_on_id("Segment size", |view: &mut EditView| {
@@ -547,14 +1359,17 @@ pub fn target_info(s: &mut Cursive) {
                     .with_id("Segment"),
             )
             .child(
-                "Segment size",
+                &field_label("Segment size", "segment_file_size"),
                 new_entry_box("Segment size", 255, &args.segment_file_size, |s, _| {
                     target_info_next(s)
                 }),
             )
-            .child("Target File Format", ewf_select)
-            .child("Generate hashes", hash_boxes)
-            .child("Compression level", compression_select)
+            .child(&field_label("Target File Format", "ewf_format"), ewf_select)
+            .child(&field_label("Generate hashes", "digests"), hash_boxes)
+            .child(
+                &field_label("Compression level", "compression_type"),
+                compression_select,
+            )
     });
     s.add_layer(
         Dialog::around(fields)
@@ -563,16 +1378,7 @@ pub fn target_info(s: &mut Cursive) {
             .title("Target information"),
     );
 
-    // Set the checkbox for two copies or disable the secondary entry based on the current value
-    if two_copies {
-        s.call_on_id("Two copies", |view: &mut Checkbox| view.check());
-    } else {
-        s.call_on_id("Secondary target directory", |view: &mut EditView| {
-            view.set_enabled(false)
-        });
-    }
-
-    // Likewise for segment size
+    // Set the checkbox for segmenting or disable the segment size entry based on the current value
     if segment {
         s.call_on_id("Segment", |view: &mut Checkbox| view.check());
     } else {
@@ -582,26 +1388,33 @@ pub fn target_info(s: &mut Cursive) {
     }
 }
 
-fn technical_options_next(s: &mut Cursive) {
+/// Returns the label of a read buffer size, such as "256 KiB".
+fn buffer_label(size: usize) -> String {
+    if size >= 1024 * 1024 {
+        format!("{} MiB", size / (1024 * 1024))
+    } else {
+        format!("{} KiB", size / 1024)
+    }
+}
+
+/// Submit the technical options form, then continue to `then`
+fn technical_options_next(s: &mut Cursive, then: fn(&mut Cursive)) {
     let success = ARGS.with(|args| -> bool {
         let mut args = args.borrow_mut();
 
         args.bytes_per_sector =
-            match s.call_on_id("Bytes per sector", |view: &mut EditView| -> Option<i32> {
-                let v = view.get_content();
-                if v.is_empty() {
-                    None
-                } else {
-                    match v.parse::<i32>() {
-                        Err(_) => None,
-                        Ok(val) => Some(val),
-                    }
-                }
+            match s.call_on_id("Bytes per sector", |view: &mut EditView| -> Option<u32> {
+                view.get_content()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|bytes| ewfargs::valid_sector_size(*bytes))
             }) {
                 None => panic!("Can't find element with ID Bytes per sector"),
                 Some(opt) if opt.is_some() => Some(opt.unwrap()),
                 Some(_) => {
-                    s.add_layer(Dialog::info("Invalid value for Bytes per sector"));
+                    s.add_layer(Dialog::info(
+                        "Bytes per sector must be a power of two of at least 512",
+                    ));
                     return false;
                 }
             };
@@ -616,11 +1429,118 @@ fn technical_options_next(s: &mut Cursive) {
             )
             .unwrap();
 
+        let mut retries = None;
+        extract_entrybox_optional(s, "Read retries", &mut retries);
+        args.read_retries = match retries
+            .filter(|retries| !retries.is_empty())
+            .map(|retries| retries.parse::<u32>())
+        {
+            None => 0,
+            Some(Ok(retries)) => retries,
+            Some(Err(_)) => {
+                s.add_layer(Dialog::info("Invalid value for Read retries"));
+                return false;
+            }
+        };
+
+        let mut granularity = None;
+        extract_entrybox_optional(s, "Error granularity", &mut granularity);
+        args.error_granularity = match granularity
+            .filter(|granularity| !granularity.is_empty())
+            .map(|granularity| granularity.parse::<u32>())
+        {
+            None => None,
+            Some(Ok(granularity)) if granularity > 0 => Some(granularity),
+            Some(_) => {
+                s.add_layer(Dialog::info("Invalid value for Error granularity"));
+                return false;
+            }
+        };
+
+        args.zero_whole_block = s
+            .call_on_id("Zero whole block", |view: &mut Checkbox| view.is_checked())
+            .unwrap();
+
+        args.rescue = s
+            .call_on_id("Rescue", |view: &mut Checkbox| view.is_checked())
+            .unwrap();
+        args.rescue_reverse = s
+            .call_on_id("Rescue reverse", |view: &mut Checkbox| view.is_checked())
+            .unwrap();
+        args.media_type = s
+            .call_on_id("Media type", |view: &mut SelectView<MediaType>| {
+                *view.selection().unwrap()
+            })
+            .unwrap();
+        args.media_flags = s
+            .call_on_id("Media flags", |view: &mut SelectView<MediaFlags>| {
+                *view.selection().unwrap()
+            })
+            .unwrap();
+
+        args.read_buffer = s
+            .call_on_id("Read buffer", |view: &mut SelectView<Option<usize>>| {
+                *view.selection().unwrap()
+            })
+            .unwrap();
+        let sector_size = args.bytes_per_sector.unwrap_or(512) as usize;
+        if args
+            .read_buffer
+            .is_some_and(|size| !size.is_multiple_of(sector_size))
+        {
+            s.add_layer(Dialog::info(
+                "The read buffer must hold a whole number of sectors",
+            ));
+            return false;
+        }
+
+        let mut threads = None;
+        extract_entrybox_optional(s, "Writer threads", &mut threads);
+        args.writer_threads = match threads.filter(|threads| !threads.is_empty()) {
+            None => None,
+            Some(threads) => match threads.parse::<u32>() {
+                Ok(threads) if threads > 0 => Some(threads),
+                _ => {
+                    s.add_layer(Dialog::info("Invalid value for Writer threads"));
+                    return false;
+                }
+            },
+        };
+
+        args.auto_tune = s
+            .call_on_id("Auto-tune", |view: &mut Checkbox| view.is_checked())
+            .unwrap();
+        if args.rescue && args.source_kind == SourceKind::Image {
+            s.add_layer(Dialog::info(
+                "Rescue mode reads drives and files; an existing image can't be rescued",
+            ));
+            return false;
+        }
+
+        if let Some(path) = acquire::existing_rescue_file(&args) {
+            s.add_layer(Dialog::info(format!(
+                "{} already exists, and may be from another drive. Choose another filename or \
+                 destination, or move it out of the way. To carry on with an interrupted \
+                 rescue, resume its job instead.",
+                path.display()
+            )));
+            return false;
+        }
+
+        if let Some(path) = report::existing_report(&args) {
+            s.add_layer(Dialog::info(format!(
+                "{} already exists, and reports are never replaced. Choose another filename or \
+                 destination, or move the report out of the way.",
+                path.display()
+            )));
+            return false;
+        }
+
         true
     });
 
     if success {
-        s.add_layer(Dialog::info("We did it!"));
+        then(s);
     }
 }
 
@@ -633,7 +1553,11 @@ fn technical_options_next(s: &mut Cursive) {
 ///
 /// # Buttons
 ///
-/// * "Next" - Currently not implemented.
+/// * "Next" - Start the acquisition by calling `acquisition`.
+/// * "Save job" - Not shown when converting an image; save everything entered so far as a
+///   prepared job, by calling `prepared_job::save`.
+/// * "Add to queue" - Not shown when converting an image; queue the acquisition to run with
+///   others, by calling `batch_queue::add`.
 /// * "Back" - Return to the target information form.
 fn technical_options(s: &mut Cursive) {
     s.pop_layer();
@@ -655,27 +1579,149 @@ fn technical_options(s: &mut Cursive) {
             .item("8 Kilobytes", ewfargs::NumSectors::Sectors8192)
             .item("16 Kilobytes", ewfargs::NumSectors::Sectors16384)
             .item("32 Kilobytes", ewfargs::NumSectors::Sectors32768)
-            .selected(ewfargs::NumSectors::default() as usize);
+            .selected(args.num_sectors as usize);
 
-        let bytes_per_sector = match args.bytes_per_sector {
-            Some(v) => Some(v.to_string()),
-            None => None,
-        };
+        let bytes_per_sector = args.bytes_per_sector.map(|v| v.to_string());
+        let read_retries = Some(args.read_retries.to_string());
+        let error_granularity = args.error_granularity.map(|v| v.to_string());
+        let mut zero_whole_block = Checkbox::new();
+        if args.zero_whole_block {
+            zero_whole_block = zero_whole_block.checked();
+        }
+        let mut rescue = Checkbox::new();
+        if args.rescue {
+            rescue = rescue.checked();
+        }
+        let mut rescue_reverse = Checkbox::new();
+        if args.rescue_reverse {
+            rescue_reverse = rescue_reverse.checked();
+        }
+        let media_type = SelectView::<MediaType>::new()
+            .popup()
+            .item(MediaType::Fixed.to_string(), MediaType::Fixed)
+            .item(MediaType::Removable.to_string(), MediaType::Removable)
+            .item(MediaType::Optical.to_string(), MediaType::Optical)
+            .item(MediaType::Memory.to_string(), MediaType::Memory)
+            .selected(args.media_type as usize);
+        let media_flags = SelectView::<MediaFlags>::new()
+            .popup()
+            .item(MediaFlags::Physical.to_string(), MediaFlags::Physical)
+            .item(MediaFlags::Logical.to_string(), MediaFlags::Logical)
+            .selected(args.media_flags as usize);
+        let mut read_buffer = SelectView::<Option<usize>>::new()
+            .popup()
+            .item("One chunk", None);
+        for (index, size) in tuning::READ_BUFFER_SIZES.iter().enumerate() {
+            read_buffer.add_item(buffer_label(*size), Some(*size));
+            if args.read_buffer == Some(*size) {
+                read_buffer.set_selection(index + 1);
+            }
+        }
+        let writer_threads = args.writer_threads.map(|threads| threads.to_string());
+        let mut auto_tune = Checkbox::new();
+        if args.auto_tune {
+            auto_tune = auto_tune.checked();
+        }
+        let next = |s: &mut Cursive, _: &str| technical_options_next(s, acquisition);
 
         ListView::new()
             .child(
-                "Bytes per sector",
-                new_entry_box("Bytes per sector", 32, &bytes_per_sector, |s, _| {
-                    technical_options_next(s)
-                }),
+                &field_label("Bytes per sector", "bytes_per_sector"),
+                new_entry_box("Bytes per sector", 32, &bytes_per_sector, next),
+            )
+            .child(
+                &field_label("Sectors to read at once", "sectors_per_chunk"),
+                num_sectors_select,
+            )
+            .child(
+                &field_label("Read retries", "read_retries"),
+                new_entry_box("Read retries", 4, &read_retries, next),
+            )
+            .child(
+                &field_label(
+                    "Error granularity (sectors, blank for one chunk)",
+                    "error_granularity",
+                ),
+                new_entry_box("Error granularity", 8, &error_granularity, next),
+            )
+            .child(
+                &field_label("Zero the whole block on a read error", "zero_whole_block"),
+                zero_whole_block.with_id("Zero whole block"),
+            )
+            .child(
+                &field_label("Rescue mode (multi-pass, resumable)", "rescue"),
+                rescue.with_id("Rescue"),
+            )
+            .child(
+                &field_label("Rescue backwards on the first pass", "rescue_reverse"),
+                rescue_reverse.with_id("Rescue reverse"),
+            )
+            .child(
+                &field_label("Media type", "media_type"),
+                media_type.with_id("Media type"),
+            )
+            .child(
+                &field_label("Media flags", "media_flags"),
+                media_flags.with_id("Media flags"),
+            )
+            .child(
+                &field_label("Read buffer", "read_buffer"),
+                read_buffer.with_id("Read buffer"),
+            )
+            .child(
+                &field_label(
+                    "Writer threads (blank to write copies one at a time)",
+                    "writer_threads",
+                ),
+                new_entry_box("Writer threads", 4, &writer_threads, next),
+            )
+            .child(
+                &field_label(
+                    "Auto-tune (benchmark for a few seconds, then use the fastest)",
+                    "auto_tune",
+                ),
+                auto_tune.with_id("Auto-tune"),
             )
-            .child("Sectors to read at once", num_sectors_select)
     });
 
-    s.add_layer(
-        Dialog::around(fields)
-            .title("Technical options")
-            .button("Back", target_info)
-            .button("Next", |_| ()),
-    );
+    let mut dialog = Dialog::around(fields)
+        .title("Technical options")
+        .button("Back", target_info);
+    // Prepared jobs image drives and files; converting an existing image isn't prepared ahead
+    let source_kind = ARGS.with(|args| args.borrow().source_kind);
+    if source_kind != SourceKind::Image {
+        dialog = dialog
+            .button("Save job", |s| {
+                technical_options_next(s, prepared_job::save)
+            })
+            .button("Add to queue", |s| {
+                technical_options_next(s, batch_queue::add)
+            });
+    }
+    s.add_layer(dialog.button("Next", |s| technical_options_next(s, acquisition)));
+}
+
+/// Returns the status text for a phase of a rescue.
+pub fn rescue_status(args: &ArgsList, phase: Phase, pass: u32) -> String {
+    let source = args.source_device.clone().unwrap_or_default();
+    match phase {
+        Phase::Copying => format!("Rescuing {}: copying, pass {} of 2", source, pass),
+        Phase::Retrying => format!(
+            "Rescuing {}: retrying bad sectors, pass {} of {}",
+            source, pass, args.read_retries
+        ),
+        Phase::Finished => format!("Writing the image of {}", source),
+        _ => format!("Rescuing {}: {}", source, phase.to_string().to_lowercase()),
+    }
+}
+
+/// Start the acquisition entered in the wizard in the background and show it in the monitor.
+///
+/// # Arguments
+///
+/// * `s` - A mutable reference to the `Cursive` instance to display on.
+fn acquisition(s: &mut Cursive) {
+    let args = ARGS.with(|args| args.borrow().clone());
+    let info = INFO.with(|info| info.borrow().clone());
+    monitor::start(s, args, info, None);
 }
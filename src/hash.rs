@@ -0,0 +1,86 @@
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::ewfargs::DigestType;
+
+/// The digests calculated over a stream of data, as lowercase hex strings.
+#[derive(Clone, Default, PartialEq)]
+pub struct Digests {
+    /// The MD5 digest, which is always calculated.
+    pub md5: String,
+    /// The SHA1 digest, if it was requested.
+    pub sha1: Option<String>,
+    /// The SHA256 digest, if it was requested.
+    pub sha256: Option<String>,
+}
+
+impl Digests {
+    /// Returns true if every digest present in both `self` and `other` is the same. MD5 must
+    /// always be present and the same.
+    pub fn matches(&self, other: &Digests) -> bool {
+        fn same(a: &Option<String>, b: &Option<String>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+                _ => true,
+            }
+        }
+
+        !self.md5.is_empty()
+            && self.md5.eq_ignore_ascii_case(&other.md5)
+            && same(&self.sha1, &other.sha1)
+            && same(&self.sha256, &other.sha256)
+    }
+}
+
+/// Calculates every digest requested by a `DigestType` in a single pass over the data.
+#[derive(Clone)]
+pub struct Hasher {
+    md5: Md5,
+    sha1: Option<Sha1>,
+    sha256: Option<Sha256>,
+}
+
+impl Hasher {
+    /// Returns a new `Hasher` calculating MD5 plus any other digests in `digest_type`.
+    pub fn new(digest_type: DigestType) -> Hasher {
+        Hasher {
+            md5: Md5::new(),
+            sha1: if digest_type.contains(DigestType::SHA1) {
+                Some(Sha1::new())
+            } else {
+                None
+            },
+            sha256: if digest_type.contains(DigestType::SHA256) {
+                Some(Sha256::new())
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Feed a block of data to every digest.
+    pub fn update(&mut self, data: &[u8]) {
+        self.md5.input(data);
+        if let Some(ref mut sha1) = self.sha1 {
+            sha1.input(data);
+        }
+        if let Some(ref mut sha256) = self.sha256 {
+            sha256.input(data);
+        }
+    }
+
+    /// Consume the `Hasher` and return the finished digests.
+    pub fn finish(self) -> Digests {
+        Digests {
+            md5: to_hex(&self.md5.result()),
+            sha1: self.sha1.map(|h| to_hex(&h.result())),
+            sha256: self.sha256.map(|h| to_hex(&h.result())),
+        }
+    }
+}
+
+/// Format a byte slice as a lowercase hex string.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,273 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// The visible and native sizes of an ATA drive, as reported by `hdparm -N`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct MaxSectors {
+    /// The number of sectors the drive currently makes visible.
+    pub visible: u64,
+    /// The number of sectors the drive has without a Host Protected Area.
+    pub native: u64,
+    /// Whether hdparm reported the HPA as enabled.
+    pub hpa_enabled: bool,
+}
+
+/// What is known about the hidden areas of a drive.
+#[derive(Clone, Default)]
+pub struct HiddenAreas {
+    /// The visible and native sizes, if `hdparm -N` reported them.
+    pub max_sectors: Option<MaxSectors>,
+    /// The real number of sectors from the Device Configuration Overlay, if
+    /// `hdparm --dco-identify` reported it.
+    pub dco_max_sectors: Option<u64>,
+    /// Whether the HPA was temporarily removed before the drive was read.
+    pub hpa_removed: bool,
+}
+
+impl HiddenAreas {
+    /// Returns true if the drive has sectors hidden by a Host Protected Area.
+    pub fn has_hpa(&self) -> bool {
+        match self.max_sectors {
+            Some(max) => max.visible < max.native,
+            None => false,
+        }
+    }
+
+    /// Returns true if a Device Configuration Overlay hides sectors beyond the native size.
+    pub fn has_dco(&self) -> bool {
+        match (self.max_sectors, self.dco_max_sectors) {
+            (Some(max), Some(dco)) => dco > max.native,
+            _ => false,
+        }
+    }
+
+    /// Returns a description of the HPA for display and the report.
+    pub fn hpa_summary(&self) -> String {
+        match self.max_sectors {
+            None => String::from("unknown (hdparm could not read the drive's max sectors)"),
+            Some(max) if self.hpa_removed => format!(
+                "removed before reading ({} sectors were visible, {} native)",
+                max.visible, max.native
+            ),
+            Some(max) if max.visible < max.native => format!(
+                "PRESENT: {} of {} native sectors visible, {} hidden",
+                max.visible,
+                max.native,
+                max.native - max.visible
+            ),
+            Some(max) => format!("not present ({} sectors visible)", max.visible),
+        }
+    }
+
+    /// Returns a description of the DCO for display and the report.
+    pub fn dco_summary(&self) -> String {
+        match (self.max_sectors, self.dco_max_sectors) {
+            (_, None) => String::from("unknown (hdparm could not identify the DCO)"),
+            (Some(max), Some(dco)) if dco > max.native => format!(
+                "PRESENT: real max {} sectors, {} hidden beyond the native size",
+                dco,
+                dco - max.native
+            ),
+            (None, Some(dco)) => format!("real max {} sectors (native size unknown)", dco),
+            (_, Some(dco)) => format!("not present (real max {} sectors)", dco),
+        }
+    }
+}
+
+/// Parse the output of `hdparm -N`, such as
+/// ` max sectors   = 976771055/976773168, HPA is enabled`.
+pub fn parse_hdparm_max_sectors(output: &str) -> Option<MaxSectors> {
+    let line = output
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("max sectors"))?;
+
+    let value = line[line.find('=')? + 1..].trim();
+    let (sectors, state) = match value.find(',') {
+        Some(index) => (&value[..index], &value[index + 1..]),
+        None => (value, ""),
+    };
+    let mut sectors = sectors.split('/').map(|count| count.trim().parse::<u64>());
+    let visible = sectors.next()?.ok()?;
+    let native = sectors.next()?.ok()?;
+
+    // Bridges that don't pass the command through make hdparm print `0/1`
+    if visible == 0 {
+        return None;
+    }
+
+    Some(MaxSectors {
+        visible,
+        native,
+        hpa_enabled: state.contains("HPA is enabled"),
+    })
+}
+
+/// Parse the output of `hdparm --dco-identify`, returning the `Real max sectors` value.
+pub fn parse_dco_max_sectors(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("Real max sectors"))
+        .and_then(|line| line[line.find(':')? + 1..].trim().parse().ok())
+}
+
+/// Run hdparm with the given arguments against a drive, returning its stdout.
+fn hdparm(args: &[&str], device: &str) -> io::Result<String> {
+    let output = Command::new("hdparm").args(args).arg(device).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "hdparm failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Look for a Host Protected Area and a Device Configuration Overlay on an ATA drive. Drives
+/// hdparm can't query, such as USB bridges that don't pass ATA commands through, are reported
+/// as unknown rather than as an error.
+pub fn detect(device: &str) -> HiddenAreas {
+    HiddenAreas {
+        max_sectors: hdparm(&["-N"], device)
+            .ok()
+            .and_then(|output| parse_hdparm_max_sectors(&output)),
+        dco_max_sectors: hdparm(&["--dco-identify"], device)
+            .ok()
+            .and_then(|output| parse_dco_max_sectors(&output)),
+        hpa_removed: false,
+    }
+}
+
+/// Temporarily remove the HPA by setting the visible size to the native size. The change is
+/// volatile, so the drive returns to its original size at the next power cycle.
+///
+/// The kernel is then asked to rescan the drive, so the new size is visible when it is read.
+pub fn remove_hpa(device: &str, native: u64) -> io::Result<()> {
+    hdparm(&["-N", &native.to_string()], device)?;
+
+    let name = fs::canonicalize(device)?;
+    if let Some(name) = name.file_name() {
+        let rescan = Path::new("/sys/class/block")
+            .join(name)
+            .join("device/rescan");
+        if rescan.exists() {
+            fs::write(rescan, "1")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HDPARM_HPA: &str = "
+/dev/sda:
+ max sectors   = 976771055/976773168, HPA is enabled
+";
+
+    const HDPARM_NO_HPA: &str = "
+/dev/sda:
+ max sectors   = 976773168/976773168, HPA is disabled
+";
+
+    const HDPARM_USB_BRIDGE: &str = "
+/dev/sdb:
+SG_IO: bad/missing sense data, sb[]:  70 00 05 00 00 00 00 0a 00 00 00 00 20 00 00 c0 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+ max sectors   = 0/1, HPA is enabled
+";
+
+    const HDPARM_UNSUPPORTED: &str = "
+/dev/nvme0n1:
+";
+
+    const DCO_IDENTIFY: &str = "
+/dev/sda:
+DCO Revision: 0x0002
+The following features can be selectively disabled via DCO:
+\tTransfer modes:
+\t\t mdma0 mdma1 mdma2
+\t\t udma0 udma1 udma2 udma3 udma4 udma5 udma6
+\tReal max sectors: 976773168
+\tATA command/feature sets:
+\t\t SMART self_test error_log security HPA 48_bit
+\t\t (?): selective_test conveyance_test write_read_verify WWN
+\tSATA command/feature sets:
+\t\t (?): NCQ interface_power_management SSP
+";
+
+    const DCO_HIDDEN: &str = "
+/dev/sda:
+DCO Revision: 0x0001
+The following features can be selectively disabled via DCO:
+\tTransfer modes:
+\t\t udma0 udma1 udma2 udma3 udma4 udma5
+\tReal max sectors: 312581808
+\tATA command/feature sets:
+\t\t SMART self_test error_log security HPA 48_bit
+";
+
+    fn areas(hdparm: &str, dco: &str) -> HiddenAreas {
+        HiddenAreas {
+            max_sectors: parse_hdparm_max_sectors(hdparm),
+            dco_max_sectors: parse_dco_max_sectors(dco),
+            hpa_removed: false,
+        }
+    }
+
+    #[test]
+    fn parses_max_sectors_with_hpa() {
+        let max = parse_hdparm_max_sectors(HDPARM_HPA).unwrap();
+        assert_eq!(max.visible, 976_771_055);
+        assert_eq!(max.native, 976_773_168);
+        assert!(max.hpa_enabled);
+
+        let areas = areas(HDPARM_HPA, DCO_IDENTIFY);
+        assert!(areas.has_hpa());
+        assert!(!areas.has_dco());
+    }
+
+    #[test]
+    fn parses_max_sectors_without_hpa() {
+        let max = parse_hdparm_max_sectors(HDPARM_NO_HPA).unwrap();
+        assert_eq!(max.visible, 976_773_168);
+        assert_eq!(max.native, 976_773_168);
+        assert!(!max.hpa_enabled);
+
+        let areas = areas(HDPARM_NO_HPA, DCO_IDENTIFY);
+        assert!(!areas.has_hpa());
+        assert!(!areas.has_dco());
+    }
+
+    #[test]
+    fn unsupported_drives_have_no_max_sectors() {
+        assert!(parse_hdparm_max_sectors(HDPARM_USB_BRIDGE).is_none());
+        assert!(parse_hdparm_max_sectors(HDPARM_UNSUPPORTED).is_none());
+        assert!(parse_dco_max_sectors(HDPARM_UNSUPPORTED).is_none());
+
+        let areas = areas(HDPARM_USB_BRIDGE, HDPARM_UNSUPPORTED);
+        assert!(!areas.has_hpa());
+        assert!(!areas.has_dco());
+    }
+
+    #[test]
+    fn parses_dco_real_max_sectors() {
+        assert_eq!(parse_dco_max_sectors(DCO_IDENTIFY), Some(976_773_168));
+        assert_eq!(parse_dco_max_sectors(DCO_HIDDEN), Some(312_581_808));
+
+        let hidden = "\n/dev/sda:\n max sectors   = 234441648/234441648, HPA is disabled\n";
+        let areas = areas(hidden, DCO_HIDDEN);
+        assert!(!areas.has_hpa());
+        assert!(areas.has_dco());
+    }
+
+    #[test]
+    fn dco_without_max_sectors_is_not_reported_as_hidden() {
+        let areas = areas(HDPARM_UNSUPPORTED, DCO_HIDDEN);
+        assert!(!areas.has_dco());
+        assert_eq!(areas.dco_max_sectors, Some(312_581_808));
+    }
+}
@@ -0,0 +1,303 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread::{self, JoinHandle};
+
+use crate::ewfinfo;
+use crate::hash::Digests;
+
+/// Reads the media data of an EWF image by streaming it out of ewfexport.
+///
+/// Once the end of the data is reached, ewfexport's exit status is checked, so an image that it
+/// couldn't read to the end is an error rather than data that just stops.
+pub struct EwfExportReader {
+    child: Child,
+    stdout: ChildStdout,
+    /// Collects what ewfexport writes to stderr, so it can't fill the pipe and block it.
+    stderr: Option<JoinHandle<String>>,
+}
+
+impl EwfExportReader {
+    /// Start ewfexport, writing the media data of the image to its stdout.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_segment` - The first segment file of the image.
+    pub fn open(first_segment: &Path) -> io::Result<EwfExportReader> {
+        let mut child = Command::new("ewfexport")
+            .arg("-q")
+            .arg("-u")
+            .arg("-f")
+            .arg("raw")
+            .arg("-t")
+            .arg("-")
+            .arg(first_segment)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().unwrap();
+        let mut stderr_pipe = child.stderr.take().unwrap();
+        let stderr = thread::spawn(move || {
+            let mut stderr = String::new();
+            let _ = stderr_pipe.read_to_string(&mut stderr);
+            stderr
+        });
+
+        Ok(EwfExportReader {
+            child,
+            stdout,
+            stderr: Some(stderr),
+        })
+    }
+
+    /// Wait for ewfexport to exit once its output has ended.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` with what ewfexport wrote to stderr if it failed
+    /// - `Ok` if it exited successfully, or had already been waited for
+    fn finish(&mut self) -> io::Result<()> {
+        let stderr = match self.stderr.take() {
+            Some(stderr) => stderr,
+            None => return Ok(()),
+        };
+        let status = self.child.wait()?;
+        let stderr = stderr.join().unwrap_or_default();
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "ewfexport failed ({}): {}",
+                status,
+                stderr.trim()
+            )))
+        }
+    }
+}
+
+impl Read for EwfExportReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.stdout.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            self.finish()?;
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for EwfExportReader {
+    fn drop(&mut self) {
+        if self.stderr.is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// Read from `reader` until `buf` is full or the end of the data is reached.
+///
+/// # Returns
+///
+/// The number of bytes read, which is only less than `buf.len()` at the end of the data.
+pub fn read_full<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Returns true if `path` looks like the first segment of an EWF image (`.E01` or `.Ex01`).
+pub fn is_ewf_first_segment(path: &Path) -> bool {
+    match path.extension() {
+        Some(extension) => {
+            let extension = extension.to_string_lossy();
+            extension.eq_ignore_ascii_case("e01") || extension.eq_ignore_ascii_case("ex01")
+        }
+        None => false,
+    }
+}
+
+/// Returns the extension of segment `number` of an EWF image, given the extension of its first
+/// segment. Segments run `E01` to `E99`, then `EAA` to `EZZ`, `FAA` and so on; `Ex01` images
+/// follow the same pattern after their `Ex` prefix. The case of the first extension is kept.
+///
+/// # Returns
+///
+/// - `None` if `first` is not a first segment extension or `number` is out of range
+/// - `Some` with the extension otherwise
+pub fn ewf_segment_extension(first: &str, number: u32) -> Option<String> {
+    let prefix = match first.len() {
+        3 | 4 if first.ends_with("01") => &first[..first.len() - 2],
+        _ => return None,
+    };
+    if number == 0 {
+        return None;
+    }
+    if number <= 99 {
+        return Some(format!("{}{:02}", prefix, number));
+    }
+
+    let lowercase = first.chars().next()?.is_ascii_lowercase();
+    let letter = |offset: u32| -> Option<char> {
+        let base = if lowercase { b'a' } else { b'A' };
+        if offset < 26 {
+            Some((base + offset as u8) as char)
+        } else {
+            None
+        }
+    };
+
+    let index = number - 100;
+    let low = letter(index % 26)?;
+    let middle = letter(index / 26 % 26)?;
+    let rollover = index / (26 * 26);
+
+    // The first letter of the prefix advances once the two letter suffixes are used up
+    let mut prefix: Vec<char> = prefix.chars().collect();
+    let head = prefix.last_mut()?;
+    let advanced = (*head as u32)
+        .checked_add(rollover)
+        .and_then(std::char::from_u32)?;
+    if !advanced.is_ascii_alphabetic() {
+        return None;
+    }
+    *head = advanced;
+
+    Some(format!(
+        "{}{}{}",
+        prefix.into_iter().collect::<String>(),
+        middle,
+        low
+    ))
+}
+
+/// Returns every segment file of an EWF image that exists on disk, in order, starting with
+/// `first_segment`.
+pub fn ewf_segments(first_segment: &Path) -> Vec<PathBuf> {
+    let first = match first_segment.extension() {
+        Some(extension) => extension.to_string_lossy().into_owned(),
+        None => return vec![first_segment.to_path_buf()],
+    };
+
+    let mut segments = Vec::new();
+    for number in 1.. {
+        let segment = match ewf_segment_extension(&first, number) {
+            Some(extension) => first_segment.with_extension(extension),
+            None => break,
+        };
+        if !segment.is_file() {
+            break;
+        }
+        segments.push(segment);
+    }
+    segments
+}
+
+/// Returns true if `path` looks like a raw image, or the first segment of a split raw image.
+pub fn is_raw_image(path: &Path) -> bool {
+    match path.extension() {
+        Some(extension) => {
+            let extension = extension.to_string_lossy().to_lowercase();
+            matches!(extension.as_str(), "dd" | "raw" | "img" | "001")
+        }
+        None => false,
+    }
+}
+
+/// Returns true if `path` looks like an image grim can read: an EWF first segment or a raw image.
+pub fn is_image(path: &Path) -> bool {
+    is_ewf_first_segment(path) || is_raw_image(path)
+}
+
+/// Returns every file of a raw image that exists on disk, in order. A split image starts with a
+/// `.001` file and continues with `.002`, `.003` and so on; any other image is a single file.
+pub fn raw_segments(first_segment: &Path) -> Vec<PathBuf> {
+    let is_split = first_segment
+        .extension()
+        .is_some_and(|extension| extension == "001");
+    if !is_split {
+        return vec![first_segment.to_path_buf()];
+    }
+
+    let mut segments = Vec::new();
+    for number in 1..=999 {
+        let segment = first_segment.with_extension(format!("{:03}", number));
+        if !segment.is_file() {
+            break;
+        }
+        segments.push(segment);
+    }
+    segments
+}
+
+/// Reads a list of files one after another, as if they were a single file.
+pub struct SplitReader {
+    files: Vec<PathBuf>,
+    next: usize,
+    current: Option<File>,
+}
+
+impl SplitReader {
+    /// Returns a reader over `files`, in order. Each file is opened when reading reaches it.
+    pub fn new(files: Vec<PathBuf>) -> SplitReader {
+        SplitReader {
+            files,
+            next: 0,
+            current: None,
+        }
+    }
+}
+
+impl Read for SplitReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.current.is_none() {
+                match self.files.get(self.next) {
+                    Some(file) => self.current = Some(File::open(file)?),
+                    None => return Ok(0),
+                }
+                self.next += 1;
+            }
+
+            match self.current.as_mut().unwrap().read(buf)? {
+                0 if !buf.is_empty() => self.current = None,
+                read => return Ok(read),
+            }
+        }
+    }
+}
+
+/// Open an existing raw or EWF image for reading its media data.
+///
+/// # Returns
+///
+/// - `Err` if the image could not be opened or its size could not be determined
+/// - `Ok` with a reader over the media data, its size in bytes, and the digests stored in the
+///   image if it is an EWF image that stores them
+pub fn open_image(path: &Path) -> io::Result<(Box<dyn Read>, u64, Option<Digests>)> {
+    if is_ewf_first_segment(path) {
+        let info = ewfinfo::run_ewfinfo(path)?;
+        let size = info.media_size().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "ewfinfo did not report the media size",
+            )
+        })?;
+        Ok((Box::new(EwfExportReader::open(path)?), size, info.digests()))
+    } else {
+        let files = raw_segments(path);
+        let mut size = 0;
+        for file in &files {
+            size += file.metadata()?.len();
+        }
+        Ok((Box::new(SplitReader::new(files)), size, None))
+    }
+}
@@ -0,0 +1,1068 @@
+use chrono::{DateTime, Local};
+use json::{self, array, object, JsonValue};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::acquire::target_dirs;
+use crate::compare::SectorRange;
+use crate::config::Config;
+use crate::ewfargs::{
+    self, ArgsList, CompressionType, DigestType, EwfFormat, MediaFlags, MediaType, NumSectors,
+    SourceKind,
+};
+use crate::hash::Digests;
+use crate::hpa::{HiddenAreas, MaxSectors};
+use crate::image;
+use crate::partition::{Partition, Scheme, SourceRange};
+use crate::sysinfo;
+use crate::tuning::Benchmark;
+use crate::LoggingInfo;
+
+/// The version of the job file format written by this version of grim.
+const JOB_VERSION: u64 = 1;
+
+/// The file at the root of a filesystem listing the jobs with images on it, so that interrupted
+/// jobs can be found after a power cut without searching the whole filesystem.
+const JOB_INDEX: &str = ".grim-jobs";
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Returns an optional string field.
+fn string(value: &JsonValue) -> Option<String> {
+    value.as_str().map(String::from)
+}
+
+/// Returns an error saying the field `name` of a job's arguments doesn't hold `expected`.
+fn wrong_type(name: &str, expected: &str) -> io::Error {
+    invalid(&format!(
+        "The field \"{}\" in the job file must be {}",
+        name, expected
+    ))
+}
+
+/// Returns an optional string field of a job's arguments.
+fn string_arg(args: &JsonValue, name: &str) -> io::Result<Option<String>> {
+    match args[name] {
+        JsonValue::Null => Ok(None),
+        ref value => value
+            .as_str()
+            .map(|value| Some(value.to_string()))
+            .ok_or_else(|| wrong_type(name, "a string")),
+    }
+}
+
+/// Returns an optional whole number field of a job's arguments.
+fn u32_arg(args: &JsonValue, name: &str) -> io::Result<Option<u32>> {
+    match args[name] {
+        JsonValue::Null => Ok(None),
+        ref value => value
+            .as_u32()
+            .map(Some)
+            .ok_or_else(|| wrong_type(name, "a whole number")),
+    }
+}
+
+/// Returns an optional size field of a job's arguments.
+fn usize_arg(args: &JsonValue, name: &str) -> io::Result<Option<usize>> {
+    match args[name] {
+        JsonValue::Null => Ok(None),
+        ref value => value
+            .as_usize()
+            .map(Some)
+            .ok_or_else(|| wrong_type(name, "a whole number")),
+    }
+}
+
+/// Returns a true or false field of a job's arguments, which is false if it is missing.
+fn bool_arg(args: &JsonValue, name: &str) -> io::Result<bool> {
+    match args[name] {
+        JsonValue::Null => Ok(false),
+        ref value => value
+            .as_bool()
+            .ok_or_else(|| wrong_type(name, "true or false")),
+    }
+}
+
+/// Returns the members of an optional list field of a job's arguments, which must all be strings.
+fn strings_arg(args: &JsonValue, name: &str) -> io::Result<Vec<String>> {
+    match args[name] {
+        JsonValue::Null => Ok(Vec::new()),
+        JsonValue::Array(ref members) => members
+            .iter()
+            .map(|member| {
+                member
+                    .as_str()
+                    .map(String::from)
+                    .ok_or_else(|| wrong_type(name, "a list of strings"))
+            })
+            .collect(),
+        _ => Err(wrong_type(name, "a list of strings")),
+    }
+}
+
+/// Returns a required number field.
+fn number(value: &JsonValue, name: &str) -> io::Result<u64> {
+    value
+        .as_u64()
+        .ok_or_else(|| invalid(&format!("Missing or invalid {} in the job file", name)))
+}
+
+/// Returns a required time field.
+fn time(value: &JsonValue, name: &str) -> io::Result<DateTime<Local>> {
+    value
+        .as_str()
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map(|time| time.with_timezone(&Local))
+        .ok_or_else(|| invalid(&format!("Missing or invalid {} in the job file", name)))
+}
+
+fn source_kind_name(kind: SourceKind) -> &'static str {
+    match kind {
+        SourceKind::Device => "device",
+        SourceKind::Image => "image",
+        SourceKind::Other => "other",
+    }
+}
+
+fn partition_to_json(partition: &Partition) -> JsonValue {
+    object! {
+        "scheme" => match partition.scheme {
+            Scheme::Mbr => "mbr",
+            Scheme::Gpt => "gpt",
+        },
+        "number" => partition.number,
+        "type_name" => partition.type_name.clone(),
+        "type_id" => partition.type_id.clone(),
+        "guid" => partition.guid.clone(),
+        "label" => partition.label.clone(),
+        "start" => partition.start,
+        "sectors" => partition.sectors
+    }
+}
+
+fn partition_from_json(value: &JsonValue) -> io::Result<Partition> {
+    Ok(Partition {
+        scheme: match value["scheme"].as_str() {
+            Some("mbr") => Scheme::Mbr,
+            Some("gpt") => Scheme::Gpt,
+            _ => return Err(invalid("Invalid partition scheme in the job file")),
+        },
+        number: number(&value["number"], "partition number")? as u32,
+        type_name: string(&value["type_name"]).unwrap_or_default(),
+        type_id: string(&value["type_id"]).unwrap_or_default(),
+        guid: string(&value["guid"]),
+        label: string(&value["label"]),
+        start: number(&value["start"], "partition start")?,
+        sectors: number(&value["sectors"], "partition sectors")?,
+    })
+}
+
+fn tuning_to_json(tuning: &Benchmark) -> JsonValue {
+    object! {
+        "read_buffer" => tuning.read_buffer,
+        "writer_threads" => tuning.writer_threads,
+        "sectors_per_chunk" => tuning.num_sectors.map(NumSectors::sectors),
+        "throughput" => tuning.throughput,
+        "trials" => tuning.trials,
+        "failed_trials" => tuning.failed_trials
+    }
+}
+
+fn tuning_from_json(value: &JsonValue) -> io::Result<Benchmark> {
+    Ok(Benchmark {
+        read_buffer: number(&value["read_buffer"], "tuned read buffer")? as usize,
+        writer_threads: number(&value["writer_threads"], "tuned writer threads")? as usize,
+        num_sectors: match value["sectors_per_chunk"].as_u32() {
+            Some(sectors) => Some(
+                NumSectors::from_sectors(sectors)
+                    .ok_or_else(|| invalid("Invalid tuned sectors per chunk in the job file"))?,
+            ),
+            None => None,
+        },
+        throughput: value["throughput"].as_f64().unwrap_or_default(),
+        trials: value["trials"].as_usize().unwrap_or_default(),
+        failed_trials: value["failed_trials"].as_usize().unwrap_or_default(),
+    })
+}
+
+fn range_to_json(range: &SourceRange) -> JsonValue {
+    object! {
+        "sector_size" => range.sector_size,
+        "start" => range.start,
+        "sectors" => range.sectors,
+        "partition" => range.partition.as_ref().map(partition_to_json)
+    }
+}
+
+fn range_from_json(value: &JsonValue) -> io::Result<SourceRange> {
+    Ok(SourceRange {
+        sector_size: number(&value["sector_size"], "range sector size")?,
+        start: number(&value["start"], "range start")?,
+        sectors: number(&value["sectors"], "range sectors")?,
+        partition: match value["partition"] {
+            JsonValue::Null => None,
+            ref partition => Some(partition_from_json(partition)?),
+        },
+    })
+}
+
+/// Serialize every field of an `ArgsList` to JSON.
+pub fn args_to_json(args: &ArgsList) -> JsonValue {
+    let mut digests = Vec::new();
+    if args.digest_type.contains(DigestType::SHA1) {
+        digests.push("sha1");
+    }
+    if args.digest_type.contains(DigestType::SHA256) {
+        digests.push("sha256");
+    }
+
+    let custom_fields = if args.custom_fields.is_empty() {
+        JsonValue::Null
+    } else {
+        let mut fields = JsonValue::new_object();
+        for (label, value) in &args.custom_fields {
+            fields[label.as_str()] = value.as_str().into();
+        }
+        fields
+    };
+
+    object! {
+        "source_device" => args.source_device.clone(),
+        "source_kind" => source_kind_name(args.source_kind),
+        "source_range" => args.source_range.as_ref().map(range_to_json),
+        "sectors_per_chunk" => args.num_sectors.sectors(),
+        "compression_type" => args.compression_type.as_arg(),
+        "case_number" => args.case_number.clone(),
+        "digests" => digests,
+        "description" => args.description.clone(),
+        "examiner_name" => args.examiner_name.clone(),
+        "evidence_number" => args.evidence_number.clone(),
+        "ewf_format" => args.ewf_format.as_arg(),
+        "notes" => args.notes.clone(),
+        "custom_fields" => custom_fields,
+        "bytes_per_sector" => args.bytes_per_sector,
+        "segment_file_size" => args.segment_file_size.clone(),
+        "target_dirs" => args.target_dirs.clone(),
+        "target_filename" => args.target_filename.clone(),
+        "target_template" => args.target_template.clone(),
+        "read_retries" => args.read_retries,
+        "error_granularity" => args.error_granularity,
+        "zero_whole_block" => args.zero_whole_block,
+        "rescue" => args.rescue,
+        "rescue_reverse" => args.rescue_reverse,
+        "media_type" => args.media_type.as_arg(),
+        "media_flags" => args.media_flags.as_arg(),
+        "read_buffer" => args.read_buffer,
+        "writer_threads" => args.writer_threads,
+        "auto_tune" => args.auto_tune
+    }
+}
+
+/// Deserialize an `ArgsList` written by `args_to_json`. Missing fields take their defaults.
+///
+/// # Returns
+///
+/// - `Err` with `ErrorKind::InvalidData` if a field isn't one grim knows, doesn't hold the type
+///   of value it should, or has a value grim doesn't recognize
+/// - `Ok` with the arguments
+pub fn args_from_json(value: &JsonValue) -> io::Result<ArgsList> {
+    let mut args = ArgsList::new();
+
+    if !value.is_null() && !value.is_object() {
+        return Err(invalid("The arguments in the job file must be an object"));
+    }
+    // A misspelled field would otherwise leave its setting at the default without a word
+    let known = args_to_json(&args);
+    if let Some((name, _)) = value.entries().find(|&(name, _)| !known.has_key(name)) {
+        return Err(invalid(&format!(
+            "The field \"{}\" in the job file is not one grim knows",
+            name
+        )));
+    }
+
+    args.source_device = string_arg(value, "source_device")?;
+    args.source_kind = match string_arg(value, "source_kind")?.as_deref() {
+        None | Some("device") => SourceKind::Device,
+        Some("image") => SourceKind::Image,
+        Some("other") => SourceKind::Other,
+        Some(_) => return Err(invalid("Invalid source kind in the job file")),
+    };
+    args.source_range = match value["source_range"] {
+        JsonValue::Null => None,
+        ref range => Some(range_from_json(range)?),
+    };
+    if let Some(sectors) = u32_arg(value, "sectors_per_chunk")? {
+        args.num_sectors = NumSectors::from_sectors(sectors)
+            .ok_or_else(|| invalid("Invalid sectors per chunk in the job file"))?;
+    }
+    if let Some(compression) = string_arg(value, "compression_type")? {
+        args.compression_type = CompressionType::from_arg(&compression)
+            .ok_or_else(|| invalid("Invalid compression type in the job file"))?;
+    }
+    args.case_number = string_arg(value, "case_number")?;
+    for digest in strings_arg(value, "digests")? {
+        match digest.as_str() {
+            "md5" => (),
+            "sha1" => args.digest_type |= DigestType::SHA1,
+            "sha256" => args.digest_type |= DigestType::SHA256,
+            _ => return Err(invalid("Invalid digest in the job file")),
+        }
+    }
+    args.description = string_arg(value, "description")?;
+    args.examiner_name = string_arg(value, "examiner_name")?;
+    args.evidence_number = string_arg(value, "evidence_number")?;
+    if let Some(format) = string_arg(value, "ewf_format")? {
+        args.ewf_format = EwfFormat::from_arg(&format)
+            .ok_or_else(|| invalid("Invalid image format in the job file"))?;
+    }
+    args.notes = string_arg(value, "notes")?;
+    let custom_fields = &value["custom_fields"];
+    if !custom_fields.is_null() && !custom_fields.is_object() {
+        return Err(wrong_type("custom_fields", "an object"));
+    }
+    args.custom_fields = custom_fields
+        .entries()
+        .map(|(label, value)| {
+            value
+                .as_str()
+                .map(|value| (label.to_string(), value.to_string()))
+                .ok_or_else(|| wrong_type(&format!("custom_fields.{}", label), "a string"))
+        })
+        .collect::<io::Result<_>>()?;
+    args.bytes_per_sector = u32_arg(value, "bytes_per_sector")?;
+    args.segment_file_size = string_arg(value, "segment_file_size")?;
+    args.target_dirs = strings_arg(value, "target_dirs")?;
+    args.target_filename = string_arg(value, "target_filename")?;
+    args.target_template = string_arg(value, "target_template")?;
+    if let Some(retries) = u32_arg(value, "read_retries")? {
+        args.read_retries = retries;
+    }
+    args.error_granularity = u32_arg(value, "error_granularity")?;
+    args.zero_whole_block = bool_arg(value, "zero_whole_block")?;
+    args.rescue = bool_arg(value, "rescue")?;
+    args.rescue_reverse = bool_arg(value, "rescue_reverse")?;
+    if let Some(media_type) = string_arg(value, "media_type")? {
+        args.media_type = MediaType::from_arg(&media_type)
+            .ok_or_else(|| invalid("Invalid media type in the job file"))?;
+    }
+    if let Some(media_flags) = string_arg(value, "media_flags")? {
+        args.media_flags = MediaFlags::from_arg(&media_flags)
+            .ok_or_else(|| invalid("Invalid media flags in the job file"))?;
+    }
+    args.read_buffer = usize_arg(value, "read_buffer")?;
+    args.writer_threads = u32_arg(value, "writer_threads")?;
+    args.auto_tune = bool_arg(value, "auto_tune")?;
+
+    Ok(args)
+}
+
+/// Serialize the information about the source and host gathered before an acquisition. The
+/// SMART snapshot is not kept, since it is out of date by the time a job is resumed.
+pub fn info_to_json(info: &LoggingInfo) -> JsonValue {
+    let hidden_areas = info.hidden_areas.as_ref().map(|hidden| {
+        object! {
+            "visible_sectors" => hidden.max_sectors.map(|max| max.visible),
+            "native_sectors" => hidden.max_sectors.map(|max| max.native),
+            "hpa_enabled" => hidden.max_sectors.map(|max| max.hpa_enabled),
+            "dco_max_sectors" => hidden.dco_max_sectors,
+            "hpa_removed" => hidden.hpa_removed
+        }
+    });
+
+    object! {
+        "host_serial" => info.host_serial.clone(),
+        "drive_serial" => info.drive_serial.clone(),
+        "drive_product" => info.drive_product.clone(),
+        "drive_size" => info.drive_size.clone(),
+        "hidden_areas" => hidden_areas
+    }
+}
+
+/// Deserialize the information written by `info_to_json`.
+pub fn info_from_json(value: &JsonValue) -> LoggingInfo {
+    let hidden = &value["hidden_areas"];
+    let hidden_areas = if hidden.is_null() {
+        None
+    } else {
+        let max_sectors = match (
+            hidden["visible_sectors"].as_u64(),
+            hidden["native_sectors"].as_u64(),
+        ) {
+            (Some(visible), Some(native)) => Some(MaxSectors {
+                visible,
+                native,
+                hpa_enabled: hidden["hpa_enabled"].as_bool().unwrap_or_default(),
+            }),
+            _ => None,
+        };
+        Some(HiddenAreas {
+            max_sectors,
+            dco_max_sectors: hidden["dco_max_sectors"].as_u64(),
+            hpa_removed: hidden["hpa_removed"].as_bool().unwrap_or_default(),
+        })
+    };
+
+    LoggingInfo {
+        host_serial: string(&value["host_serial"]),
+        drive_serial: string(&value["drive_serial"]),
+        drive_product: string(&value["drive_product"]),
+        drive_size: string(&value["drive_size"]),
+        hidden_areas,
+        smart: None,
+    }
+}
+
+/// What identifies the source of a job, so that the same source can be found again when the job
+/// is resumed or loaded, even if it has been given a different device name.
+#[derive(Clone)]
+pub struct SourceIdentity {
+    /// The path the source had when the job was created.
+    pub path: String,
+    /// What kind of source it is.
+    pub kind: SourceKind,
+    /// The serial number of a physical drive, if it reported one.
+    pub serial: Option<String>,
+    /// The size of the whole source in bytes.
+    pub size: u64,
+}
+
+/// Returns the size of the source at `path` in bytes.
+fn measure(path: &str, kind: SourceKind) -> io::Result<u64> {
+    match kind {
+        SourceKind::Image => Ok(image::open_image(Path::new(path))?.1),
+        SourceKind::Device | SourceKind::Other => File::open(path)?.seek(SeekFrom::End(0)),
+    }
+}
+
+impl SourceIdentity {
+    /// Returns the identity of the source described by `args` and `info`, measuring its size.
+    pub fn of(args: &ArgsList, info: &LoggingInfo) -> io::Result<SourceIdentity> {
+        let path = args
+            .source_device
+            .clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No source selected"))?;
+        Ok(SourceIdentity {
+            size: measure(&path, args.source_kind)?,
+            path,
+            kind: args.source_kind,
+            serial: info
+                .drive_serial
+                .clone()
+                .filter(|serial| !serial.is_empty()),
+        })
+    }
+
+    /// Find the source again. A physical drive with a serial number is looked for among every
+    /// attached drive, since it may have a different device name now; any other source must
+    /// still be at the same path. Either way, its size must not have changed.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` with `ErrorKind::NotFound` if the source isn't attached or has changed size
+    /// - `Ok` with the path the source is at now
+    pub fn locate(&self) -> io::Result<String> {
+        if let (SourceKind::Device, Some(serial)) = (self.kind, &self.serial) {
+            for disk in sysinfo::get_all_disks()? {
+                if disk.serial.trim() == serial.trim()
+                    && measure(&disk.logical_name, self.kind).ok() == Some(self.size)
+                {
+                    return Ok(disk.logical_name);
+                }
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "No attached drive has serial number {} and a size of {} bytes",
+                    serial, self.size
+                ),
+            ));
+        }
+
+        match measure(&self.path, self.kind) {
+            Ok(size) if size == self.size => Ok(self.path.clone()),
+            Ok(size) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "{} is now {} bytes, not {} bytes; it is not the same source",
+                    self.path, size, self.size
+                ),
+            )),
+            Err(e) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} can't be opened: {}", self.path, e),
+            )),
+        }
+    }
+
+    /// Returns a line identifying the source, saved with a rescue map so that the map is only
+    /// used for the same source: its serial number if it has one, otherwise its path, and its size.
+    pub fn label(&self) -> String {
+        match self.serial {
+            Some(ref serial) => format!("serial {}, {} bytes", serial.trim(), self.size),
+            None => format!("{}, {} bytes", self.path, self.size),
+        }
+    }
+
+    /// Returns the identity as a JSON object for the job file.
+    pub fn to_json(&self) -> JsonValue {
+        object! {
+            "path" => self.path.clone(),
+            "kind" => source_kind_name(self.kind),
+            "serial" => self.serial.clone(),
+            "size" => self.size
+        }
+    }
+
+    /// Read an identity written by `to_json`.
+    pub fn from_json(value: &JsonValue) -> io::Result<SourceIdentity> {
+        Ok(SourceIdentity {
+            path: string(&value["path"]).ok_or_else(|| invalid("Missing source path"))?,
+            kind: match value["kind"].as_str() {
+                Some("device") => SourceKind::Device,
+                Some("image") => SourceKind::Image,
+                Some("other") => SourceKind::Other,
+                _ => return Err(invalid("Invalid source kind in the job file")),
+            },
+            serial: string(&value["serial"]),
+            size: number(&value["size"], "source size")?,
+        })
+    }
+}
+
+/// A time an interrupted job was resumed.
+#[derive(Clone)]
+pub struct Resumption {
+    /// When the job was resumed.
+    pub at: DateTime<Local>,
+    /// How many bytes had already been written when it was resumed.
+    pub bytes_done: u64,
+}
+
+/// The durable state of an acquisition, saved next to the image as it is written so that an
+/// interrupted acquisition can be resumed from the last consistent point.
+#[derive(Clone)]
+pub struct JobState {
+    /// The arguments of the acquisition.
+    pub args: ArgsList,
+    /// The information about the source and host gathered before the acquisition.
+    pub info: LoggingInfo,
+    /// The source being acquired.
+    pub source: SourceIdentity,
+    /// When the acquisition first started.
+    pub started: DateTime<Local>,
+    /// How many bytes have been read and written to every destination, flushed to disk.
+    pub bytes_done: u64,
+    /// The digests of the first `bytes_done` bytes. The running hash state can't be saved, so on
+    /// resume it is rebuilt by reading back the image, and checked against these.
+    pub digests: Digests,
+    /// The sectors that couldn't be read so far.
+    pub bad_sectors: Vec<SectorRange>,
+    /// The files written to each destination so far, in the order of `args.target_dirs`.
+    pub segments: Vec<Vec<PathBuf>>,
+    /// Every time the job has been resumed.
+    pub resumptions: Vec<Resumption>,
+    /// The settings chosen by benchmarking, once an auto-tuned job has been benchmarked.
+    pub tuning: Option<Benchmark>,
+}
+
+impl JobState {
+    /// Returns the state of a job that is about to start.
+    pub fn new(args: &ArgsList, info: &LoggingInfo) -> io::Result<JobState> {
+        Ok(JobState {
+            args: args.clone(),
+            info: info.clone(),
+            source: SourceIdentity::of(args, info)?,
+            started: Local::now(),
+            bytes_done: 0,
+            digests: Digests::default(),
+            bad_sectors: Vec::new(),
+            segments: Vec::new(),
+            resumptions: Vec::new(),
+            tuning: None,
+        })
+    }
+
+    /// Returns true if the job can be resumed. Raw images are resumed by appending to them, but
+    /// ewfacquirestream can't append to an EWF image. An EWF job can only be resumed if it used
+    /// the rescue engine: the rescue carries on from its map, and any part of the image already
+    /// written is removed and written again from the rescued copy.
+    pub fn resumable(&self) -> bool {
+        self.args.ewf_format.is_raw() || self.args.rescue
+    }
+
+    /// Find the job's source again, as `SourceIdentity::locate` does, and point the job's
+    /// arguments at wherever it is now.
+    pub fn locate_source(&mut self) -> io::Result<()> {
+        self.args.source_device = Some(self.source.locate()?);
+        Ok(())
+    }
+
+    /// Returns the job state as the JSON document saved in the job file.
+    pub fn to_json(&self) -> JsonValue {
+        let bad_sectors: Vec<JsonValue> = self
+            .bad_sectors
+            .iter()
+            .map(|range| array![range.start, range.end])
+            .collect();
+        let segments: Vec<JsonValue> = self
+            .segments
+            .iter()
+            .map(|files| {
+                JsonValue::from(
+                    files
+                        .iter()
+                        .map(|file| file.to_string_lossy().into_owned())
+                        .collect::<Vec<String>>(),
+                )
+            })
+            .collect();
+        let resumptions: Vec<JsonValue> = self
+            .resumptions
+            .iter()
+            .map(|resumption| {
+                object! {
+                    "at" => resumption.at.to_rfc3339(),
+                    "bytes_done" => resumption.bytes_done
+                }
+            })
+            .collect();
+
+        object! {
+            "version" => JOB_VERSION,
+            "args" => args_to_json(&self.args),
+            "info" => info_to_json(&self.info),
+            "source" => self.source.to_json(),
+            "started" => self.started.to_rfc3339(),
+            "bytes_done" => self.bytes_done,
+            "digests" => object! {
+                "md5" => self.digests.md5.clone(),
+                "sha1" => self.digests.sha1.clone(),
+                "sha256" => self.digests.sha256.clone()
+            },
+            "bad_sectors" => bad_sectors,
+            "segments" => segments,
+            "resumptions" => resumptions,
+            "tuning" => self.tuning.as_ref().map(tuning_to_json)
+        }
+    }
+
+    /// Parse a job state file.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` with `ErrorKind::InvalidData` if the file isn't a job state file grim can read
+    /// - `Ok` with the job state
+    pub fn parse(text: &str) -> io::Result<JobState> {
+        let value = json::parse(text).map_err(|e| invalid(&format!("Invalid job file: {}", e)))?;
+        if number(&value["version"], "version")? > JOB_VERSION {
+            return Err(invalid(
+                "The job file was written by a newer version of grim",
+            ));
+        }
+
+        let mut bad_sectors = Vec::new();
+        for range in value["bad_sectors"].members() {
+            bad_sectors.push(SectorRange {
+                start: number(&range[0], "bad sector range")?,
+                end: number(&range[1], "bad sector range")?,
+            });
+        }
+        let mut resumptions = Vec::new();
+        for resumption in value["resumptions"].members() {
+            resumptions.push(Resumption {
+                at: time(&resumption["at"], "resumption time")?,
+                bytes_done: number(&resumption["bytes_done"], "resumption offset")?,
+            });
+        }
+        let digests = &value["digests"];
+
+        Ok(JobState {
+            args: args_from_json(&value["args"])?,
+            info: info_from_json(&value["info"]),
+            source: SourceIdentity::from_json(&value["source"])?,
+            started: time(&value["started"], "start time")?,
+            bytes_done: number(&value["bytes_done"], "bytes done")?,
+            digests: Digests {
+                md5: string(&digests["md5"]).unwrap_or_default(),
+                sha1: string(&digests["sha1"]),
+                sha256: string(&digests["sha256"]),
+            },
+            bad_sectors,
+            segments: value["segments"]
+                .members()
+                .map(|files| {
+                    files
+                        .members()
+                        .filter_map(|file| file.as_str().map(PathBuf::from))
+                        .collect()
+                })
+                .collect(),
+            resumptions,
+            tuning: match value["tuning"] {
+                JsonValue::Null => None,
+                ref tuning => Some(tuning_from_json(tuning)?),
+            },
+        })
+    }
+
+    /// Read and parse a job state file.
+    pub fn load(path: &Path) -> io::Result<JobState> {
+        JobState::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Save the job state, replacing the file atomically so a power cut can't leave it half written.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temporary = path.with_extension("job.tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(self.to_json().pretty(2).as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary, path)?;
+        if let Some(dir) = path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns where the job state file for an acquisition is kept: next to the first copy of the image.
+pub fn job_path(args: &ArgsList) -> Option<PathBuf> {
+    let dir = target_dirs(args).into_iter().next()?;
+    Some(dir.join(format!(
+        "{}.job",
+        args.target_filename.clone().unwrap_or_default()
+    )))
+}
+
+/// Returns the root of the filesystem `path` is on.
+fn filesystem_root(path: &Path) -> io::Result<PathBuf> {
+    let mut root = fs::canonicalize(path)?;
+    let device = fs::metadata(&root)?.dev();
+    while let Some(parent) = root.parent() {
+        if fs::metadata(parent)?.dev() != device {
+            break;
+        }
+        root = parent.to_path_buf();
+    }
+    Ok(root)
+}
+
+/// Returns the job files listed in the index at the root of a filesystem.
+fn read_index(index: &Path) -> Vec<PathBuf> {
+    fs::read_to_string(index)
+        .map(|text| {
+            text.lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Rewrite the index at the root of a filesystem, removing it if no jobs are left.
+fn write_index(index: &Path, jobs: &[PathBuf]) -> io::Result<()> {
+    if jobs.is_empty() {
+        return match fs::remove_file(index) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        };
+    }
+
+    let temporary = index.with_extension("tmp");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&temporary)?;
+    for job in jobs {
+        writeln!(file, "{}", job.display())?;
+    }
+    file.sync_all()?;
+    fs::rename(&temporary, index)
+}
+
+/// Add a job file to the index at the root of its filesystem, so `find_interrupted` can find it.
+pub fn register(job: &Path) -> io::Result<()> {
+    let dir = job.parent().unwrap_or_else(|| Path::new("."));
+    let job = fs::canonicalize(dir)?.join(job.file_name().unwrap_or_default());
+    let index = filesystem_root(dir)?.join(JOB_INDEX);
+
+    let mut jobs = read_index(&index);
+    if !jobs.contains(&job) {
+        jobs.push(job);
+    }
+    write_index(&index, &jobs)
+}
+
+/// Remove a finished or discarded job: its state file and its entry in the index. The image
+/// files are left alone.
+pub fn remove(job: &Path) -> io::Result<()> {
+    let dir = job.parent().unwrap_or_else(|| Path::new("."));
+    let absolute = fs::canonicalize(dir)?.join(job.file_name().unwrap_or_default());
+    let index = filesystem_root(dir)?.join(JOB_INDEX);
+
+    let jobs: Vec<PathBuf> = read_index(&index)
+        .into_iter()
+        .filter(|listed| *listed != absolute)
+        .collect();
+    write_index(&index, &jobs)?;
+    match fs::remove_file(job) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Decode the octal escapes the kernel uses for spaces and other characters in /proc/mounts.
+fn unescape_mount_point(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'\\' && index + 3 < bytes.len() {
+            let value = std::str::from_utf8(&bytes[index + 1..index + 4])
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+            if let Some(value) = value {
+                decoded.push(value);
+                index += 4;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Returns the mount points of every filesystem backed by a device, from /proc/mounts.
+fn mount_points() -> io::Result<Vec<PathBuf>> {
+    Ok(fs::read_to_string("/proc/mounts")?
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            let mount_point = fields.next()?;
+            if device.starts_with('/') {
+                Some(PathBuf::from(unescape_mount_point(mount_point)))
+            } else {
+                None
+            }
+        })
+        .collect())
+}
+
+/// Find every interrupted job on the mounted filesystems, by reading the index at the root of each.
+///
+/// # Returns
+///
+/// Each job file that still exists, with its state or the reason it couldn't be read.
+pub fn find_interrupted() -> Vec<(PathBuf, io::Result<JobState>)> {
+    let mut found = Vec::new();
+    for mount_point in mount_points().unwrap_or_default() {
+        for job in read_index(&mount_point.join(JOB_INDEX)) {
+            if job.exists() && !found.iter().any(|(path, _)| *path == job) {
+                let state = JobState::load(&job);
+                found.push((job, state));
+            }
+        }
+    }
+    found
+}
+
+/// The version of the prepared job file format written by this version of grim.
+const PREPARED_JOB_VERSION: u64 = 1;
+
+/// An acquisition prepared ahead of time, such as by a supervisor in the office, to be loaded
+/// into the wizard in the field. It holds the arguments of the acquisition and the identity of
+/// the source they were prepared for.
+pub struct PreparedJob {
+    /// The arguments of the acquisition.
+    pub args: ArgsList,
+    /// The source the job was prepared for, if one had been chosen.
+    pub source: Option<SourceIdentity>,
+    /// The fields of `args` that were set in the file, by their names in the file.
+    pub from_file: Vec<String>,
+}
+
+impl PreparedJob {
+    /// Returns a job prepared from the arguments and source information entered so far.
+    pub fn new(args: &ArgsList, info: &LoggingInfo) -> PreparedJob {
+        PreparedJob {
+            args: args.clone(),
+            source: SourceIdentity::of(args, info).ok(),
+            from_file: Vec::new(),
+        }
+    }
+
+    /// Returns the job as the JSON document saved in a prepared job file.
+    pub fn to_json(&self) -> JsonValue {
+        object! {
+            "prepared_job" => PREPARED_JOB_VERSION,
+            "saved" => Local::now().to_rfc3339(),
+            "args" => args_to_json(&self.args),
+            "source" => self.source.as_ref().map(SourceIdentity::to_json)
+        }
+    }
+
+    /// Parse the contents of a prepared job file, recording which fields it sets.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` with `ErrorKind::InvalidData` if the file isn't a prepared job grim can read
+    /// - `Ok` with the job
+    pub fn parse(text: &str) -> io::Result<PreparedJob> {
+        let value = json::parse(text).map_err(|e| invalid(&format!("Invalid job file: {}", e)))?;
+        match value["prepared_job"].as_u64() {
+            None => return Err(invalid("The file is not a prepared job")),
+            Some(version) if version > PREPARED_JOB_VERSION => {
+                return Err(invalid(
+                    "The job file was written by a newer version of grim",
+                ))
+            }
+            Some(_) => (),
+        }
+
+        let args = &value["args"];
+        Ok(PreparedJob {
+            args: args_from_json(args)?,
+            source: match value["source"] {
+                JsonValue::Null => None,
+                ref source => Some(SourceIdentity::from_json(source)?),
+            },
+            from_file: args
+                .entries()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        })
+    }
+
+    /// Read and parse a prepared job file.
+    pub fn load(path: &Path) -> io::Result<PreparedJob> {
+        PreparedJob::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Save the job to a file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_json().pretty(2))
+    }
+
+    /// Returns true if the field with the given name in the file was set by the file.
+    pub fn is_from_file(&self, name: &str) -> bool {
+        self.from_file.iter().any(|field| field == name)
+    }
+
+    /// Check the parts of the job that depend on the machine it is loaded on, or that the
+    /// wizard's forms don't check themselves, and the examiner information against the rules in
+    /// the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration whose field rules the examiner information must follow.
+    ///
+    /// # Returns
+    ///
+    /// A description of each problem found, which is empty if the job looks usable.
+    pub fn problems(&self, config: &Config) -> Vec<String> {
+        let args = &self.args;
+        let mut problems = config.field_problems(args);
+
+        if self.source.is_none() {
+            problems.push(String::from("The job doesn't say which source to image."));
+        }
+        if let Some(ref filename) = args.target_filename {
+            if filename.is_empty() || filename.contains('/') {
+                problems.push(format!("\"{}\" is not a valid filename.", filename));
+            }
+        }
+        if args.target_dirs.is_empty() {
+            problems.push(String::from("The job has no destinations."));
+        }
+        for dir in &args.target_dirs {
+            if !Path::new(dir).is_dir() {
+                problems.push(format!("The destination {} doesn't exist.", dir));
+            }
+        }
+        if args.segment_file_size.is_some() && args.segment_size_bytes().is_none() {
+            problems.push(format!(
+                "The segment size {} is not valid.",
+                args.segment_file_size.clone().unwrap_or_default()
+            ));
+        }
+        if args
+            .bytes_per_sector
+            .is_some_and(|bytes| !ewfargs::valid_sector_size(bytes))
+        {
+            problems.push(String::from(
+                "The bytes per sector must be a power of two of at least 512.",
+            ));
+        }
+        if args.rescue && args.source_kind == SourceKind::Image {
+            problems.push(String::from(
+                "Rescue mode is set, but an existing image can't be rescued.",
+            ));
+        }
+        if let (Some(range), Some(source)) = (&args.source_range, &self.source) {
+            if !range.fits(source.size) {
+                problems.push(String::from(
+                    "The part of the source to image is empty or runs past the end of the source.",
+                ));
+            }
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_round_trip() {
+        let mut args = ArgsList::new();
+        args.examiner_name = Some(String::from("A. Examiner"));
+        args.target_dirs = vec![String::from("/mnt/a"), String::from("/mnt/b")];
+        args.target_template = Some(String::from("{case}_{evidence}"));
+        args.bytes_per_sector = Some(4096);
+        args.rescue = true;
+        args.custom_fields = vec![(String::from("Bag"), String::from("B-17"))];
+
+        let parsed = args_from_json(&args_to_json(&args)).unwrap();
+        assert_eq!(parsed.examiner_name, args.examiner_name);
+        assert_eq!(parsed.target_dirs, args.target_dirs);
+        assert_eq!(parsed.target_template, args.target_template);
+        assert_eq!(parsed.bytes_per_sector, Some(4096));
+        assert!(parsed.rescue);
+        assert_eq!(parsed.custom_fields, args.custom_fields);
+    }
+
+    #[test]
+    fn wrong_types_are_rejected() {
+        for (name, value) in &[
+            ("compression_type", JsonValue::from(5)),
+            ("bytes_per_sector", JsonValue::from("4096")),
+            ("rescue", JsonValue::from("yes")),
+            ("target_dirs", JsonValue::from("/mnt/a")),
+            ("target_dirs", array!["/mnt/a", 5]),
+            ("custom_fields", array!["B-17"]),
+        ] {
+            let mut args = args_to_json(&ArgsList::new());
+            args[*name] = value.clone();
+            let error = args_from_json(&args).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(error.to_string().contains(name), "{}", error);
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let mut args = args_to_json(&ArgsList::new());
+        args["compression_level"] = "best".into();
+        let error = args_from_json(&args).err().unwrap();
+        assert!(error.to_string().contains("compression_level"), "{}", error);
+    }
+}
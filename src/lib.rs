@@ -1,13 +1,35 @@
 #[macro_use]
 extern crate bitflags;
 
+pub mod acquire;
+pub mod batch;
+pub mod block;
+pub mod compare;
+pub mod config;
+pub mod ewfinfo;
+pub mod hash;
+pub mod hpa;
+pub mod image;
+pub mod job;
+pub mod partition;
+pub mod report;
+pub mod rescue;
+pub mod smart;
 pub mod sysinfo;
+pub mod template;
+#[cfg(test)]
+mod test_util;
+pub mod tuning;
+pub mod verify;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct LoggingInfo {
     pub host_serial: Option<String>,
     pub drive_serial: Option<String>,
     pub drive_product: Option<String>,
+    pub drive_size: Option<String>,
+    pub hidden_areas: Option<hpa::HiddenAreas>,
+    pub smart: Option<smart::SmartSnapshot>,
 }
 
 impl LoggingInfo {
@@ -17,10 +39,17 @@ impl LoggingInfo {
 }
 
 pub mod ewfargs {
-    #[derive(Copy, Clone)]
+    use std::fmt;
+
+    use crate::block::ErrorPolicy;
+    use crate::partition::SourceRange;
+    use crate::rescue::RescueOptions;
+
+    #[derive(Copy, Clone, Default)]
     pub enum NumSectors {
         Sectors16,
         Sectors32,
+        #[default]
         Sectors64, // default
         Sectors128,
         Sectors256,
@@ -33,23 +62,72 @@ pub mod ewfargs {
         Sectors32768,
     }
 
-    impl Default for NumSectors {
-        fn default() -> Self {
-            NumSectors::Sectors64
+    impl NumSectors {
+        /// Returns the number of sectors this value represents.
+        pub fn sectors(self) -> u32 {
+            16 << (self as u32)
+        }
+
+        /// Returns the value representing `sectors`, if it is one of the supported counts.
+        pub fn from_sectors(sectors: u32) -> Option<NumSectors> {
+            const ALL: [NumSectors; 12] = [
+                NumSectors::Sectors16,
+                NumSectors::Sectors32,
+                NumSectors::Sectors64,
+                NumSectors::Sectors128,
+                NumSectors::Sectors256,
+                NumSectors::Sectors512,
+                NumSectors::Sectors1024,
+                NumSectors::Sectors2048,
+                NumSectors::Sectors4096,
+                NumSectors::Sectors8192,
+                NumSectors::Sectors16384,
+                NumSectors::Sectors32768,
+            ];
+            ALL.iter().cloned().find(|value| value.sectors() == sectors)
         }
     }
 
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, Default)]
     pub enum CompressionType {
+        #[default]
         None, // default
         EmptyBlock,
         Fast,
         Best,
     }
 
-    impl Default for CompressionType {
-        fn default() -> Self {
-            CompressionType::None
+    impl CompressionType {
+        /// Returns the value ewfacquirestream expects for `-c`.
+        pub fn as_arg(self) -> &'static str {
+            match self {
+                CompressionType::None => "none",
+                CompressionType::EmptyBlock => "empty-block",
+                CompressionType::Fast => "fast",
+                CompressionType::Best => "best",
+            }
+        }
+
+        /// Returns the compression type for a value of `-c`, as returned by `as_arg`.
+        pub fn from_arg(arg: &str) -> Option<CompressionType> {
+            match arg {
+                "none" => Some(CompressionType::None),
+                "empty-block" => Some(CompressionType::EmptyBlock),
+                "fast" => Some(CompressionType::Fast),
+                "best" => Some(CompressionType::Best),
+                _ => None,
+            }
+        }
+    }
+
+    impl fmt::Display for CompressionType {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                CompressionType::None => write!(f, "None"),
+                CompressionType::EmptyBlock => write!(f, "Empty Block"),
+                CompressionType::Fast => write!(f, "Fast"),
+                CompressionType::Best => write!(f, "Best"),
+            }
         }
     }
 
@@ -62,24 +140,246 @@ pub mod ewfargs {
         }
     }
 
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, Default)]
     pub enum EwfFormat {
         FTK,
         Encase2,
         Encase3,
         Encase4,
         Encase5,
+        #[default]
         Encase6, // default
         Encase7,
         Linen5,
         Linen6,
         Linen7,
         EwfX,
+        /// Not an EWF format; a raw (dd) image, split into numbered segments
+        /// when a segment size is set.
+        Raw,
+    }
+
+    impl EwfFormat {
+        /// Returns true if this format is written by grim itself rather than by ewfacquirestream.
+        pub fn is_raw(self) -> bool {
+            matches!(self, EwfFormat::Raw)
+        }
+
+        /// Returns the value ewfacquirestream expects for `-f`.
+        pub fn as_arg(self) -> &'static str {
+            match self {
+                EwfFormat::FTK => "ftk",
+                EwfFormat::Encase2 => "encase2",
+                EwfFormat::Encase3 => "encase3",
+                EwfFormat::Encase4 => "encase4",
+                EwfFormat::Encase5 => "encase5",
+                EwfFormat::Encase6 => "encase6",
+                EwfFormat::Encase7 => "encase7",
+                EwfFormat::Linen5 => "linen5",
+                EwfFormat::Linen6 => "linen6",
+                EwfFormat::Linen7 => "linen7",
+                EwfFormat::EwfX => "ewfx",
+                EwfFormat::Raw => "raw",
+            }
+        }
+
+        /// Returns true if this format keeps the case fields only in the `header` section, which
+        /// libewf writes in an 8-bit codepage, ASCII by default. EnCase 4 and later and linen
+        /// images also have a UTF-16 `header2` section, and EWFX images a UTF-8 `xheader`
+        /// section, which tools reading them take the fields from instead.
+        ///
+        /// See the "header" and "header2" sections of the Expert Witness Compression Format
+        /// specification in the libewf documentation, and `libewf_header_sections_generate` in
+        /// libewf for which formats write which sections.
+        pub fn has_ascii_header_only(self) -> bool {
+            matches!(
+                self,
+                EwfFormat::FTK | EwfFormat::Encase2 | EwfFormat::Encase3
+            )
+        }
+
+        /// Returns a header value as tools reading an image in this format will see it. In
+        /// formats with only an ASCII header, libuna writes each character it can't encode as
+        /// the ASCII substitute character (0x1A), shown here as `?`. This is only a preview: the
+        /// value given to ewfacquirestream is never changed.
+        pub fn header_preview(self, value: &str) -> String {
+            if !self.has_ascii_header_only() {
+                return value.to_string();
+            }
+            value
+                .chars()
+                .map(|c| if c.is_ascii() { c } else { '?' })
+                .collect()
+        }
+
+        /// Returns the format for a value of `-f`, as returned by `as_arg`.
+        pub fn from_arg(arg: &str) -> Option<EwfFormat> {
+            match arg {
+                "ftk" => Some(EwfFormat::FTK),
+                "encase2" => Some(EwfFormat::Encase2),
+                "encase3" => Some(EwfFormat::Encase3),
+                "encase4" => Some(EwfFormat::Encase4),
+                "encase5" => Some(EwfFormat::Encase5),
+                "encase6" => Some(EwfFormat::Encase6),
+                "encase7" => Some(EwfFormat::Encase7),
+                "linen5" => Some(EwfFormat::Linen5),
+                "linen6" => Some(EwfFormat::Linen6),
+                "linen7" => Some(EwfFormat::Linen7),
+                "ewfx" => Some(EwfFormat::EwfX),
+                "raw" => Some(EwfFormat::Raw),
+                _ => None,
+            }
+        }
+    }
+
+    impl fmt::Display for EwfFormat {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                EwfFormat::FTK => write!(f, "FTK"),
+                EwfFormat::Encase2 => write!(f, "Encase2"),
+                EwfFormat::Encase3 => write!(f, "Encase3"),
+                EwfFormat::Encase4 => write!(f, "Encase4"),
+                EwfFormat::Encase5 => write!(f, "Encase5"),
+                EwfFormat::Encase6 => write!(f, "Encase6"),
+                EwfFormat::Encase7 => write!(f, "Encase7"),
+                EwfFormat::Linen5 => write!(f, "Linen5"),
+                EwfFormat::Linen6 => write!(f, "Linen6"),
+                EwfFormat::Linen7 => write!(f, "Linen7"),
+                EwfFormat::EwfX => write!(f, "EwfX"),
+                EwfFormat::Raw => write!(f, "Raw (dd)"),
+            }
+        }
+    }
+
+    /// Where the data being acquired comes from.
+    #[derive(Copy, Clone, Default, PartialEq)]
+    pub enum SourceKind {
+        /// A physical drive, chosen from the list of disks.
+        #[default]
+        Device, // default
+        /// An existing raw or EWF image, being exported or converted.
+        Image,
+        /// Any other block device or file, such as a loop device, a partition or an LVM volume.
+        Other,
+    }
+
+    impl fmt::Display for SourceKind {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                SourceKind::Device => write!(f, "Physical drive"),
+                SourceKind::Image => write!(f, "Existing image"),
+                SourceKind::Other => write!(f, "Other path (not a physical drive)"),
+            }
+        }
+    }
+
+    /// Returns true if `bytes` is a sector size grim can image with: a power of two of at least
+    /// 512 bytes.
+    pub fn valid_sector_size(bytes: u32) -> bool {
+        bytes >= 512 && bytes.is_power_of_two()
+    }
+
+    /// Make a value safe to store in an EWF header, which keeps each value on one line of a
+    /// tab-separated table. Line breaks become " / ", so the lines of a multi-line value can still
+    /// be told apart, blank lines are dropped, and tabs and other control characters become
+    /// spaces.
+    pub fn header_text(value: &str) -> String {
+        value
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .map(|c| if c.is_control() { ' ' } else { c })
+                    .collect::<String>()
+                    .trim()
+                    .to_string()
+            })
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+
+    /// The kind of media the evidence is, as recorded in the image header.
+    #[derive(Copy, Clone, Default, PartialEq)]
+    pub enum MediaType {
+        /// A fixed disk, such as an internal hard drive or SSD.
+        #[default]
+        Fixed, // default
+        /// Removable media, such as a USB flash drive.
+        Removable,
+        /// An optical disc, such as a CD or DVD.
+        Optical,
+        /// A memory card or memory device, such as an SD card.
+        Memory,
+    }
+
+    impl MediaType {
+        /// Returns the value ewfacquirestream expects for `-m`.
+        pub fn as_arg(self) -> &'static str {
+            match self {
+                MediaType::Fixed => "fixed",
+                MediaType::Removable => "removable",
+                MediaType::Optical => "optical",
+                MediaType::Memory => "memory",
+            }
+        }
+
+        /// Returns the media type for a value of `-m`, as returned by `as_arg`.
+        pub fn from_arg(arg: &str) -> Option<MediaType> {
+            match arg {
+                "fixed" => Some(MediaType::Fixed),
+                "removable" => Some(MediaType::Removable),
+                "optical" => Some(MediaType::Optical),
+                "memory" => Some(MediaType::Memory),
+                _ => None,
+            }
+        }
     }
 
-    impl Default for EwfFormat {
-        fn default() -> Self {
-            EwfFormat::Encase6
+    impl fmt::Display for MediaType {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                MediaType::Fixed => write!(f, "Fixed disk"),
+                MediaType::Removable => write!(f, "Removable media"),
+                MediaType::Optical => write!(f, "Optical disc"),
+                MediaType::Memory => write!(f, "Memory"),
+            }
+        }
+    }
+
+    /// Whether the evidence is a whole physical device or a logical part of one, such as a
+    /// partition or volume, as recorded in the image header.
+    #[derive(Copy, Clone, Default, PartialEq)]
+    pub enum MediaFlags {
+        #[default]
+        Physical, // default
+        Logical,
+    }
+
+    impl MediaFlags {
+        /// Returns the value ewfacquirestream expects for `-M`.
+        pub fn as_arg(self) -> &'static str {
+            match self {
+                MediaFlags::Physical => "physical",
+                MediaFlags::Logical => "logical",
+            }
+        }
+
+        /// Returns the media flags for a value of `-M`, as returned by `as_arg`.
+        pub fn from_arg(arg: &str) -> Option<MediaFlags> {
+            match arg {
+                "physical" => Some(MediaFlags::Physical),
+                "logical" => Some(MediaFlags::Logical),
+                _ => None,
+            }
+        }
+    }
+
+    impl fmt::Display for MediaFlags {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                MediaFlags::Physical => write!(f, "Physical"),
+                MediaFlags::Logical => write!(f, "Logical"),
+            }
         }
     }
 
@@ -88,6 +388,10 @@ pub mod ewfargs {
     pub struct ArgsList {
         /// The device to image.
         pub source_device: Option<String>, // set in windows::select_source
+        /// What kind of source `source_device` is.
+        pub source_kind: SourceKind, // set in windows::select_source_then and convert_image::image_chosen
+        /// The part of the source to image, if not all of it.
+        pub source_range: Option<SourceRange>, // set in windows::select_partition
         /// The number of sectors to read at once.
         pub num_sectors: NumSectors, // TODO
         /// The type of compression to use.
@@ -106,22 +410,232 @@ pub mod ewfargs {
         pub ewf_format: EwfFormat, // set in windows::target_info_next
         /// Notes about the evidence.
         pub notes: Option<String>, // set in windows::examiner_info_next
+        /// The labels and values of the custom fields from the configuration, in order.
+        pub custom_fields: Vec<(String, String)>, // set in windows::examiner_info_next
         /// How many bytes are in a sector.
-        pub bytes_per_sector: Option<i32>, // TODO
+        pub bytes_per_sector: Option<u32>, // set in windows::technical_options_next
         /// How large to make segments for segmented image files.
         pub segment_file_size: Option<String>, // set in windows::target_info_next
-        /// The path to the target.
-        pub target_dir: Option<String>, // set in windows::target_info_next
-        /// The path to the seconday target.
-        pub secondary_target_dir: Option<String>, // set in windows::target_info_next
-        /// The filename to use for both the target and the secondary target.
+        /// The directories to write a copy of the image to.
+        pub target_dirs: Vec<String>, // set in windows::target_info_next
+        /// The filename to use for every copy of the image.
         pub target_filename: Option<String>, // set in windows::target_info_next
+        /// The filename as it was typed, with any placeholders, which `target_filename` is made
+        /// from each time the target information form is submitted.
+        pub target_template: Option<String>, // set in windows::target_info_next
+        /// How many times to retry a failed read before treating its sectors as unreadable.
+        pub read_retries: u32, // set in windows::technical_options_next
+        /// How many sectors to give up on at once after a read error; the sectors per chunk if unset.
+        pub error_granularity: Option<u32>, // set in windows::technical_options_next
+        /// Unreadable sectors are always zeroed in the image. This chooses whether the whole error
+        /// granularity block around a read error is zeroed with them, as EnCase and `ewfacquire -w`
+        /// do, rather than re-reading the block sector by sector to keep what can be read.
+        pub zero_whole_block: bool, // set in windows::technical_options_next
+        /// Whether to read the source with the multi-pass rescue engine rather than in one pass.
+        pub rescue: bool, // set in windows::technical_options_next
+        /// Whether the rescue engine's first pass reads from the end of the source backwards.
+        pub rescue_reverse: bool, // set in windows::technical_options_next
+        /// The kind of media the source is.
+        pub media_type: MediaType, // set in windows::use_source and windows::technical_options_next
+        /// Whether the source is a whole physical device or a logical part of one.
+        pub media_flags: MediaFlags, // set in windows::use_source and windows::technical_options_next
+        /// How many bytes to read from the source at once; one chunk if unset.
+        pub read_buffer: Option<usize>, // set in windows::technical_options_next
+        /// How many destinations to write to at the same time; one at a time if unset.
+        pub writer_threads: Option<u32>, // set in windows::technical_options_next
+        /// Whether to benchmark the source and destinations before imaging, and use the read
+        /// buffer size and writer threads that were fastest instead of the ones set.
+        pub auto_tune: bool, // set in windows::technical_options_next
     }
 
     impl ArgsList {
         /// Returns a new `ArgsList` with all default options.
         pub fn new() -> ArgsList {
-            Default::default()
+            ArgsList {
+                read_retries: 2,
+                ..Default::default()
+            }
+        }
+
+        /// Returns the segment size in bytes, if the image should be segmented.
+        ///
+        /// The segment size is validated when it is entered, so this only returns `None` when
+        /// no segment size has been set.
+        pub fn segment_size_bytes(&self) -> Option<u64> {
+            self.segment_file_size
+                .as_ref()
+                .and_then(|size| convert_byte_size_string::convert_to_bytes(size).ok())
+                .map(|size| size as u64)
+        }
+
+        /// Returns the number of bytes to read from the source at once.
+        pub fn chunk_size(&self) -> usize {
+            self.bytes_per_sector.unwrap_or(512) as usize * self.num_sectors.sectors() as usize
+        }
+
+        /// Returns the number of bytes to read from the source at once, which is one chunk unless
+        /// a read buffer size is set.
+        pub fn read_buffer_size(&self) -> usize {
+            self.read_buffer.unwrap_or_else(|| self.chunk_size())
+        }
+
+        /// Returns how many destinations to write to at the same time.
+        pub fn writer_thread_count(&self) -> usize {
+            self.writer_threads.unwrap_or(1).max(1) as usize
+        }
+
+        /// Returns how read errors should be handled while reading the source.
+        pub fn error_policy(&self) -> ErrorPolicy {
+            ErrorPolicy {
+                sector_size: self.bytes_per_sector.unwrap_or(512) as u64,
+                retries: self.read_retries,
+                granularity: self
+                    .error_granularity
+                    .unwrap_or_else(|| self.num_sectors.sectors())
+                    as u64,
+                zero_block: self.zero_whole_block,
+            }
+        }
+
+        /// Returns how the rescue engine should read the source. Failed sectors get one retry
+        /// pass per read retry.
+        pub fn rescue_options(&self) -> RescueOptions {
+            let cluster_size = self.chunk_size() as u64;
+            RescueOptions {
+                sector_size: self.bytes_per_sector.unwrap_or(512) as u64,
+                cluster_size,
+                skip_size: cluster_size.max(64 * 1024),
+                retry_passes: self.read_retries,
+                reverse: self.rescue_reverse,
+            }
+        }
+
+        /// Returns whether the source looks like a whole physical device or a logical part of one:
+        /// a partition of a drive, or a source that isn't a physical drive, is logical.
+        pub fn suggested_media_flags(&self) -> MediaFlags {
+            let partition = self
+                .source_range
+                .as_ref()
+                .is_some_and(|range| range.partition.is_some());
+            if self.source_kind == SourceKind::Other || partition {
+                MediaFlags::Logical
+            } else {
+                MediaFlags::Physical
+            }
+        }
+
+        /// Returns the notes to store in the image: the notes followed by each custom field that
+        /// was filled in, since the EWF header has no place of its own for them.
+        pub fn header_notes(&self) -> Option<String> {
+            let mut parts: Vec<String> = self.notes.iter().cloned().collect();
+            parts.extend(
+                self.custom_fields
+                    .iter()
+                    .filter(|(_, value)| !value.is_empty())
+                    .map(|(label, value)| format!("{}: {}", label, value)),
+            );
+            parts.retain(|part| !part.is_empty());
+            if parts.is_empty() {
+                None
+            } else {
+                Some(parts.join("; "))
+            }
+        }
+
+        /// Returns the case fields stored in the image header, by their ewfacquirestream flags and
+        /// their labels, with the values made safe by `header_text`.
+        pub fn header_values(&self) -> Vec<(&'static str, &'static str, Option<String>)> {
+            let safe = |value: &Option<String>| value.as_deref().map(header_text);
+            vec![
+                ("-C", "Case number", safe(&self.case_number)),
+                ("-D", "Description", safe(&self.description)),
+                ("-e", "Examiner name", safe(&self.examiner_name)),
+                ("-E", "Evidence number", safe(&self.evidence_number)),
+                ("-N", "Notes", safe(&self.header_notes())),
+            ]
+        }
+
+        /// Returns a warning for each case field the image format can't store as it was entered,
+        /// showing what tools reading the image will see, as `EwfFormat::header_preview` does.
+        /// Only formats with an ASCII header alone have warnings.
+        pub fn header_warnings(&self) -> Vec<String> {
+            if !self.ewf_format.has_ascii_header_only() {
+                return Vec::new();
+            }
+
+            let mut warnings = Vec::new();
+            for (_, label, value) in self.header_values() {
+                let value = match value {
+                    Some(ref value) if !value.is_ascii() => value,
+                    _ => continue,
+                };
+                warnings.push(format!(
+                    "{} has characters other than ASCII. {} images will show:\n{}",
+                    label,
+                    self.ewf_format,
+                    self.ewf_format.header_preview(value)
+                ));
+            }
+            warnings
+        }
+
+        /// Build the argument list for ewfacquirestream.
+        ///
+        /// # Arguments
+        ///
+        /// * `target` - The path to write the image to, without an extension.
+        pub fn ewfacquirestream_args(&self, target: &str) -> Vec<String> {
+            fn push(list: &mut Vec<String>, flag: &str, value: &Option<String>) {
+                if let Some(value) = value {
+                    list.push(flag.to_string());
+                    list.push(value.clone());
+                }
+            }
+
+            let mut list = vec![
+                "-q".to_string(),
+                "-b".to_string(),
+                self.num_sectors.sectors().to_string(),
+                "-c".to_string(),
+                self.compression_type.as_arg().to_string(),
+                "-f".to_string(),
+                self.ewf_format.as_arg().to_string(),
+                "-m".to_string(),
+                self.media_type.as_arg().to_string(),
+                "-M".to_string(),
+                self.media_flags.as_arg().to_string(),
+            ];
+
+            for (flag, _, value) in self.header_values() {
+                push(&mut list, flag, &value);
+            }
+            push(
+                &mut list,
+                "-P",
+                &self.bytes_per_sector.map(|bytes| bytes.to_string()),
+            );
+            push(
+                &mut list,
+                "-S",
+                &self.segment_size_bytes().map(|size| size.to_string()),
+            );
+
+            let mut digests = Vec::new();
+            if self.digest_type.contains(DigestType::SHA1) {
+                digests.push("sha1");
+            }
+            if self.digest_type.contains(DigestType::SHA256) {
+                digests.push("sha256");
+            }
+            if !digests.is_empty() {
+                list.push("-d".to_string());
+                list.push(digests.join(","));
+            }
+
+            list.push("-t".to_string());
+            list.push(target.to_string());
+
+            list
         }
     }
 }
@@ -0,0 +1,677 @@
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::image::read_full;
+
+/// The partitioning scheme a partition was found in.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Scheme {
+    /// A classic MBR partition table, including logical partitions in an extended partition.
+    Mbr,
+    /// A GUID partition table.
+    Gpt,
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scheme::Mbr => write!(f, "MBR"),
+            Scheme::Gpt => write!(f, "GPT"),
+        }
+    }
+}
+
+/// A partition found on a drive.
+#[derive(Clone)]
+pub struct Partition {
+    /// The partitioning scheme the partition was found in.
+    pub scheme: Scheme,
+    /// The partition number, as Linux would number it; MBR logical partitions start at 5.
+    pub number: u32,
+    /// A human-readable name for the partition type.
+    pub type_name: String,
+    /// The MBR type byte, or the GPT type GUID.
+    pub type_id: String,
+    /// The unique GUID of a GPT partition.
+    pub guid: Option<String>,
+    /// The name of a GPT partition.
+    pub label: Option<String>,
+    /// The first sector of the partition.
+    pub start: u64,
+    /// The number of sectors in the partition.
+    pub sectors: u64,
+}
+
+impl fmt::Display for Partition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Partition {} ({} {}",
+            self.number, self.scheme, self.type_name
+        )?;
+        if let Some(ref label) = self.label {
+            write!(f, ", \"{}\"", label)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// The part of a source to image, when only a partition or a range of sectors is wanted.
+#[derive(Clone)]
+pub struct SourceRange {
+    /// The size of a sector in bytes.
+    pub sector_size: u64,
+    /// The first sector to image.
+    pub start: u64,
+    /// The number of sectors to image.
+    pub sectors: u64,
+    /// The partition the range covers, if one was chosen.
+    pub partition: Option<Partition>,
+}
+
+impl SourceRange {
+    /// Returns the range covering a partition.
+    pub fn from_partition(partition: &Partition, sector_size: u64) -> SourceRange {
+        SourceRange {
+            sector_size,
+            start: partition.start,
+            sectors: partition.sectors,
+            partition: Some(partition.clone()),
+        }
+    }
+
+    /// Returns the offset of the range from the start of the source, in bytes. A range that
+    /// couldn't be on any source gives `u64::MAX`; `fits` checks for that.
+    pub fn offset(&self) -> u64 {
+        self.start.saturating_mul(self.sector_size)
+    }
+
+    /// Returns the length of the range in bytes, or `u64::MAX` as `offset` does.
+    pub fn length(&self) -> u64 {
+        self.sectors.saturating_mul(self.sector_size)
+    }
+
+    /// Returns true if the range holds at least one sector and ends within a source of `size`
+    /// bytes.
+    pub fn fits(&self, size: u64) -> bool {
+        let end = self
+            .start
+            .checked_add(self.sectors)
+            .and_then(|end| end.checked_mul(self.sector_size));
+        self.sectors > 0 && end.is_some_and(|end| end <= size)
+    }
+}
+
+/// MBR partition types that hold a chain of logical partitions.
+const EXTENDED_TYPES: &[u8] = &[0x05, 0x0f, 0x85];
+
+/// The most common MBR partition types.
+const MBR_TYPES: &[(u8, &str)] = &[
+    (0x01, "FAT12"),
+    (0x04, "FAT16 (<32M)"),
+    (0x05, "Extended"),
+    (0x06, "FAT16"),
+    (0x07, "NTFS/exFAT/HPFS"),
+    (0x0b, "FAT32"),
+    (0x0c, "FAT32 (LBA)"),
+    (0x0e, "FAT16 (LBA)"),
+    (0x0f, "Extended (LBA)"),
+    (0x27, "Windows recovery"),
+    (0x82, "Linux swap"),
+    (0x83, "Linux"),
+    (0x85, "Linux extended"),
+    (0x8e, "Linux LVM"),
+    (0xa5, "FreeBSD"),
+    (0xa8, "Apple UFS"),
+    (0xaf, "Apple HFS/HFS+"),
+    (0xee, "GPT protective"),
+    (0xef, "EFI System"),
+    (0xfd, "Linux RAID"),
+];
+
+/// The most common GPT partition type GUIDs.
+const GPT_TYPES: &[(&str, &str)] = &[
+    ("C12A7328-F81F-11D2-BA4B-00A0C93EC93B", "EFI System"),
+    ("21686148-6449-6E6F-744E-656564454649", "BIOS boot"),
+    ("E3C9E316-0B5C-4DB8-817D-F92DF00215AE", "Microsoft reserved"),
+    (
+        "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7",
+        "Microsoft basic data",
+    ),
+    ("DE94BBA4-06D1-4D40-A16A-BFD50179D6AC", "Windows recovery"),
+    (
+        "5808C8AA-7E8F-42E0-85D2-E1E90434CFB3",
+        "Microsoft LDM metadata",
+    ),
+    ("AF9B60A0-1431-4F62-BC68-3311714A69AD", "Microsoft LDM data"),
+    ("0FC63DAF-8483-4772-8E79-3D69D8477DE4", "Linux filesystem"),
+    (
+        "4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709",
+        "Linux root (x86-64)",
+    ),
+    ("0657FD6D-A4AB-43C4-84E5-0933C84B4F4F", "Linux swap"),
+    ("E6D6D379-F507-44C2-A23C-238F2A3DF928", "Linux LVM"),
+    ("A19D880F-05FC-4D3B-A006-743F0F84911E", "Linux RAID"),
+    ("48465300-0000-11AA-AA11-00306543ECAC", "Apple HFS+"),
+    ("7C3457EF-0000-11AA-AA11-00306543ECAC", "Apple APFS"),
+];
+
+/// The most logical partitions to follow, in case an extended partition's chain loops.
+const MAX_LOGICAL: u32 = 256;
+
+/// The most entries a GPT can have for grim to read it. Real tables have 128.
+const MAX_GPT_ENTRIES: u64 = 16384;
+
+/// The largest GPT entry grim reads. The UEFI specification requires entries to be 128 bytes
+/// times a power of two, and real tables use 128.
+const MAX_GPT_ENTRY_SIZE: u64 = 4096;
+
+/// The largest GPT partition entry array grim reads, so a corrupt header can't make it allocate
+/// more than this.
+const MAX_GPT_TABLE_BYTES: u64 = 4 * 1024 * 1024;
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0_u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0_u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Format a GUID stored in the mixed-endian layout GPT uses.
+pub fn format_guid(data: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{}-{}",
+        u32_at(data, 0),
+        u16_at(data, 4),
+        u16_at(data, 6),
+        data[8..10]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>(),
+        data[10..16]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>()
+    )
+}
+
+/// Returns a name for an MBR partition type.
+fn mbr_type_name(partition_type: u8) -> String {
+    MBR_TYPES
+        .iter()
+        .find(|(id, _)| *id == partition_type)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| format!("Unknown (0x{:02x})", partition_type))
+}
+
+/// Returns a name for a GPT partition type GUID.
+fn gpt_type_name(type_guid: &str) -> String {
+    GPT_TYPES
+        .iter()
+        .find(|(guid, _)| *guid == type_guid)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| String::from("Unknown"))
+}
+
+/// Read `count` sectors starting at `sector`. A short read at the end of the device, or sectors
+/// that couldn't be on any device, are an `ErrorKind::UnexpectedEof` error.
+fn read_sectors<R: Read + Seek + ?Sized>(
+    device: &mut R,
+    sector_size: u64,
+    sector: u64,
+    count: u64,
+) -> io::Result<Vec<u8>> {
+    let (offset, length) = match (
+        sector.checked_mul(sector_size),
+        count.checked_mul(sector_size),
+    ) {
+        (Some(offset), Some(length)) if offset.checked_add(length).is_some() => (offset, length),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("Sector {} is past the end of any device", sector),
+            ))
+        }
+    };
+    let mut data = vec![0_u8; length as usize];
+    device.seek(SeekFrom::Start(offset))?;
+    if read_full(device, &mut data)? < data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Sector {} is past the end of the device", sector),
+        ));
+    }
+    Ok(data)
+}
+
+/// An entry in an MBR or EBR partition table.
+struct MbrEntry {
+    partition_type: u8,
+    start: u64,
+    sectors: u64,
+}
+
+/// Parse the four entries of an MBR or EBR, skipping empty ones. Returns `None` if the sector
+/// doesn't end with the boot signature.
+fn mbr_entries(sector: &[u8]) -> Option<Vec<MbrEntry>> {
+    if sector.len() < 512 || sector[510] != 0x55 || sector[511] != 0xaa {
+        return None;
+    }
+
+    Some(
+        (0..4)
+            .map(|index| {
+                let entry = &sector[446 + index * 16..446 + (index + 1) * 16];
+                MbrEntry {
+                    partition_type: entry[4],
+                    start: u64::from(u32_at(entry, 8)),
+                    sectors: u64::from(u32_at(entry, 12)),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Follow the chain of EBRs in an extended partition, returning its logical partitions. The
+/// chain ends at an EBR without a boot signature or past the end of the device, or at one
+/// already visited.
+fn logical_partitions<R: Read + Seek + ?Sized>(
+    device: &mut R,
+    sector_size: u64,
+    extended_start: u64,
+) -> io::Result<Vec<Partition>> {
+    let mut partitions = Vec::new();
+    let mut visited = Vec::new();
+    let mut ebr = extended_start;
+
+    for number in 5..5 + MAX_LOGICAL {
+        if visited.contains(&ebr) {
+            break;
+        }
+        visited.push(ebr);
+        let sector = match read_sectors_if_present(device, sector_size, ebr, 1)? {
+            Some(sector) => sector,
+            None => break,
+        };
+        let entries = match mbr_entries(&sector) {
+            Some(entries) => entries,
+            None => break,
+        };
+
+        // The first entry is relative to this EBR, the link to the next EBR is relative to the
+        // start of the extended partition
+        let logical = &entries[0];
+        if logical.partition_type != 0 && logical.sectors != 0 {
+            partitions.push(Partition {
+                scheme: Scheme::Mbr,
+                number,
+                type_name: mbr_type_name(logical.partition_type),
+                type_id: format!("0x{:02x}", logical.partition_type),
+                guid: None,
+                label: None,
+                start: ebr + logical.start,
+                sectors: logical.sectors,
+            });
+        }
+
+        let link = &entries[1];
+        if !EXTENDED_TYPES.contains(&link.partition_type) || link.start == 0 {
+            break;
+        }
+        ebr = extended_start + link.start;
+    }
+
+    Ok(partitions)
+}
+
+/// Read sectors as `read_sectors` does, returning `None` if they are past the end of the device.
+fn read_sectors_if_present<R: Read + Seek + ?Sized>(
+    device: &mut R,
+    sector_size: u64,
+    sector: u64,
+    count: u64,
+) -> io::Result<Option<Vec<u8>>> {
+    match read_sectors(device, sector_size, sector, count) {
+        Ok(data) => Ok(Some(data)),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Parse a GUID partition table, whose header is in sector 1. Returns `None` if there is no valid
+/// GPT header: one that is missing, or whose partition entry array is out of range or larger
+/// than `MAX_GPT_TABLE_BYTES`.
+fn gpt_partitions<R: Read + Seek + ?Sized>(
+    device: &mut R,
+    sector_size: u64,
+) -> io::Result<Option<Vec<Partition>>> {
+    let header = match read_sectors_if_present(device, sector_size, 1, 1)? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    if &header[0..8] != b"EFI PART" {
+        return Ok(None);
+    }
+
+    let entries_start = u64_at(&header, 72);
+    let entry_count = u64::from(u32_at(&header, 80));
+    let entry_size = u64::from(u32_at(&header, 84));
+    if !(128..=MAX_GPT_ENTRY_SIZE).contains(&entry_size)
+        || !entry_size.is_multiple_of(128)
+        || entry_count > MAX_GPT_ENTRIES
+    {
+        return Ok(None);
+    }
+    let table_bytes = match entry_count.checked_mul(entry_size) {
+        Some(bytes) if bytes <= MAX_GPT_TABLE_BYTES => bytes,
+        _ => return Ok(None),
+    };
+
+    let table_sectors = table_bytes.div_ceil(sector_size);
+    let table = match read_sectors_if_present(device, sector_size, entries_start, table_sectors)? {
+        Some(table) => table,
+        None => return Ok(None),
+    };
+
+    let mut partitions = Vec::new();
+    for (index, entry) in table
+        .chunks(entry_size as usize)
+        .take(entry_count as usize)
+        .enumerate()
+    {
+        if entry[0..16].iter().all(|byte| *byte == 0) {
+            continue;
+        }
+
+        let type_guid = format_guid(&entry[0..16]);
+        let first = u64_at(entry, 32);
+        let last = u64_at(entry, 40);
+        let name: Vec<u16> = entry[56..128]
+            .chunks(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|unit| *unit != 0)
+            .collect();
+        let label = String::from_utf16_lossy(&name);
+
+        partitions.push(Partition {
+            scheme: Scheme::Gpt,
+            number: index as u32 + 1,
+            type_name: gpt_type_name(&type_guid),
+            type_id: type_guid,
+            guid: Some(format_guid(&entry[16..32])),
+            label: if label.is_empty() { None } else { Some(label) },
+            start: first,
+            sectors: last.saturating_sub(first) + 1,
+        });
+    }
+
+    Ok(Some(partitions))
+}
+
+/// Read the partition table of a drive.
+///
+/// A protective MBR (one with a type 0xEE entry) means the drive uses GPT; if the GPT header is
+/// missing, the MBR entries are listed instead. Extended partitions are listed along with the
+/// logical partitions inside them.
+///
+/// # Arguments
+///
+/// * `device` - The drive, or an image of it.
+/// * `sector_size` - The logical sector size of the drive.
+///
+/// # Returns
+///
+/// - `Err` if the drive could not be read
+/// - `Ok` with an empty list if the drive has no partition table
+/// - `Ok` with the partitions, in table order
+pub fn read_partitions<R: Read + Seek + ?Sized>(
+    device: &mut R,
+    sector_size: u64,
+) -> io::Result<Vec<Partition>> {
+    let sector_size = sector_size.max(512);
+    let mbr = read_sectors(device, sector_size, 0, 1)?;
+    let entries = match mbr_entries(&mbr) {
+        Some(entries) => entries,
+        None => return Ok(Vec::new()),
+    };
+
+    if entries.iter().any(|entry| entry.partition_type == 0xee) {
+        if let Some(partitions) = gpt_partitions(device, sector_size)? {
+            return Ok(partitions);
+        }
+    }
+
+    let mut partitions = Vec::new();
+    let mut logical = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.partition_type == 0 || entry.sectors == 0 {
+            continue;
+        }
+
+        partitions.push(Partition {
+            scheme: Scheme::Mbr,
+            number: index as u32 + 1,
+            type_name: mbr_type_name(entry.partition_type),
+            type_id: format!("0x{:02x}", entry.partition_type),
+            guid: None,
+            label: None,
+            start: entry.start,
+            sectors: entry.sectors,
+        });
+
+        if EXTENDED_TYPES.contains(&entry.partition_type) && logical.is_empty() {
+            logical = logical_partitions(device, sector_size, entry.start)?;
+        }
+    }
+    partitions.extend(logical);
+
+    Ok(partitions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const SECTOR: u64 = 512;
+
+    /// Returns an empty disk of `sectors` sectors.
+    fn disk(sectors: u64) -> Vec<u8> {
+        vec![0_u8; (sectors * SECTOR) as usize]
+    }
+
+    /// Write an entry into the MBR or EBR at `sector`, and give it a boot signature.
+    fn mbr_entry(
+        disk: &mut [u8],
+        sector: u64,
+        index: usize,
+        partition_type: u8,
+        start: u32,
+        sectors: u32,
+    ) {
+        let base = (sector * SECTOR) as usize;
+        let entry = base + 446 + index * 16;
+        disk[entry + 4] = partition_type;
+        disk[entry + 8..entry + 12].copy_from_slice(&start.to_le_bytes());
+        disk[entry + 12..entry + 16].copy_from_slice(&sectors.to_le_bytes());
+        disk[base + 510] = 0x55;
+        disk[base + 511] = 0xaa;
+    }
+
+    /// Write a GPT header in sector 1 with its entry array starting at `entries_start`.
+    fn gpt_header(disk: &mut [u8], entries_start: u64, entry_count: u32, entry_size: u32) {
+        let header = SECTOR as usize;
+        disk[header..header + 8].copy_from_slice(b"EFI PART");
+        disk[header + 72..header + 80].copy_from_slice(&entries_start.to_le_bytes());
+        disk[header + 80..header + 84].copy_from_slice(&entry_count.to_le_bytes());
+        disk[header + 84..header + 88].copy_from_slice(&entry_size.to_le_bytes());
+    }
+
+    /// Write a 128-byte GPT entry into the array starting at sector 2.
+    fn gpt_entry(
+        disk: &mut [u8],
+        index: usize,
+        type_guid: [u8; 16],
+        first: u64,
+        last: u64,
+        label: &str,
+    ) {
+        let entry = 2 * SECTOR as usize + index * 128;
+        disk[entry..entry + 16].copy_from_slice(&type_guid);
+        disk[entry + 16] = index as u8 + 1;
+        disk[entry + 32..entry + 40].copy_from_slice(&first.to_le_bytes());
+        disk[entry + 40..entry + 48].copy_from_slice(&last.to_le_bytes());
+        for (offset, unit) in label.encode_utf16().enumerate() {
+            disk[entry + 56 + offset * 2..entry + 58 + offset * 2]
+                .copy_from_slice(&unit.to_le_bytes());
+        }
+    }
+
+    /// The type GUID of a Linux filesystem, as it is stored on disk.
+    const LINUX_FILESYSTEM: [u8; 16] = [
+        0xaf, 0x3d, 0xc6, 0x0f, 0x83, 0x84, 0x72, 0x47, 0x8e, 0x79, 0x3d, 0x69, 0xd8, 0x47, 0x7d,
+        0xe4,
+    ];
+
+    fn read(disk: Vec<u8>) -> Vec<(u32, String, u64, u64)> {
+        read_partitions(&mut Cursor::new(disk), SECTOR)
+            .unwrap()
+            .into_iter()
+            .map(|partition| {
+                (
+                    partition.number,
+                    partition.type_name,
+                    partition.start,
+                    partition.sectors,
+                )
+            })
+            .collect()
+    }
+
+    fn partition(
+        number: u32,
+        type_name: &str,
+        start: u64,
+        sectors: u64,
+    ) -> (u32, String, u64, u64) {
+        (number, type_name.to_string(), start, sectors)
+    }
+
+    #[test]
+    fn reads_primary_mbr_partitions() {
+        let mut image = disk(64);
+        mbr_entry(&mut image, 0, 0, 0x83, 2, 20);
+        mbr_entry(&mut image, 0, 2, 0x07, 30, 10);
+        assert_eq!(
+            read(image),
+            vec![
+                partition(1, "Linux", 2, 20),
+                partition(3, "NTFS/exFAT/HPFS", 30, 10),
+            ]
+        );
+
+        // Without a boot signature, there's no partition table
+        assert!(read(disk(64)).is_empty());
+    }
+
+    #[test]
+    fn follows_logical_partitions() {
+        let mut image = disk(64);
+        mbr_entry(&mut image, 0, 0, 0x83, 2, 8);
+        mbr_entry(&mut image, 0, 1, 0x0f, 10, 40);
+        // Links to later EBRs are relative to the extended partition
+        mbr_entry(&mut image, 10, 0, 0x83, 1, 4);
+        mbr_entry(&mut image, 10, 1, 0x05, 10, 10);
+        mbr_entry(&mut image, 20, 0, 0x82, 1, 5);
+        mbr_entry(&mut image, 20, 1, 0x05, 20, 10);
+        mbr_entry(&mut image, 30, 0, 0x83, 2, 6);
+        assert_eq!(
+            read(image),
+            vec![
+                partition(1, "Linux", 2, 8),
+                partition(2, "Extended (LBA)", 10, 40),
+                partition(5, "Linux", 11, 4),
+                partition(6, "Linux swap", 21, 5),
+                partition(7, "Linux", 32, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_a_looping_or_broken_chain() {
+        let mut chain = disk(64);
+        mbr_entry(&mut chain, 0, 0, 0x05, 10, 40);
+        mbr_entry(&mut chain, 10, 0, 0x83, 1, 4);
+        mbr_entry(&mut chain, 10, 1, 0x05, 10, 10);
+        mbr_entry(&mut chain, 20, 0, 0x83, 1, 5);
+        let expected = vec![
+            partition(1, "Extended", 10, 40),
+            partition(5, "Linux", 11, 4),
+            partition(6, "Linux", 21, 5),
+        ];
+
+        // The second EBR links to itself
+        let mut looping = chain.clone();
+        mbr_entry(&mut looping, 20, 1, 0x05, 10, 10);
+        assert_eq!(read(looping), expected);
+
+        // A link past the end of the device ends the chain, keeping what was found
+        let mut past_end = chain;
+        mbr_entry(&mut past_end, 20, 1, 0x05, 1000, 10);
+        assert_eq!(read(past_end), expected);
+    }
+
+    #[test]
+    fn reads_gpt_behind_a_protective_mbr() {
+        let mut image = disk(64);
+        mbr_entry(&mut image, 0, 0, 0xee, 1, 63);
+        gpt_header(&mut image, 2, 4, 128);
+        gpt_entry(&mut image, 0, LINUX_FILESYSTEM, 34, 43, "root");
+        gpt_entry(&mut image, 2, LINUX_FILESYSTEM, 44, 63, "");
+
+        let partitions = read_partitions(&mut Cursor::new(image), SECTOR).unwrap();
+        assert_eq!(partitions.len(), 2);
+        assert_eq!(partitions[0].number, 1);
+        assert_eq!(partitions[0].type_name, "Linux filesystem");
+        assert_eq!(
+            partitions[0].type_id,
+            "0FC63DAF-8483-4772-8E79-3D69D8477DE4"
+        );
+        assert_eq!(partitions[0].label.as_deref(), Some("root"));
+        assert_eq!((partitions[0].start, partitions[0].sectors), (34, 10));
+        assert_eq!(partitions[1].number, 3);
+        assert_eq!(partitions[1].label, None);
+        assert_eq!((partitions[1].start, partitions[1].sectors), (44, 20));
+    }
+
+    #[test]
+    fn corrupt_gpt_headers_fall_back_to_the_mbr() {
+        let protective = vec![partition(1, "GPT protective", 1, 63)];
+        let corrupt: [(u64, u32, u32); 5] = [
+            // Entries too large, and not a multiple of 128
+            (2, 4, u32::MAX),
+            (2, 4, 200),
+            // Too many entries
+            (2, u32::MAX, 128),
+            // An entry array past the end of the disk, or of any disk
+            (1000, 4, 128),
+            (u64::MAX / 2, 4, 128),
+        ];
+        for &(entries_start, entry_count, entry_size) in &corrupt {
+            let mut image = disk(64);
+            mbr_entry(&mut image, 0, 0, 0xee, 1, 63);
+            gpt_header(&mut image, entries_start, entry_count, entry_size);
+            gpt_entry(&mut image, 0, LINUX_FILESYSTEM, 34, 43, "root");
+            assert_eq!(read(image), protective);
+        }
+    }
+}
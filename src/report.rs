@@ -0,0 +1,609 @@
+use chrono::{DateTime, Local};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::acquire::AcquisitionResult;
+use crate::batch::BatchItem;
+use crate::compare::Comparison;
+use crate::ewfargs::{ArgsList, SourceKind};
+use crate::hash::Digests;
+use crate::smart::SmartSnapshot;
+use crate::verify::ImageVerification;
+use crate::LoggingInfo;
+
+/// Returns the value of an optional field for display, or "(none)" if it is unset or blank.
+fn field(value: &Option<String>) -> &str {
+    match value {
+        Some(value) if !value.is_empty() => value,
+        _ => "(none)",
+    }
+}
+
+/// Returns the path the report for an image in `dir` should be written to.
+pub fn report_path(dir: &Path, args: &ArgsList) -> PathBuf {
+    dir.join(format!(
+        "{}.report.txt",
+        args.target_filename.clone().unwrap_or_default()
+    ))
+}
+
+/// Returns the first report an acquisition would write next to a copy of its image that already
+/// exists. `write_report` never replaces a report, so an acquisition whose report path is taken
+/// would only fail once the image has been written.
+pub fn existing_report(args: &ArgsList) -> Option<PathBuf> {
+    args.target_dirs
+        .iter()
+        .map(|dir| report_path(Path::new(dir), args))
+        .find(|path| path.exists())
+}
+
+/// Returns the path the combined report for a batch of acquisitions for a case, started at
+/// `started`, should be written to in `dir`.
+pub fn case_report_path(dir: &Path, case_number: &str, started: DateTime<Local>) -> PathBuf {
+    dir.join(format!(
+        "{}.case-report-{}.txt",
+        case_number.replace('/', "_"),
+        started.format("%Y%m%d-%H%M%S")
+    ))
+}
+
+/// Append a labelled line to a report. The lines after the first of a multi-line value are
+/// indented to line up with it.
+fn line(report: &mut String, label: &str, value: &str) {
+    let value = value
+        .lines()
+        .collect::<Vec<_>>()
+        .join(&format!("\n{}", " ".repeat(23)));
+    report.push_str(&format!("  {:<21}{}\n", format!("{}:", label), value));
+}
+
+/// Append the report title.
+fn title(report: &mut String, kind: &str) {
+    let title = format!("grim {} {} report", env!("CARGO_PKG_VERSION"), kind);
+    report.push_str(&format!("{}\n{}\n\n", title, "=".repeat(title.len())));
+}
+
+/// Append the case information entered by the examiner.
+fn case_section(report: &mut String, args: &ArgsList) {
+    report.push_str("Case information\n");
+    line(report, "Examiner name", field(&args.examiner_name));
+    line(report, "Case number", field(&args.case_number));
+    line(report, "Evidence number", field(&args.evidence_number));
+    line(report, "Description", field(&args.description));
+    line(report, "Notes", field(&args.notes));
+    // Custom fields are written as they were entered, whatever the image header could hold
+    for (label, value) in &args.custom_fields {
+        line(
+            report,
+            label,
+            if value.is_empty() { "(none)" } else { value },
+        );
+    }
+    report.push('\n');
+}
+
+/// Append the SMART data read from the source drive before it was read.
+fn smart_section(report: &mut String, smart: &SmartSnapshot) {
+    let count = |value: Option<u64>| field(&value.map(|value| value.to_string())).to_string();
+
+    report.push_str("SMART\n");
+    line(report, "Read at", &smart.taken.to_rfc2822());
+    line(report, "Overall health", smart.health());
+    line(report, "Power-on hours", &count(smart.power_on_hours));
+    line(
+        report,
+        "Reallocated sectors",
+        &count(smart.reallocated_sectors),
+    );
+    line(report, "Pending sectors", &count(smart.pending_sectors));
+    line(report, "Uncorrectable", &count(smart.uncorrectable_sectors));
+    if smart.media_errors.is_some() {
+        line(report, "Media errors", &count(smart.media_errors));
+    }
+    line(report, "Temperature (C)", &count(smart.temperature));
+    for warning in smart.warnings() {
+        report.push_str(&format!("  WARNING: {}\n", warning));
+    }
+    if !smart.attributes.is_empty() {
+        report.push_str("  Attributes (ID, name, value, worst, threshold, raw):\n");
+        for attribute in &smart.attributes {
+            report.push_str(&format!(
+                "    {:>3} {:<26} {:>3} {:>3} {:>3} {}\n",
+                attribute.id,
+                attribute.name,
+                attribute.value,
+                attribute.worst,
+                attribute.threshold,
+                attribute.raw
+            ));
+        }
+    }
+    report.push('\n');
+}
+
+/// Append the details of the source drive and the host it was read on.
+fn source_section(report: &mut String, args: &ArgsList, info: &LoggingInfo, size: u64) {
+    report.push_str("Source\n");
+    line(report, "Source type", &args.source_kind.to_string());
+    let path_label = match args.source_kind {
+        SourceKind::Device => "Device",
+        SourceKind::Image => "Image",
+        SourceKind::Other => "Path",
+    };
+    line(report, path_label, field(&args.source_device));
+    line(report, "Product", field(&info.drive_product));
+    line(report, "Serial number", field(&info.drive_serial));
+    line(report, "Reported size", field(&info.drive_size));
+    let size_label = if args.source_range.is_some() {
+        "Imaged size"
+    } else {
+        "Size"
+    };
+    line(report, size_label, &format!("{} bytes", size));
+    line(report, "Host serial number", field(&info.host_serial));
+    if let Some(ref hidden) = info.hidden_areas {
+        line(report, "HPA", &hidden.hpa_summary());
+        line(report, "DCO", &hidden.dco_summary());
+    }
+    report.push('\n');
+
+    if let Some(ref smart) = info.smart {
+        smart_section(report, smart);
+    }
+
+    if let Some(ref range) = args.source_range {
+        report.push_str("Imaged range of the source\n");
+        if let Some(ref partition) = range.partition {
+            line(report, "Partition", &partition.to_string());
+            line(report, "Partition type", &partition.type_id);
+            if let Some(ref guid) = partition.guid {
+                line(report, "Partition GUID", guid);
+            }
+        }
+        line(report, "Sector size", &range.sector_size.to_string());
+        line(report, "First sector", &range.start.to_string());
+        line(report, "Sectors", &range.sectors.to_string());
+        line(report, "Byte offset", &range.offset().to_string());
+        report.push('\n');
+    }
+}
+
+/// Append a set of digests, prefixing each label with `prefix`.
+fn digest_lines(report: &mut String, prefix: &str, digests: &Digests) {
+    let md5 = if digests.md5.is_empty() {
+        "(none)"
+    } else {
+        &digests.md5
+    };
+    line(report, &format!("{}MD5", prefix), md5);
+    if let Some(ref sha1) = digests.sha1 {
+        line(report, &format!("{}SHA1", prefix), sha1);
+    }
+    if let Some(ref sha256) = digests.sha256 {
+        line(report, &format!("{}SHA256", prefix), sha256);
+    }
+}
+
+/// Append the times the source was read, the bytes read, and their digests.
+fn read_section(report: &mut String, heading: &str, result: &AcquisitionResult) {
+    report.push_str(heading);
+    report.push('\n');
+    line(report, "Started", &result.started.to_rfc2822());
+    line(report, "Finished", &result.finished.to_rfc2822());
+    for resumption in &result.resumptions {
+        line(
+            report,
+            "Resumed",
+            &format!(
+                "{} after {} bytes",
+                resumption.at.to_rfc2822(),
+                resumption.bytes_done
+            ),
+        );
+    }
+    line(report, "Bytes read", &result.bytes_acquired.to_string());
+    digest_lines(report, "", &result.digests);
+    if result.bad_sectors.is_empty() {
+        line(report, "Unreadable sectors", "none");
+    } else {
+        line(
+            report,
+            "Unreadable sectors",
+            &format!(
+                "{} in {} ranges, zeroed in the image",
+                result
+                    .bad_sectors
+                    .iter()
+                    .map(|range| range.count())
+                    .sum::<u64>(),
+                result.bad_sectors.len()
+            ),
+        );
+        for range in &result.bad_sectors {
+            report.push_str(&format!("    {}\n", range));
+        }
+    }
+    report.push('\n');
+}
+
+/// Build the chain-of-custody report for a finished acquisition.
+///
+/// # Arguments
+///
+/// * `args` - The arguments the acquisition was run with.
+/// * `info` - The logging information collected about the host and the source drive.
+/// * `result` - The result of the acquisition.
+pub fn acquisition_report(
+    args: &ArgsList,
+    info: &LoggingInfo,
+    result: &AcquisitionResult,
+) -> String {
+    let mut report = String::new();
+
+    title(&mut report, "acquisition");
+    case_section(&mut report, args);
+    source_section(&mut report, args, info, result.source_size);
+
+    report.push_str("Image\n");
+    line(&mut report, "Format", &args.ewf_format.to_string());
+    line(&mut report, "Media type", &args.media_type.to_string());
+    line(&mut report, "Media flags", &args.media_flags.to_string());
+    if !args.ewf_format.is_raw() {
+        line(
+            &mut report,
+            "Compression",
+            &args.compression_type.to_string(),
+        );
+    }
+    line(&mut report, "Segment size", field(&args.segment_file_size));
+    line(
+        &mut report,
+        "Bytes per sector",
+        field(&args.bytes_per_sector.map(|bytes| bytes.to_string())),
+    );
+    line(
+        &mut report,
+        "Sectors per chunk",
+        &result
+            .tuning
+            .as_ref()
+            .and_then(|tuning| tuning.num_sectors)
+            .unwrap_or(args.num_sectors)
+            .sectors()
+            .to_string(),
+    );
+    let (read_buffer, writer_threads) = match result.tuning {
+        Some(ref tuning) => (tuning.read_buffer, tuning.writer_threads),
+        None => (args.read_buffer_size(), args.writer_thread_count()),
+    };
+    line(
+        &mut report,
+        "Read buffer",
+        &format!("{} bytes", read_buffer),
+    );
+    line(&mut report, "Writer threads", &writer_threads.to_string());
+    line(
+        &mut report,
+        "Tuning",
+        &match result.tuning {
+            Some(ref tuning) if tuning.failed_trials > 0 => format!(
+                "auto, fastest of {} combinations benchmarked at {:.1} MB/s; {} failed and \
+                 were not considered",
+                tuning.trials,
+                tuning.throughput / 1_000_000.0,
+                tuning.failed_trials
+            ),
+            Some(ref tuning) => format!(
+                "auto, fastest of {} combinations benchmarked at {:.1} MB/s",
+                tuning.trials,
+                tuning.throughput / 1_000_000.0
+            ),
+            None => String::from("manual"),
+        },
+    );
+    match result.rescue {
+        Some(ref rescue) => {
+            line(
+                &mut report,
+                "Read method",
+                if args.rescue_reverse {
+                    "multi-pass rescue, first pass backwards"
+                } else {
+                    "multi-pass rescue"
+                },
+            );
+            line(&mut report, "Retry passes", &args.read_retries.to_string());
+            line(&mut report, "Rescue map", &rescue.map.to_string_lossy());
+            line(&mut report, "Rescued copy", &rescue.copy.to_string_lossy());
+            line(
+                &mut report,
+                "Resumed",
+                if rescue.resumed {
+                    "yes, from an interrupted rescue"
+                } else {
+                    "no"
+                },
+            );
+        }
+        None => {
+            let policy = args.error_policy();
+            line(&mut report, "Read retries", &policy.retries.to_string());
+            line(
+                &mut report,
+                "Error granularity",
+                &format!("{} sectors", policy.granularity),
+            );
+            line(
+                &mut report,
+                "On read error",
+                if policy.zero_block {
+                    "zero the whole granularity block"
+                } else {
+                    "re-read by sector, zero unreadable sectors"
+                },
+            );
+        }
+    }
+    if !args.ewf_format.is_raw() && !result.bad_sectors.is_empty() {
+        // ewfacquirestream reads a stream, so it has no way of being told about read errors
+        line(
+            &mut report,
+            "EWF error section",
+            "not written; the unreadable sectors are only listed in this report",
+        );
+    }
+    report.push('\n');
+
+    read_section(&mut report, "Acquisition", result);
+
+    report.push_str("Destinations\n");
+    for destination in &result.destinations {
+        report.push_str(&format!("  {}\n", destination.dir.display()));
+        for file in &destination.files {
+            report.push_str(&format!("    {}\n", file.display()));
+        }
+        match destination.verification {
+            Some(ref verification) if verification.matches => {
+                report.push_str("    Verified: digests match\n")
+            }
+            Some(ref verification) => report.push_str(&format!(
+                "    Verified: DIGESTS DO NOT MATCH (MD5 {})\n",
+                verification.digests.md5
+            )),
+            None => report.push_str("    Verified: not verified\n"),
+        }
+    }
+
+    report
+}
+
+/// Build the report for a source drive that was hashed without being imaged.
+///
+/// # Arguments
+///
+/// * `args` - The arguments hashing was run with.
+/// * `info` - The logging information collected about the host and the source drive.
+/// * `result` - The result of hashing the drive.
+pub fn hash_report(args: &ArgsList, info: &LoggingInfo, result: &AcquisitionResult) -> String {
+    let mut report = String::new();
+
+    title(&mut report, "hash");
+    case_section(&mut report, args);
+    source_section(&mut report, args, info, result.source_size);
+    read_section(&mut report, "Hashing", result);
+
+    report
+}
+
+/// Build the report for a drive compared against an existing image.
+///
+/// # Arguments
+///
+/// * `args` - The arguments the drive was hashed with.
+/// * `info` - The logging information collected about the host and the source drive.
+/// * `comparison` - The result of the comparison.
+pub fn comparison_report(args: &ArgsList, info: &LoggingInfo, comparison: &Comparison) -> String {
+    let mut report = String::new();
+
+    title(&mut report, "comparison");
+    case_section(&mut report, args);
+    source_section(&mut report, args, info, comparison.drive.source_size);
+    read_section(&mut report, "Hashing", &comparison.drive);
+
+    report.push_str("Image\n");
+    line(
+        &mut report,
+        "First segment",
+        &comparison.image.display().to_string(),
+    );
+    digest_lines(&mut report, "Stored ", &comparison.stored);
+    report.push('\n');
+
+    report.push_str("Result\n");
+    if comparison.matches() {
+        line(&mut report, "Digests", "MATCH");
+    } else {
+        line(&mut report, "Digests", "DO NOT MATCH");
+    }
+    match comparison.differing {
+        Some(ref ranges) if ranges.is_empty() => line(&mut report, "Differing sectors", "none"),
+        Some(ref ranges) => {
+            line(
+                &mut report,
+                "Differing sectors",
+                &format!(
+                    "{} in {} ranges",
+                    ranges.iter().map(|range| range.count()).sum::<u64>(),
+                    ranges.len()
+                ),
+            );
+            for range in ranges {
+                report.push_str(&format!("    {}\n", range));
+            }
+        }
+        None => line(&mut report, "Differing sectors", "not compared"),
+    }
+
+    report
+}
+
+/// Build the report for an existing image verified from the main menu.
+///
+/// # Arguments
+///
+/// * `args` - The examiner and report details entered for the verification.
+/// * `verification` - The result of the verification.
+pub fn verification_report(args: &ArgsList, verification: &ImageVerification) -> String {
+    let mut report = String::new();
+
+    title(&mut report, "verification");
+    report.push_str(
+        "Verification
+",
+    );
+    line(&mut report, "Examiner name", field(&args.examiner_name));
+    line(&mut report, "Case number", field(&args.case_number));
+    line(&mut report, "Evidence number", field(&args.evidence_number));
+    line(&mut report, "Started", &verification.started.to_rfc2822());
+    line(&mut report, "Finished", &verification.finished.to_rfc2822());
+    report.push('\n');
+
+    report.push_str("Image\n");
+    line(
+        &mut report,
+        "First segment",
+        &verification.image.display().to_string(),
+    );
+    digest_lines(&mut report, "Stored ", &verification.output.stored);
+    report.push('\n');
+
+    report.push_str("Calculated\n");
+    digest_lines(&mut report, "", &verification.output.calculated);
+    report.push('\n');
+
+    report.push_str("Result\n");
+    if verification.output.passed() {
+        line(&mut report, "Verification", "PASS");
+    } else if !verification.output.success {
+        line(
+            &mut report,
+            "Verification",
+            "FAIL (ewfverify reported failure)",
+        );
+    } else {
+        line(&mut report, "Verification", "FAIL (digests do not match)");
+    }
+
+    report
+}
+
+/// Returns the outcome of an acquisition in a few words.
+fn outcome(result: &Option<Result<AcquisitionResult, String>>) -> String {
+    match result {
+        None => String::from("not run"),
+        Some(Err(e)) => format!("FAILED: {}", e),
+        Some(Ok(result)) => {
+            let verified = result.destinations.iter().all(|destination| {
+                destination
+                    .verification
+                    .as_ref()
+                    .is_some_and(|verification| verification.matches)
+            });
+            if verified {
+                String::from("imaged, every copy verified")
+            } else {
+                String::from("imaged, VERIFICATION FAILED")
+            }
+        }
+    }
+}
+
+/// Build the combined report for a batch of sources imaged for one case, summarizing every
+/// acquisition. The full details of each are in its own acquisition report.
+///
+/// # Arguments
+///
+/// * `items` - Every item in the batch, with its result.
+/// * `started` - When the batch was started.
+pub fn case_report(items: &[BatchItem], started: DateTime<Local>) -> String {
+    let mut report = String::new();
+    let imaged = items
+        .iter()
+        .filter(|item| matches!(item.result, Some(Ok(_))))
+        .count();
+
+    title(&mut report, "case");
+    report.push_str("Case information\n");
+    if let Some(item) = items.first() {
+        line(
+            &mut report,
+            "Examiner name",
+            field(&item.args.examiner_name),
+        );
+        line(&mut report, "Case number", field(&item.args.case_number));
+        line(
+            &mut report,
+            "Host serial number",
+            field(&item.info.host_serial),
+        );
+    }
+    line(&mut report, "Batch started", &started.to_rfc2822());
+    line(&mut report, "Sources", &items.len().to_string());
+    line(&mut report, "Imaged", &imaged.to_string());
+    line(
+        &mut report,
+        "Not imaged",
+        &(items.len() - imaged).to_string(),
+    );
+    report.push('\n');
+
+    for item in items {
+        let args = &item.args;
+        report.push_str(&format!("Evidence {}\n", field(&args.evidence_number)));
+        line(&mut report, "Description", field(&args.description));
+        line(&mut report, "Source", field(&args.source_device));
+        line(&mut report, "Product", field(&item.info.drive_product));
+        line(&mut report, "Serial number", field(&item.info.drive_serial));
+        line(&mut report, "Outcome", &outcome(&item.result));
+        if let Some(Ok(ref result)) = item.result {
+            line(&mut report, "Started", &result.started.to_rfc2822());
+            line(&mut report, "Finished", &result.finished.to_rfc2822());
+            line(
+                &mut report,
+                "Bytes read",
+                &result.bytes_acquired.to_string(),
+            );
+            digest_lines(&mut report, "", &result.digests);
+            if !result.bad_sectors.is_empty() {
+                line(
+                    &mut report,
+                    "Unreadable sectors",
+                    &result
+                        .bad_sectors
+                        .iter()
+                        .map(|range| range.count())
+                        .sum::<u64>()
+                        .to_string(),
+                );
+            }
+            for destination in &result.destinations {
+                line(
+                    &mut report,
+                    "Report",
+                    &report_path(&destination.dir, args).display().to_string(),
+                );
+            }
+        }
+        report.push('\n');
+    }
+
+    report
+}
+
+/// Write a report to `path`. Refuses to overwrite an existing report.
+pub fn write_report(path: &Path, report: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(report.as_bytes())?;
+    file.sync_all()
+}
@@ -0,0 +1,1030 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::block::BlockDevice;
+use crate::compare::{push_range, SectorRange};
+
+/// The state of an area of the source, using the same characters as a ddrescue map file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlockStatus {
+    /// Not read yet.
+    NonTried,
+    /// A read covering this area failed; its edges haven't been trimmed yet.
+    NonTrimmed,
+    /// Trimmed, but not yet read sector by sector.
+    NonScraped,
+    /// Failed to read sector by sector.
+    BadSector,
+    /// Read successfully.
+    Finished,
+}
+
+impl BlockStatus {
+    /// Returns the character ddrescue uses for this status.
+    pub fn as_char(self) -> char {
+        match self {
+            BlockStatus::NonTried => '?',
+            BlockStatus::NonTrimmed => '*',
+            BlockStatus::NonScraped => '/',
+            BlockStatus::BadSector => '-',
+            BlockStatus::Finished => '+',
+        }
+    }
+
+    /// Returns the status for a ddrescue map file character.
+    pub fn from_char(status: char) -> Option<BlockStatus> {
+        match status {
+            '?' => Some(BlockStatus::NonTried),
+            '*' => Some(BlockStatus::NonTrimmed),
+            '/' => Some(BlockStatus::NonScraped),
+            '-' => Some(BlockStatus::BadSector),
+            '+' => Some(BlockStatus::Finished),
+            _ => None,
+        }
+    }
+}
+
+/// The phase a rescue is in, using the same characters as a ddrescue map file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Phase {
+    /// Reading areas that haven't been tried, in large clusters.
+    Copying,
+    /// Reading the edges of failed clusters sector by sector.
+    Trimming,
+    /// Reading what's left of failed clusters sector by sector.
+    Scraping,
+    /// Retrying bad sectors.
+    Retrying,
+    /// Every phase is done.
+    Finished,
+}
+
+impl Phase {
+    /// Returns the character ddrescue uses for this phase.
+    pub fn as_char(self) -> char {
+        match self {
+            Phase::Copying => '?',
+            Phase::Trimming => '*',
+            Phase::Scraping => '/',
+            Phase::Retrying => '-',
+            Phase::Finished => '+',
+        }
+    }
+
+    /// Returns the phase for a ddrescue map file character.
+    pub fn from_char(phase: char) -> Option<Phase> {
+        match phase {
+            '?' => Some(Phase::Copying),
+            '*' => Some(Phase::Trimming),
+            '/' => Some(Phase::Scraping),
+            '-' => Some(Phase::Retrying),
+            '+' => Some(Phase::Finished),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Phase::Copying => write!(f, "Copying"),
+            Phase::Trimming => write!(f, "Trimming"),
+            Phase::Scraping => write!(f, "Scraping"),
+            Phase::Retrying => write!(f, "Retrying"),
+            Phase::Finished => write!(f, "Finished"),
+        }
+    }
+}
+
+/// An area of the source and its state.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MapBlock {
+    /// The offset of the area in bytes.
+    pub pos: u64,
+    /// The size of the area in bytes.
+    pub size: u64,
+    /// The state of the area.
+    pub status: BlockStatus,
+}
+
+impl MapBlock {
+    /// Returns the offset just past the end of the area.
+    pub fn end(&self) -> u64 {
+        self.pos + self.size
+    }
+}
+
+/// The state of a rescue, kept in a map file compatible with ddrescue's so it can be resumed.
+#[derive(Clone)]
+pub struct RescueMap {
+    /// The offset the rescue was last working at.
+    pub current_pos: u64,
+    /// The phase the rescue is in.
+    pub phase: Phase,
+    /// The pass within the phase: 1 or 2 while copying, the retry pass while retrying.
+    pub pass: u32,
+    /// The areas of the source, in order, covering it from start to end without gaps.
+    pub blocks: Vec<MapBlock>,
+    /// The source the map is of, as `SourceIdentity::label` describes it, saved in a comment so
+    /// that ddrescue can still read the map.
+    pub source: Option<String>,
+}
+
+/// The start of the comment a map's source is saved in.
+const SOURCE_COMMENT: &str = "# Source: ";
+
+/// Parse a number in a map file, which may be decimal or hexadecimal with a `0x` prefix.
+fn parse_number(value: &str) -> Option<u64> {
+    if value.starts_with("0x") || value.starts_with("0X") {
+        u64::from_str_radix(&value[2..], 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+impl RescueMap {
+    /// Returns the map for a rescue that hasn't started, of a source `size` bytes long.
+    pub fn new(size: u64) -> RescueMap {
+        let blocks = if size == 0 {
+            Vec::new()
+        } else {
+            vec![MapBlock {
+                pos: 0,
+                size,
+                status: BlockStatus::NonTried,
+            }]
+        };
+        RescueMap {
+            current_pos: 0,
+            phase: Phase::Copying,
+            pass: 1,
+            blocks,
+            source: None,
+        }
+    }
+
+    /// Parse a map file.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` with `ErrorKind::InvalidData` if the map is malformed or has gaps
+    /// - `Ok` with the map
+    pub fn parse(text: &str) -> io::Result<RescueMap> {
+        let invalid =
+            |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        let source = text
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(SOURCE_COMMENT))
+            .map(|source| source.trim().to_string());
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        let status_line = lines
+            .next()
+            .ok_or_else(|| invalid("The map file is empty"))?;
+        let mut fields = status_line.split_whitespace();
+        let current_pos = fields
+            .next()
+            .and_then(parse_number)
+            .ok_or_else(|| invalid("Invalid current position in the map file"))?;
+        let phase = fields
+            .next()
+            .and_then(|field| field.chars().next())
+            .and_then(Phase::from_char)
+            .ok_or_else(|| invalid("Invalid current status in the map file"))?;
+        let pass = fields.next().and_then(parse_number).unwrap_or(1) as u32;
+
+        let mut blocks: Vec<MapBlock> = Vec::new();
+        for line in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 3 {
+                return Err(invalid("Invalid block in the map file"));
+            }
+            let block = MapBlock {
+                pos: parse_number(fields[0]).ok_or_else(|| invalid("Invalid block position"))?,
+                size: parse_number(fields[1]).ok_or_else(|| invalid("Invalid block size"))?,
+                status: fields[2]
+                    .chars()
+                    .next()
+                    .and_then(BlockStatus::from_char)
+                    .ok_or_else(|| invalid("Invalid block status"))?,
+            };
+            let expected = blocks.last().map(MapBlock::end).unwrap_or(0);
+            if block.pos != expected {
+                return Err(invalid("The blocks in the map file are not contiguous"));
+            }
+            blocks.push(block);
+        }
+
+        Ok(RescueMap {
+            current_pos,
+            phase,
+            pass,
+            blocks,
+            source,
+        })
+    }
+
+    /// Load a map file.
+    pub fn load(path: &Path) -> io::Result<RescueMap> {
+        RescueMap::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Save the map, replacing the file atomically so a power cut can't leave it half written.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temporary = path.with_extension("map.tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(self.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary, path)
+    }
+
+    /// Returns the size of the source the map covers.
+    pub fn size(&self) -> u64 {
+        self.blocks.last().map(MapBlock::end).unwrap_or(0)
+    }
+
+    /// Returns the number of bytes with the given status.
+    pub fn bytes_with(&self, status: BlockStatus) -> u64 {
+        self.blocks
+            .iter()
+            .filter(|block| block.status == status)
+            .map(|block| block.size)
+            .sum()
+    }
+
+    /// Returns the areas with the given status, in order.
+    pub fn blocks_with(&self, status: BlockStatus) -> Vec<MapBlock> {
+        self.blocks
+            .iter()
+            .filter(|block| block.status == status)
+            .cloned()
+            .collect()
+    }
+
+    /// Set the status of an area, splitting and merging blocks as needed.
+    pub fn set(&mut self, pos: u64, size: u64, status: BlockStatus) {
+        let end = (pos + size).min(self.size());
+        if size == 0 || pos >= end {
+            return;
+        }
+
+        let first = self.blocks.partition_point(|block| block.end() <= pos);
+        let mut last = first;
+        while last < self.blocks.len() && self.blocks[last].pos < end {
+            last += 1;
+        }
+
+        let mut replacement = Vec::with_capacity(3);
+        let head = self.blocks[first];
+        if head.pos < pos {
+            replacement.push(MapBlock {
+                size: pos - head.pos,
+                ..head
+            });
+        }
+        replacement.push(MapBlock {
+            pos,
+            size: end - pos,
+            status,
+        });
+        let tail = self.blocks[last - 1];
+        if tail.end() > end {
+            replacement.push(MapBlock {
+                pos: end,
+                size: tail.end() - end,
+                status: tail.status,
+            });
+        }
+        let inserted = replacement.len();
+        self.blocks.splice(first..last, replacement);
+
+        // Merge the new blocks with their neighbours where the status matches
+        let mut index = first.saturating_sub(1);
+        let mut stop = (first + inserted + 1).min(self.blocks.len());
+        while index + 1 < stop {
+            if self.blocks[index].status == self.blocks[index + 1].status {
+                self.blocks[index].size += self.blocks[index + 1].size;
+                self.blocks.remove(index + 1);
+                stop -= 1;
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// Returns every sector that hasn't been read successfully. Once the rescue has finished,
+    /// these are the sectors that couldn't be read.
+    pub fn unread_sectors(&self, sector_size: u64) -> Vec<SectorRange> {
+        let sector_size = sector_size.max(1);
+        let mut ranges = Vec::new();
+        for block in self
+            .blocks
+            .iter()
+            .filter(|block| block.status != BlockStatus::Finished)
+        {
+            push_range(
+                &mut ranges,
+                block.pos / sector_size,
+                (block.end() - 1) / sector_size,
+            );
+        }
+        ranges
+    }
+}
+
+impl fmt::Display for RescueMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "# Rescue map file. Created by grim {}",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        if let Some(ref source) = self.source {
+            writeln!(f, "{}{}", SOURCE_COMMENT, source)?;
+        }
+        writeln!(f, "# current_pos  current_status  current_pass")?;
+        writeln!(
+            f,
+            "0x{:08X}     {}               {}",
+            self.current_pos,
+            self.phase.as_char(),
+            self.pass
+        )?;
+        writeln!(f, "#      pos        size  status")?;
+        for block in &self.blocks {
+            writeln!(
+                f,
+                "0x{:08X}  0x{:08X}  {}",
+                block.pos,
+                block.size,
+                block.status.as_char()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings for a rescue.
+#[derive(Clone, Copy)]
+pub struct RescueOptions {
+    /// The size of a sector in bytes; trimming, scraping and retrying read one sector at a time.
+    pub sector_size: u64,
+    /// How much to read at once while copying, in bytes.
+    pub cluster_size: u64,
+    /// How far to skip ahead after the first read error while copying, in bytes. The skip
+    /// doubles with every consecutive error.
+    pub skip_size: u64,
+    /// How many times to retry bad sectors once everything else has been read.
+    pub retry_passes: u32,
+    /// Whether the first copying pass, and every other retry pass, runs from the end of the
+    /// source to the start.
+    pub reverse: bool,
+}
+
+/// How often to save the map while a phase is running.
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The state shared by every phase of a rescue.
+struct Rescuer<'a, D: BlockDevice + ?Sized, F: FnMut(&RescueMap)> {
+    device: &'a D,
+    start: u64,
+    output: &'a File,
+    map: &'a mut RescueMap,
+    map_path: &'a Path,
+    options: RescueOptions,
+    progress: F,
+    abort: &'a AtomicBool,
+    last_save: Instant,
+    buffer: Vec<u8>,
+}
+
+impl<'a, D: BlockDevice + ?Sized, F: FnMut(&RescueMap)> Rescuer<'a, D, F> {
+    /// Save the map if it hasn't been saved for a while, and stop if the rescue was cancelled.
+    fn checkpoint(&mut self, pos: u64) -> io::Result<()> {
+        self.map.current_pos = pos;
+        (self.progress)(self.map);
+
+        if self.abort.load(Ordering::Relaxed) {
+            self.map.save(self.map_path)?;
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
+        if self.last_save.elapsed() >= SAVE_INTERVAL {
+            self.map.save(self.map_path)?;
+            self.last_save = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Move to a new phase and pass, saving the map.
+    fn begin(&mut self, phase: Phase, pass: u32) -> io::Result<()> {
+        self.map.phase = phase;
+        self.map.pass = pass;
+        self.map.save(self.map_path)?;
+        self.last_save = Instant::now();
+        Ok(())
+    }
+
+    /// Try to read an area once, writing it to the output and marking it finished on success.
+    ///
+    /// # Returns
+    ///
+    /// Whether the read succeeded.
+    fn try_read(&mut self, pos: u64, size: u64) -> io::Result<bool> {
+        let size = size as usize;
+        if self.buffer.len() < size {
+            self.buffer.resize(size, 0);
+        }
+
+        match self
+            .device
+            .read_at(&mut self.buffer[..size], self.start + pos)
+        {
+            Ok(read) => {
+                self.output.write_all_at(&self.buffer[..read], pos)?;
+                self.map.set(pos, read as u64, BlockStatus::Finished);
+                // Anything past a short read is past the end of the device
+                self.map.set(
+                    pos + read as u64,
+                    (size - read) as u64,
+                    BlockStatus::BadSector,
+                );
+                Ok(true)
+            }
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Read the areas that haven't been tried in clusters, in the given direction. When
+    /// `skipping`, an error skips ahead so that bad areas don't slow down the first pass.
+    fn copy(&mut self, reverse: bool, skipping: bool) -> io::Result<()> {
+        let cluster = self
+            .options
+            .cluster_size
+            .max(self.options.sector_size)
+            .max(1);
+        let mut blocks = self.map.blocks_with(BlockStatus::NonTried);
+        if reverse {
+            blocks.reverse();
+        }
+
+        for block in blocks {
+            let mut skip = 0;
+            let mut done = 0;
+            while done < block.size {
+                // Clusters are aligned to the start of the block in both directions, so reading
+                // backwards starts with whatever is left over at the end
+                let remaining = block.size - done;
+                let (pos, size) = if reverse {
+                    let size = match remaining % cluster {
+                        0 => cluster,
+                        partial => partial,
+                    };
+                    (block.pos + remaining - size, size)
+                } else {
+                    (block.pos + done, cluster.min(remaining))
+                };
+
+                if self.try_read(pos, size)? {
+                    skip = 0;
+                    done += size;
+                } else {
+                    self.map.set(pos, size, BlockStatus::NonTrimmed);
+                    done += size;
+                    if skipping {
+                        // Leave the skipped area untried for the next pass
+                        skip = if skip == 0 {
+                            self.options.skip_size
+                        } else {
+                            (skip * 2).min(block.size)
+                        };
+                        let skip = skip / cluster * cluster;
+                        done = (done + skip).min(block.size);
+                    }
+                }
+                self.checkpoint(pos)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the edges of each failed cluster sector by sector, from the start forwards and the
+    /// end backwards, until a sector fails. Whatever is left between the failures is scraped.
+    fn trim(&mut self) -> io::Result<()> {
+        let sector = self.options.sector_size.max(1);
+
+        for block in self.map.blocks_with(BlockStatus::NonTrimmed) {
+            let mut low = block.pos;
+            let mut high = block.end();
+
+            while low < high {
+                let size = sector.min(high - low);
+                let ok = self.try_read(low, size)?;
+                self.checkpoint(low)?;
+                if !ok {
+                    self.map.set(low, size, BlockStatus::BadSector);
+                    low += size;
+                    break;
+                }
+                low += size;
+            }
+
+            while low < high {
+                // Sectors are counted from the start of the block, so the last may be short
+                let size = match (high - block.pos) % sector {
+                    0 => sector,
+                    partial => partial,
+                };
+                let pos = high - size;
+                let ok = self.try_read(pos, size)?;
+                self.checkpoint(pos)?;
+                if !ok {
+                    self.map.set(pos, size, BlockStatus::BadSector);
+                    high = pos;
+                    break;
+                }
+                high = pos;
+            }
+
+            self.map
+                .set(low, high.saturating_sub(low), BlockStatus::NonScraped);
+        }
+        Ok(())
+    }
+
+    /// Read every sector in the areas with `status` one at a time, in the given direction,
+    /// marking the ones that fail as bad.
+    fn sector_by_sector(&mut self, status: BlockStatus, reverse: bool) -> io::Result<()> {
+        let sector = self.options.sector_size.max(1);
+        let mut blocks = self.map.blocks_with(status);
+        if reverse {
+            blocks.reverse();
+        }
+
+        for block in blocks {
+            let mut sectors: Vec<u64> = (0..block.size.div_ceil(sector))
+                .map(|index| block.pos + index * sector)
+                .collect();
+            if reverse {
+                sectors.reverse();
+            }
+
+            for pos in sectors {
+                let size = sector.min(block.end() - pos);
+                if !self.try_read(pos, size)? {
+                    self.map.set(pos, size, BlockStatus::BadSector);
+                }
+                self.checkpoint(pos)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rescue a failing source into `output` in several passes, as ddrescue does:
+///
+/// 1. Copy the areas that haven't been tried in clusters, skipping ahead after errors.
+/// 2. Copy the skipped areas in the other direction, without skipping.
+/// 3. Trim the edges of the failed clusters sector by sector.
+/// 4. Scrape what's left of the failed clusters sector by sector.
+/// 5. Retry the bad sectors `options.retry_passes` times.
+///
+/// The map is saved to `map_path` regularly, between phases, and when the rescue is cancelled,
+/// so a rescue can be resumed by passing the loaded map back in. Phases that the map says have
+/// already finished are skipped.
+///
+/// # Arguments
+///
+/// * `device` - The source to rescue.
+/// * `start` - The offset in the source to start at, such as the start of a partition.
+/// * `output` - Where to write the rescued data; the area at offset `pos` in the map is written at `pos`.
+/// * `map` - The state of the rescue, which is updated as it runs.
+/// * `map_path` - Where to save the map.
+/// * `options` - How to read the source.
+/// * `progress` - Called with the map after every read.
+/// * `abort` - When set, the map is saved and `ErrorKind::Interrupted` is returned.
+#[allow(clippy::too_many_arguments)]
+pub fn rescue<D, F>(
+    device: &D,
+    start: u64,
+    output: &File,
+    map: &mut RescueMap,
+    map_path: &Path,
+    options: &RescueOptions,
+    progress: F,
+    abort: &AtomicBool,
+) -> io::Result<()>
+where
+    D: BlockDevice + ?Sized,
+    F: FnMut(&RescueMap),
+{
+    let mut rescuer = Rescuer {
+        device,
+        start,
+        output,
+        map,
+        map_path,
+        options: *options,
+        progress,
+        abort,
+        last_save: Instant::now(),
+        buffer: Vec::new(),
+    };
+    let reverse = options.reverse;
+
+    if rescuer.map.phase == Phase::Copying {
+        if rescuer.map.pass <= 1 {
+            rescuer.begin(Phase::Copying, 1)?;
+            rescuer.copy(reverse, true)?;
+        }
+        rescuer.begin(Phase::Copying, 2)?;
+        rescuer.copy(!reverse, false)?;
+        rescuer.begin(Phase::Trimming, 1)?;
+    }
+
+    if rescuer.map.phase == Phase::Trimming {
+        rescuer.trim()?;
+        rescuer.begin(Phase::Scraping, 1)?;
+    }
+
+    if rescuer.map.phase == Phase::Scraping {
+        rescuer.sector_by_sector(BlockStatus::NonScraped, false)?;
+        rescuer.begin(Phase::Retrying, 1)?;
+    }
+
+    if rescuer.map.phase == Phase::Retrying {
+        let first = rescuer.map.pass.max(1);
+        for pass in first..=options.retry_passes {
+            rescuer.begin(Phase::Retrying, pass)?;
+            rescuer.sector_by_sector(BlockStatus::BadSector, reverse && pass % 2 == 1)?;
+        }
+        rescuer.begin(Phase::Finished, 1)?;
+    }
+
+    Ok(())
+}
+
+/// Open the file a rescue writes to, creating it at `size` bytes if it doesn't exist. An
+/// existing file is reused, so a rescue can be resumed; a new rescue must check that it doesn't
+/// exist first, as `acquire::rescue` does.
+pub fn open_output(path: &Path, size: u64) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    if file.metadata()?.len() < size {
+        file.set_len(size)?;
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::FaultyDevice;
+    use crate::test_util::{faulty_device, TempDir};
+    use std::path::PathBuf;
+
+    const SECTOR: u64 = 512;
+    const SECTORS: u64 = 64;
+
+    /// The temporary files of a test rescue: a source of `SECTORS` sectors, each filled with its
+    /// sector number plus one, and the rescued copy and map. They are removed when dropped.
+    struct Files {
+        _dir: TempDir,
+        source: PathBuf,
+        output: PathBuf,
+        map: PathBuf,
+    }
+
+    impl Files {
+        fn new(name: &str) -> Files {
+            let dir = TempDir::new(&format!("rescue-{}", name));
+            Files {
+                source: dir.numbered_sectors("rescue.src", SECTORS, SECTOR),
+                output: dir.join("rescue.out"),
+                map: dir.join("rescue.map"),
+                _dir: dir,
+            }
+        }
+
+        fn device(&self, bad_sectors: &[(u64, u64)]) -> FaultyDevice {
+            faulty_device(&self.source, SECTOR, bad_sectors)
+        }
+
+        /// Returns the value each sector of the rescued copy is filled with.
+        fn rescued(&self) -> Vec<u8> {
+            fs::read(&self.output)
+                .unwrap()
+                .chunks(SECTOR as usize)
+                .map(|sector| sector[0])
+                .collect()
+        }
+    }
+
+    fn options(reverse: bool, retry_passes: u32) -> RescueOptions {
+        RescueOptions {
+            sector_size: SECTOR,
+            cluster_size: 8 * SECTOR,
+            skip_size: 8 * SECTOR,
+            retry_passes,
+            reverse,
+        }
+    }
+
+    /// Returns the ranges as pairs, for comparing.
+    fn pairs(ranges: &[SectorRange]) -> Vec<(u64, u64)> {
+        ranges
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    /// Returns the source data with the given sectors zeroed.
+    fn expected(bad: &[u64]) -> Vec<u8> {
+        (0..SECTORS)
+            .map(|sector| {
+                if bad.contains(&sector) {
+                    0
+                } else {
+                    (sector + 1) as u8
+                }
+            })
+            .collect()
+    }
+
+    /// Run a whole rescue, returning the map, the phases it went through, and the number of
+    /// reads it made.
+    fn run(
+        files: &Files,
+        map: &mut RescueMap,
+        bad: &[(u64, u64)],
+        options: &RescueOptions,
+    ) -> (Vec<(Phase, u32)>, u64) {
+        let device = files.device(bad);
+        let output = open_output(&files.output, SECTORS * SECTOR).unwrap();
+        let mut phases: Vec<(Phase, u32)> = Vec::new();
+        rescue(
+            &device,
+            0,
+            &output,
+            map,
+            &files.map,
+            options,
+            |map| {
+                if phases.last() != Some(&(map.phase, map.pass)) {
+                    phases.push((map.phase, map.pass));
+                }
+            },
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        (phases, device.reads())
+    }
+
+    #[test]
+    fn set_splits_and_merges_blocks() {
+        let mut map = RescueMap::new(1000);
+        map.set(100, 100, BlockStatus::Finished);
+        assert_eq!(map.blocks.len(), 3);
+        assert_eq!(map.blocks[1].pos, 100);
+        assert_eq!(map.blocks[1].size, 100);
+
+        // Adjacent blocks with the same status merge
+        map.set(200, 50, BlockStatus::Finished);
+        assert_eq!(map.blocks.len(), 3);
+        assert_eq!(map.blocks[1].size, 150);
+
+        // Setting the middle of a block splits it in three
+        map.set(120, 10, BlockStatus::BadSector);
+        let statuses: Vec<(u64, u64, BlockStatus)> = map
+            .blocks
+            .iter()
+            .map(|block| (block.pos, block.size, block.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (0, 100, BlockStatus::NonTried),
+                (100, 20, BlockStatus::Finished),
+                (120, 10, BlockStatus::BadSector),
+                (130, 120, BlockStatus::Finished),
+                (250, 750, BlockStatus::NonTried),
+            ]
+        );
+
+        // Anything past the end of the source is ignored
+        map.set(0, 2000, BlockStatus::Finished);
+        assert_eq!(
+            map.blocks,
+            vec![MapBlock {
+                pos: 0,
+                size: 1000,
+                status: BlockStatus::Finished
+            }]
+        );
+        assert_eq!(map.size(), 1000);
+        assert_eq!(map.bytes_with(BlockStatus::Finished), 1000);
+    }
+
+    #[test]
+    fn parses_ddrescue_map_files() {
+        let map = RescueMap::parse(
+            "# Mapfile. Created by GNU ddrescue version 1.23\n\
+             # current_pos  current_status  current_pass\n\
+             0x00001000     *               1\n\
+             #      pos        size  status\n\
+             0x00000000  0x00001000  +\n\
+             4096  512  -\n\
+             0x00001200  0x00000E00  ?\n",
+        )
+        .unwrap();
+        assert_eq!(map.current_pos, 0x1000);
+        assert_eq!(map.phase, Phase::Trimming);
+        assert_eq!(map.pass, 1);
+        assert_eq!(map.size(), 0x2000);
+        assert_eq!(map.blocks[1].status, BlockStatus::BadSector);
+        assert_eq!(map.bytes_with(BlockStatus::NonTried), 0xE00);
+        assert_eq!(map.source, None);
+
+        assert!(RescueMap::parse("").is_err());
+        assert!(RescueMap::parse("0 ? 1\n0 512 +\n1024 512 +\n").is_err());
+        assert!(RescueMap::parse("0 ? 1\n0 512 x\n").is_err());
+    }
+
+    #[test]
+    fn saves_and_loads_maps() {
+        let files = Files::new("save");
+        let mut map = RescueMap::new(SECTORS * SECTOR);
+        map.set(0, 8 * SECTOR, BlockStatus::Finished);
+        map.set(8 * SECTOR, SECTOR, BlockStatus::NonScraped);
+        map.current_pos = 8 * SECTOR;
+        map.phase = Phase::Scraping;
+        map.source = Some(String::from("serial WD-123, 32768 bytes"));
+        map.save(&files.map).unwrap();
+
+        let loaded = RescueMap::load(&files.map).unwrap();
+        assert_eq!(loaded.current_pos, map.current_pos);
+        assert_eq!(loaded.phase, Phase::Scraping);
+        assert_eq!(loaded.pass, 1);
+        assert_eq!(loaded.blocks, map.blocks);
+        assert_eq!(loaded.source, map.source);
+        assert!(!files.map.with_extension("map.tmp").exists());
+    }
+
+    #[test]
+    fn lists_unread_sectors() {
+        let mut map = RescueMap::new(16 * SECTOR);
+        map.set(0, 16 * SECTOR, BlockStatus::Finished);
+        map.set(2 * SECTOR, SECTOR, BlockStatus::BadSector);
+        map.set(3 * SECTOR, 2 * SECTOR, BlockStatus::NonScraped);
+        map.set(10 * SECTOR, 100, BlockStatus::BadSector);
+        assert_eq!(pairs(&map.unread_sectors(SECTOR)), vec![(2, 4), (10, 10)]);
+        assert!(RescueMap::new(0).unread_sectors(SECTOR).is_empty());
+    }
+
+    #[test]
+    fn rescues_everything_but_the_bad_sectors() {
+        let files = Files::new("forward");
+        let bad = [(10, 12), (40, 40)];
+        let mut map = RescueMap::new(SECTORS * SECTOR);
+        let (phases, _) = run(&files, &mut map, &bad, &options(false, 0));
+
+        assert_eq!(
+            phases,
+            vec![
+                (Phase::Copying, 1),
+                (Phase::Copying, 2),
+                (Phase::Trimming, 1),
+                (Phase::Scraping, 1),
+            ]
+        );
+        assert_eq!(map.phase, Phase::Finished);
+        assert_eq!(pairs(&map.unread_sectors(SECTOR)), vec![(10, 12), (40, 40)]);
+        assert_eq!(
+            map.bytes_with(BlockStatus::Finished),
+            (SECTORS - 4) * SECTOR
+        );
+        assert_eq!(files.rescued(), expected(&[10, 11, 12, 40]));
+
+        let saved = RescueMap::load(&files.map).unwrap();
+        assert_eq!(saved.phase, Phase::Finished);
+        assert_eq!(saved.blocks, map.blocks);
+    }
+
+    #[test]
+    fn rescues_in_reverse() {
+        let files = Files::new("reverse");
+        let bad = [(10, 12), (40, 40), (63, 63)];
+        let mut map = RescueMap::new(SECTORS * SECTOR);
+        run(&files, &mut map, &bad, &options(true, 0));
+
+        assert_eq!(map.phase, Phase::Finished);
+        assert_eq!(
+            pairs(&map.unread_sectors(SECTOR)),
+            vec![(10, 12), (40, 40), (63, 63)]
+        );
+        assert_eq!(files.rescued(), expected(&[10, 11, 12, 40, 63]));
+    }
+
+    #[test]
+    fn skips_ahead_after_errors_on_the_first_pass() {
+        let files = Files::new("skip");
+        let device = files.device(&[(8, 8)]);
+        let output = open_output(&files.output, SECTORS * SECTOR).unwrap();
+        let mut map = RescueMap::new(SECTORS * SECTOR);
+        let mut first_pass = None;
+        rescue(
+            &device,
+            0,
+            &output,
+            &mut map,
+            &files.map,
+            &options(false, 0),
+            |map| {
+                if map.phase == Phase::Copying && map.pass == 1 {
+                    first_pass = Some(map.bytes_with(BlockStatus::NonTried));
+                }
+            },
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        // The cluster after the failed one was left for the second pass
+        assert_eq!(first_pass, Some(8 * SECTOR));
+        assert_eq!(pairs(&map.unread_sectors(SECTOR)), vec![(8, 8)]);
+    }
+
+    #[test]
+    fn retries_bad_sectors() {
+        let bad = [(10, 12), (40, 40)];
+
+        let files = Files::new("no-retries");
+        let mut map = RescueMap::new(SECTORS * SECTOR);
+        let (_, reads) = run(&files, &mut map, &bad, &options(false, 0));
+
+        let files = Files::new("retries");
+        let mut retried = RescueMap::new(SECTORS * SECTOR);
+        let (phases, retried_reads) = run(&files, &mut retried, &bad, &options(false, 2));
+
+        assert!(phases.contains(&(Phase::Retrying, 1)));
+        assert!(phases.contains(&(Phase::Retrying, 2)));
+        assert_eq!(retried_reads, reads + 2 * 4);
+        assert_eq!(retried.blocks, map.blocks);
+    }
+
+    #[test]
+    fn resumes_from_a_saved_map() {
+        let bad = [(10, 12), (40, 40)];
+
+        let files = Files::new("whole");
+        let mut map = RescueMap::new(SECTORS * SECTOR);
+        let (_, uninterrupted_reads) = run(&files, &mut map, &bad, &options(false, 1));
+
+        // Stop the rescue part of the way through the trimming phase
+        let files = Files::new("resume");
+        let device = files.device(&bad);
+        let output = open_output(&files.output, SECTORS * SECTOR).unwrap();
+        let abort = AtomicBool::new(false);
+        let mut interrupted = RescueMap::new(SECTORS * SECTOR);
+        let error = rescue(
+            &device,
+            0,
+            &output,
+            &mut interrupted,
+            &files.map,
+            &options(false, 1),
+            |map| {
+                if map.phase == Phase::Trimming {
+                    abort.store(true, Ordering::Relaxed);
+                }
+            },
+            &abort,
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+        let first_reads = device.reads();
+        drop(output);
+
+        let mut resumed = RescueMap::load(&files.map).unwrap();
+        assert_eq!(resumed.phase, Phase::Trimming);
+        let (phases, resumed_reads) = run(&files, &mut resumed, &bad, &options(false, 1));
+
+        assert_eq!(phases.first(), Some(&(Phase::Trimming, 1)));
+        assert_eq!(resumed.blocks, map.blocks);
+        assert_eq!(files.rescued(), expected(&[10, 11, 12, 40]));
+        // Nothing copied before the interruption is read again, apart from the read that was
+        // interrupted
+        assert!(first_reads + resumed_reads <= uninterrupted_reads + 1);
+    }
+}
@@ -0,0 +1,332 @@
+use chrono::{DateTime, Local};
+use json;
+use std::io;
+use std::process::Command;
+
+/// A SMART attribute of an ATA drive.
+#[derive(Clone)]
+pub struct SmartAttribute {
+    /// The attribute ID.
+    pub id: u64,
+    /// The attribute name, as smartctl names it.
+    pub name: String,
+    /// The normalized value.
+    pub value: u64,
+    /// The worst normalized value seen.
+    pub worst: u64,
+    /// The threshold at or below which the attribute is failing.
+    pub threshold: u64,
+    /// The raw value, as smartctl formats it.
+    pub raw: String,
+}
+
+/// The SMART data read from a drive before it is imaged.
+#[derive(Clone)]
+pub struct SmartSnapshot {
+    /// When the data was read.
+    pub taken: DateTime<Local>,
+    /// Whether the drive passed its overall health self-assessment, if it reported one.
+    pub passed: Option<bool>,
+    /// The number of hours the drive has been powered on.
+    pub power_on_hours: Option<u64>,
+    /// The number of reallocated sectors.
+    pub reallocated_sectors: Option<u64>,
+    /// The number of sectors waiting to be reallocated.
+    pub pending_sectors: Option<u64>,
+    /// The number of sectors that could not be read during offline scans.
+    pub uncorrectable_sectors: Option<u64>,
+    /// The number of unrecovered media errors on an NVMe drive.
+    pub media_errors: Option<u64>,
+    /// The current temperature in degrees Celsius.
+    pub temperature: Option<u64>,
+    /// Every ATA SMART attribute, in the order smartctl listed them.
+    pub attributes: Vec<SmartAttribute>,
+}
+
+impl SmartSnapshot {
+    /// Returns the reasons the drive looks unhealthy, or an empty list if it looks healthy.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.passed == Some(false) {
+            warnings.push(String::from(
+                "The drive FAILED its SMART health self-assessment",
+            ));
+        }
+
+        let counts = [
+            (self.reallocated_sectors, "reallocated sectors"),
+            (self.pending_sectors, "sectors pending reallocation"),
+            (self.uncorrectable_sectors, "uncorrectable sectors"),
+            (self.media_errors, "media errors"),
+        ];
+        for (count, description) in counts.iter() {
+            if let Some(count) = count {
+                if *count > 0 {
+                    warnings.push(format!("The drive has {} {}", count, description));
+                }
+            }
+        }
+
+        for attribute in &self.attributes {
+            if attribute.threshold > 0 && attribute.value <= attribute.threshold {
+                warnings.push(format!(
+                    "Attribute {} ({}) is at or below its failure threshold",
+                    attribute.id, attribute.name
+                ));
+            }
+        }
+
+        warnings
+    }
+
+    /// Returns true if the drive looks unhealthy enough that it should be imaged carefully.
+    pub fn looks_unhealthy(&self) -> bool {
+        !self.warnings().is_empty()
+    }
+
+    /// Returns the overall health for display and the report.
+    pub fn health(&self) -> &'static str {
+        match self.passed {
+            Some(true) => "PASSED",
+            Some(false) => "FAILED",
+            None => "unknown",
+        }
+    }
+}
+
+/// Parse the output of `smartctl --json -a`.
+///
+/// # Returns
+///
+/// - `Err` if the output is not valid JSON
+/// - `Ok(None)` if smartctl did not report any SMART data for the drive
+/// - `Ok(Some)` with the parsed snapshot
+pub fn parse_smartctl_json(output: &str) -> io::Result<Option<SmartSnapshot>> {
+    let parsed = json::parse(output).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Could not parse output of smartctl: {}", e),
+        )
+    })?;
+
+    let mut attributes = Vec::new();
+    for attribute in parsed["ata_smart_attributes"]["table"].members() {
+        attributes.push(SmartAttribute {
+            id: attribute["id"].as_u64().unwrap_or_default(),
+            name: attribute["name"].as_str().unwrap_or_default().to_string(),
+            value: attribute["value"].as_u64().unwrap_or_default(),
+            worst: attribute["worst"].as_u64().unwrap_or_default(),
+            threshold: attribute["thresh"].as_u64().unwrap_or_default(),
+            raw: match attribute["raw"]["string"].as_str() {
+                Some(raw) => raw.to_string(),
+                None => attribute["raw"]["value"].dump(),
+            },
+        });
+    }
+    let raw = |id| {
+        parsed["ata_smart_attributes"]["table"]
+            .members()
+            .find(|attribute| attribute["id"].as_u64() == Some(id))
+            .and_then(|attribute| attribute["raw"]["value"].as_u64())
+    };
+
+    let nvme = &parsed["nvme_smart_health_information_log"];
+    let passed = parsed["smart_status"]["passed"].as_bool();
+    if passed.is_none() && attributes.is_empty() && nvme.is_null() {
+        return Ok(None);
+    }
+
+    Ok(Some(SmartSnapshot {
+        taken: Local::now(),
+        passed,
+        power_on_hours: parsed["power_on_time"]["hours"]
+            .as_u64()
+            .or_else(|| nvme["power_on_hours"].as_u64()),
+        reallocated_sectors: raw(5),
+        pending_sectors: raw(197),
+        uncorrectable_sectors: raw(198),
+        media_errors: nvme["media_errors"].as_u64(),
+        temperature: parsed["temperature"]["current"].as_u64(),
+        attributes,
+    }))
+}
+
+/// Read the SMART data of a drive with smartctl.
+///
+/// smartctl's exit status is a bit mask that is non-zero for many healthy drives, so it is
+/// ignored; the JSON output says whether SMART data was available.
+///
+/// # Returns
+///
+/// - `Err` if smartctl could not be run or its output could not be parsed
+/// - `Ok(None)` if the drive did not report any SMART data
+/// - `Ok(Some)` with the snapshot
+pub fn read_smart(device: &str) -> io::Result<Option<SmartSnapshot>> {
+    let output = Command::new("smartctl")
+        .arg("--json")
+        .arg("-a")
+        .arg(device)
+        .output()?;
+    parse_smartctl_json(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trimmed from `smartctl --json -a` on a SATA drive with remapped and pending sectors.
+    const ATA: &str = r#"{
+  "json_format_version": [1, 0],
+  "smartctl": {
+    "version": [7, 1],
+    "argv": ["smartctl", "--json", "-a", "/dev/sda"],
+    "exit_status": 192
+  },
+  "device": {"name": "/dev/sda", "info_name": "/dev/sda [SAT]", "type": "sat", "protocol": "ATA"},
+  "model_name": "WDC WD5000AAKX-08U6AA0",
+  "serial_number": "WD-WCC2EXXXXXXX",
+  "smart_status": {"passed": true},
+  "ata_smart_attributes": {
+    "revision": 16,
+    "table": [
+      {"id": 1, "name": "Raw_Read_Error_Rate", "value": 200, "worst": 200, "thresh": 51,
+       "when_failed": "", "raw": {"value": 14, "string": "14"}},
+      {"id": 5, "name": "Reallocated_Sector_Ct", "value": 198, "worst": 198, "thresh": 140,
+       "when_failed": "", "raw": {"value": 8, "string": "8"}},
+      {"id": 9, "name": "Power_On_Hours", "value": 54, "worst": 54, "thresh": 0,
+       "when_failed": "", "raw": {"value": 33985, "string": "33985"}},
+      {"id": 194, "name": "Temperature_Celsius", "value": 108, "worst": 95, "thresh": 0,
+       "when_failed": "", "raw": {"value": 35, "string": "35"}},
+      {"id": 197, "name": "Current_Pending_Sector", "value": 200, "worst": 200, "thresh": 0,
+       "when_failed": "", "raw": {"value": 2, "string": "2"}},
+      {"id": 198, "name": "Offline_Uncorrectable", "value": 200, "worst": 200, "thresh": 0,
+       "when_failed": "", "raw": {"value": 0, "string": "0"}}
+    ]
+  },
+  "power_on_time": {"hours": 33985},
+  "power_cycle_count": 2210,
+  "temperature": {"current": 35}
+}"#;
+
+    /// Trimmed from `smartctl --json -a` on a healthy NVMe drive.
+    const NVME: &str = r#"{
+  "json_format_version": [1, 0],
+  "smartctl": {
+    "version": [7, 1],
+    "argv": ["smartctl", "--json", "-a", "/dev/nvme0"],
+    "exit_status": 0
+  },
+  "device": {"name": "/dev/nvme0", "info_name": "/dev/nvme0", "type": "nvme", "protocol": "NVMe"},
+  "model_name": "Samsung SSD 970 EVO Plus 500GB",
+  "serial_number": "S4EVNX0XXXXXXXX",
+  "smart_status": {"passed": true, "nvme": {"value": 0}},
+  "nvme_smart_health_information_log": {
+    "critical_warning": 0,
+    "temperature": 38,
+    "available_spare": 100,
+    "available_spare_threshold": 10,
+    "percentage_used": 2,
+    "power_cycles": 1187,
+    "power_on_hours": 4023,
+    "unsafe_shutdowns": 61,
+    "media_errors": 0,
+    "num_err_log_entries": 1534
+  },
+  "temperature": {"current": 38},
+  "power_cycle_count": 1187,
+  "power_on_time": {"hours": 4023}
+}"#;
+
+    /// `smartctl --json -a` on a USB enclosure whose bridge smartctl doesn't know.
+    const NO_SMART: &str = r#"{
+  "json_format_version": [1, 0],
+  "smartctl": {
+    "version": [7, 1],
+    "argv": ["smartctl", "--json", "-a", "/dev/sdb"],
+    "messages": [
+      {"string": "/dev/sdb: Unknown USB bridge [0x152d:0x0578 (0x508)]", "severity": "error"},
+      {"string": "Please specify device type with the -d option.", "severity": "information"}
+    ],
+    "exit_status": 1
+  }
+}"#;
+
+    #[test]
+    fn parses_ata_output() {
+        let snapshot = parse_smartctl_json(ATA).unwrap().unwrap();
+        assert_eq!(snapshot.passed, Some(true));
+        assert_eq!(snapshot.health(), "PASSED");
+        assert_eq!(snapshot.power_on_hours, Some(33985));
+        assert_eq!(snapshot.reallocated_sectors, Some(8));
+        assert_eq!(snapshot.pending_sectors, Some(2));
+        assert_eq!(snapshot.uncorrectable_sectors, Some(0));
+        assert_eq!(snapshot.media_errors, None);
+        assert_eq!(snapshot.temperature, Some(35));
+        assert_eq!(snapshot.attributes.len(), 6);
+        assert_eq!(snapshot.attributes[1].name, "Reallocated_Sector_Ct");
+        assert_eq!(snapshot.attributes[1].raw, "8");
+
+        assert_eq!(
+            snapshot.warnings(),
+            vec![
+                String::from("The drive has 8 reallocated sectors"),
+                String::from("The drive has 2 sectors pending reallocation"),
+            ]
+        );
+        assert!(snapshot.looks_unhealthy());
+    }
+
+    #[test]
+    fn warns_about_failed_health_and_attributes_at_threshold() {
+        let failing = ATA
+            .replace(r#""passed": true"#, r#""passed": false"#)
+            .replace(
+                r#""value": 198, "worst": 198"#,
+                r#""value": 140, "worst": 140"#,
+            );
+        let snapshot = parse_smartctl_json(&failing).unwrap().unwrap();
+        assert_eq!(snapshot.health(), "FAILED");
+
+        let warnings = snapshot.warnings();
+        assert_eq!(
+            warnings[0],
+            "The drive FAILED its SMART health self-assessment"
+        );
+        assert!(warnings.contains(&String::from(
+            "Attribute 5 (Reallocated_Sector_Ct) is at or below its failure threshold"
+        )));
+    }
+
+    #[test]
+    fn parses_nvme_output() {
+        let snapshot = parse_smartctl_json(NVME).unwrap().unwrap();
+        assert_eq!(snapshot.passed, Some(true));
+        assert_eq!(snapshot.power_on_hours, Some(4023));
+        assert_eq!(snapshot.media_errors, Some(0));
+        assert_eq!(snapshot.reallocated_sectors, None);
+        assert_eq!(snapshot.pending_sectors, None);
+        assert_eq!(snapshot.temperature, Some(38));
+        assert!(snapshot.attributes.is_empty());
+        assert!(snapshot.warnings().is_empty());
+        assert!(!snapshot.looks_unhealthy());
+
+        let snapshot =
+            parse_smartctl_json(&NVME.replace(r#""media_errors": 0"#, r#""media_errors": 3"#))
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            snapshot.warnings(),
+            vec![String::from("The drive has 3 media errors")]
+        );
+    }
+
+    #[test]
+    fn drives_without_smart_have_no_snapshot() {
+        assert!(parse_smartctl_json(NO_SMART).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_output_that_is_not_json() {
+        assert!(parse_smartctl_json("smartctl: unrecognized option '--json'").is_err());
+    }
+}
@@ -1,7 +1,12 @@
 use json;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom};
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
 use std::process::Command;
 
+use crate::ewfargs::MediaType;
+
 /// Hold information about a hard drive obtained from lshw.
 pub struct HdInfo {
     /// The product string of the hard drive, usually the human-readable product name.
@@ -15,6 +20,10 @@ pub struct HdInfo {
     pub units: String,
     /// The serial number of the drive.
     pub serial: String,
+    /// The size of a logical sector in bytes, if it is known.
+    pub sector_size: Option<u64>,
+    /// Whether this is a physical drive, rather than a file, partition or virtual device.
+    pub physical: bool,
 }
 
 /// Attempts to get the serial number of the machine running the application.
@@ -95,41 +104,36 @@ fn parse_children(children: &[json::JsonValue], list: &mut Vec<HdInfo>) {
         }
 
         if child["class"] == "disk" && child["id"] == "disk" {
-            let product: String;
-            let logical_name: String;
-            let size: f64;
-            let units: String;
-            let serial: String;
-
-            if child["product"].is_null() || !child["product"].is_string() {
-                product = String::from("");
-            } else {
-                product = String::from(child["product"].as_str().unwrap());
-            }
-
-            if child["logicalname"].is_null() || !child["logicalname"].is_string() {
-                logical_name = String::from("");
+            let product = if child["product"].is_null() || !child["product"].is_string() {
+                String::from("")
             } else {
-                logical_name = String::from(child["logicalname"].as_str().unwrap());
-            }
-
-            if child["size"].is_null() || !child["size"].is_number() {
-                size = 0_f64;
+                String::from(child["product"].as_str().unwrap())
+            };
+
+            let logical_name =
+                if child["logicalname"].is_null() || !child["logicalname"].is_string() {
+                    String::from("")
+                } else {
+                    String::from(child["logicalname"].as_str().unwrap())
+                };
+
+            let size = if child["size"].is_null() || !child["size"].is_number() {
+                0_f64
             } else {
-                size = child["size"].as_f64().expect("Invalid size from lshw");
-            }
+                child["size"].as_f64().expect("Invalid size from lshw")
+            };
 
-            if child["units"].is_null() || !child["units"].is_string() {
-                units = String::from("");
+            let units = if child["units"].is_null() || !child["units"].is_string() {
+                String::from("")
             } else {
-                units = String::from(child["units"].as_str().unwrap());
-            }
+                String::from(child["units"].as_str().unwrap())
+            };
 
-            if child["serial"].is_null() || !child["serial"].is_string() {
-                serial = String::from("");
+            let serial = if child["serial"].is_null() || !child["serial"].is_string() {
+                String::from("")
             } else {
-                serial = String::from(child["serial"].as_str().unwrap());
-            }
+                String::from(child["serial"].as_str().unwrap())
+            };
 
             list.push(HdInfo {
                 product: product.clone(),
@@ -137,7 +141,114 @@ fn parse_children(children: &[json::JsonValue], list: &mut Vec<HdInfo>) {
                 size,
                 units: units.clone(),
                 serial: serial.clone(),
+                sector_size: block_device_sector_size(&logical_name),
+                physical: true,
             })
         }
     }
 }
+
+/// Read an attribute of a block device from sysfs, such as `queue/logical_block_size`.
+///
+/// # Arguments
+/// - name: The kernel name of the device, such as `sda` or `loop0`
+/// - attribute: The path of the attribute under `/sys/class/block/<name>`
+fn sysfs_attribute(name: &str, attribute: &str) -> Option<String> {
+    fs::read_to_string(Path::new("/sys/class/block").join(name).join(attribute))
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Returns the kernel name of a block device, such as `sda1` for `/dev/sda1`, following symlinks
+/// such as `/dev/mapper/vg-root`.
+fn block_device_name(device: &str) -> Option<String> {
+    let device = fs::canonicalize(device).ok()?;
+    device
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Returns the logical sector size of a block device, as reported by sysfs. Partitions don't have
+/// their own queue, so their parent's is used.
+pub fn block_device_sector_size(device: &str) -> Option<u64> {
+    let name = block_device_name(device)?;
+    sysfs_attribute(&name, "queue/logical_block_size")
+        .or_else(|| sysfs_attribute(&name, "../queue/logical_block_size"))
+        .and_then(|size| size.parse().ok())
+}
+
+/// Suggest the media type of a block device from sysfs: optical drives by their SCSI device type,
+/// memory cards by their MMC transport, and removable media by the removable flag or a USB
+/// transport. Partitions are judged by the drive they are on.
+///
+/// # Returns
+///
+/// The suggested media type, which is `MediaType::Fixed` for anything that isn't recognized,
+/// including regular files.
+pub fn suggest_media_type(device: &str) -> MediaType {
+    let name = match block_device_name(device) {
+        Some(name) => name,
+        None => return MediaType::Fixed,
+    };
+    // Partitions don't have their own removable flag or device, so use their parent's
+    let attribute = |attribute: &str| {
+        sysfs_attribute(&name, attribute)
+            .or_else(|| sysfs_attribute(&name, &format!("../{}", attribute)))
+    };
+    // The transport shows in the path of the device, such as .../usb2/2-1/... or .../mmc0/...
+    let path = fs::canonicalize(Path::new("/sys/class/block").join(&name))
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    if name.starts_with("sr") || attribute("device/type").as_deref() == Some("5") {
+        MediaType::Optical
+    } else if name.starts_with("mmcblk") || path.contains("/mmc") {
+        MediaType::Memory
+    } else if attribute("removable").as_deref() == Some("1") || path.contains("/usb") {
+        MediaType::Removable
+    } else {
+        MediaType::Fixed
+    }
+}
+
+/// Describe an arbitrary block device or regular file that can be used as a source, such as a
+/// loop device, an md or LVM volume, a partition or an image file.
+///
+/// # Returns
+/// - `Err` if the path can't be opened, or is neither a block device nor a regular file
+/// - `Ok` with the size and sector size of the source; `physical` is always false
+pub fn describe_path(path: &str) -> io::Result<HdInfo> {
+    let file_type = fs::metadata(path)?.file_type();
+    let is_block_device = file_type.is_block_device();
+    if !is_block_device && !file_type.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a block device or a regular file", path),
+        ));
+    }
+
+    let size = File::open(path)?.seek(SeekFrom::End(0))?;
+
+    let (product, serial, sector_size) = if is_block_device {
+        let name = block_device_name(path).unwrap_or_default();
+        let product = sysfs_attribute(&name, "device/model")
+            .or_else(|| sysfs_attribute(&name, "dm/name"))
+            .or_else(|| sysfs_attribute(&name, "md/level"))
+            .unwrap_or_else(|| format!("Block device {}", name));
+        let serial = sysfs_attribute(&name, "device/serial").unwrap_or_default();
+        (product, serial, block_device_sector_size(path))
+    } else {
+        (String::from("Regular file"), String::new(), None)
+    };
+
+    Ok(HdInfo {
+        product,
+        logical_name: path.to_string(),
+        size: size as f64,
+        units: String::from("bytes"),
+        serial,
+        sector_size,
+        physical: false,
+    })
+}
@@ -0,0 +1,127 @@
+use chrono::{DateTime, Local};
+
+use crate::ewfargs::ArgsList;
+use crate::LoggingInfo;
+
+/// The placeholders a filename template can use, with what they are replaced by.
+pub const PLACEHOLDERS: &[(&str, &str)] = &[
+    ("case", "the case number"),
+    ("evidence", "the evidence number"),
+    ("examiner", "the examiner's name"),
+    ("serial", "the source drive's serial number"),
+    ("product", "the source drive's product name"),
+    ("date", "today's date, as YYYYMMDD"),
+    ("time", "the time, as HHMMSS"),
+];
+
+/// The characters that can't be used in a filename on FAT, NTFS or exFAT, besides control
+/// characters.
+const ILLEGAL_CHARACTERS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// The names Windows reserves for devices, which can't be used as a filename with any extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The longest filename, in characters, that `sanitize` returns. FAT, NTFS and exFAT allow 255,
+/// which leaves room for the extensions of segments and reports.
+const MAX_FILENAME: usize = 200;
+
+/// Returns the value of a placeholder.
+fn value(name: &str, args: &ArgsList, info: &LoggingInfo, now: DateTime<Local>) -> Option<String> {
+    let field = |value: &Option<String>| value.clone().unwrap_or_default();
+    Some(match name {
+        "case" => field(&args.case_number),
+        "evidence" => field(&args.evidence_number),
+        "examiner" => field(&args.examiner_name),
+        "serial" => field(&info.drive_serial).trim().to_string(),
+        "product" => field(&info.drive_product),
+        "date" => now.format("%Y%m%d").to_string(),
+        "time" => now.format("%H%M%S").to_string(),
+        _ => return None,
+    })
+}
+
+/// Expand the placeholders in a filename template, such as `{case}_{evidence}_{serial}_{date}`.
+/// Text outside braces is kept as it is; values that aren't known, such as the serial number of a
+/// file, are left empty.
+///
+/// # Arguments
+///
+/// * `template` - The template to expand.
+/// * `args` - The case fields entered so far.
+/// * `info` - The information about the source drive.
+/// * `now` - The time to use for `{date}` and `{time}`.
+///
+/// # Returns
+///
+/// - `Err` describing the problem if the template has an unknown placeholder or an unclosed brace
+/// - `Ok` with the expanded template, which still needs to be passed through `sanitize`
+pub fn expand(
+    template: &str,
+    args: &ArgsList,
+    info: &LoggingInfo,
+    now: DateTime<Local>,
+) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        expanded.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| String::from("A { in the template is never closed"))?
+            + open;
+        let name = &rest[open + 1..close];
+        expanded.push_str(
+            &value(name, args, info, now)
+                .ok_or_else(|| format!("{{{}}} is not a placeholder grim knows", name))?,
+        );
+        rest = &rest[close + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Make a filename safe to write to FAT, NTFS and exFAT as well as Linux filesystems. Illegal and
+/// control characters become underscores, trailing dots and spaces are removed, reserved device
+/// names are prefixed with an underscore, and long names are shortened.
+pub fn sanitize(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || ILLEGAL_CHARACTERS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(MAX_FILENAME)
+        .collect();
+
+    let trimmed_length = sanitized.trim_end_matches(['.', ' ']).len();
+    sanitized.truncate(trimmed_length);
+    let stem = sanitized.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// Expand a filename template and make the result safe to use as a filename, as `expand` and
+/// `sanitize` do.
+pub fn filename(
+    template: &str,
+    args: &ArgsList,
+    info: &LoggingInfo,
+    now: DateTime<Local>,
+) -> Result<String, String> {
+    let name = sanitize(&expand(template, args, info, now)?);
+    if name.is_empty() {
+        return Err(String::from("The filename is empty"));
+    }
+    Ok(name)
+}
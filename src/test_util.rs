@@ -0,0 +1,60 @@
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::block::FaultyDevice;
+use crate::compare::SectorRange;
+
+/// A temporary directory for a test, removed with everything in it when dropped. Its name holds
+/// the ID of the test process, so that test runs at the same time don't share it.
+pub struct TempDir {
+    pub path: PathBuf,
+}
+
+impl TempDir {
+    /// Returns a new, empty directory. `name` must be unique among the tests, so it starts with
+    /// the name of the module being tested.
+    pub fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("grim-test-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    /// Returns the path of `name` in the directory.
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Write `sectors` sectors to the file `name` in the directory, each filled with its sector
+    /// number plus one so that zeroed sectors stand out.
+    ///
+    /// # Returns
+    ///
+    /// The path of the file.
+    pub fn numbered_sectors(&self, name: &str, sectors: u64, sector_size: u64) -> PathBuf {
+        let path = self.join(name);
+        let data: Vec<u8> = (0..sectors)
+            .flat_map(|sector| vec![(sector + 1) as u8; sector_size as usize])
+            .collect();
+        fs::write(&path, data).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Returns a device reading the file at `path` that fails any read touching the sectors in
+/// `bad_sectors`, given as the first and last sector of each range.
+pub fn faulty_device(path: &Path, sector_size: u64, bad_sectors: &[(u64, u64)]) -> FaultyDevice {
+    let bad_sectors = bad_sectors
+        .iter()
+        .map(|&(start, end)| SectorRange { start, end })
+        .collect();
+    FaultyDevice::new(File::open(path).unwrap(), sector_size, bad_sectors)
+}
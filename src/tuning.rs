@@ -0,0 +1,679 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::acquire::{EwfWriter, ImageWriter};
+use crate::block::BlockDevice;
+use crate::ewfargs::{ArgsList, NumSectors};
+use crate::hash::Digests;
+use crate::image;
+
+/// The read buffer sizes that can be chosen, and that auto-tuning tries.
+pub const READ_BUFFER_SIZES: &[usize] = &[
+    64 * 1024,
+    256 * 1024,
+    1024 * 1024,
+    4 * 1024 * 1024,
+    16 * 1024 * 1024,
+];
+
+/// The numbers of sectors per chunk that auto-tuning tries for an EWF image.
+pub const CHUNK_SECTORS: &[NumSectors] = &[
+    NumSectors::Sectors64,
+    NumSectors::Sectors256,
+    NumSectors::Sectors1024,
+    NumSectors::Sectors4096,
+];
+
+/// How long each combination is tried for while benchmarking.
+const TRIAL_TIME: Duration = Duration::from_millis(300);
+
+/// The most each trial reads or writes, so fast disks don't spend the whole trial filling the
+/// destination.
+const TRIAL_BYTES: u64 = 256 * 1024 * 1024;
+
+/// How much of the source is fed to ewfacquirestream, over and over, to try each chunk size, so
+/// that it compresses data like the source's.
+const CHUNK_SAMPLE: u64 = 16 * 1024 * 1024;
+
+/// How many trials this process has started, so that each names its files uniquely even when
+/// several acquisitions benchmark the same directory at once.
+static TRIALS_STARTED: AtomicUsize = AtomicUsize::new(0);
+
+/// How many chunks each writer thread can have waiting before the reader waits for it.
+const QUEUE_DEPTH: usize = 4;
+
+/// The settings chosen by benchmarking the source and destinations before an acquisition.
+#[derive(Clone)]
+pub struct Benchmark {
+    /// How many bytes to read from the source at once.
+    pub read_buffer: usize,
+    /// How many destinations to write to at the same time.
+    pub writer_threads: usize,
+    /// How many sectors each chunk of an EWF image holds, or `None` if chunk sizes weren't
+    /// benchmarked: raw images have no chunks, and every EWF trial may have failed.
+    pub num_sectors: Option<NumSectors>,
+    /// The throughput the chosen settings reached, in bytes per second.
+    pub throughput: f64,
+    /// How many combinations of settings were tried.
+    pub trials: usize,
+    /// How many of the trials failed, such as reads of a part of the source that couldn't be
+    /// read; they weren't considered.
+    pub failed_trials: usize,
+}
+
+impl Benchmark {
+    /// Returns `args` with the settings this benchmark chose.
+    pub fn apply(&self, args: &ArgsList) -> ArgsList {
+        let mut tuned = args.clone();
+        tuned.read_buffer = Some(self.read_buffer);
+        tuned.writer_threads = Some(self.writer_threads as u32);
+        if let Some(num_sectors) = self.num_sectors {
+            tuned.num_sectors = num_sectors;
+        }
+        tuned
+    }
+}
+
+/// A task run on a writer thread's writers, given the error from an earlier write if there was
+/// one.
+type Run<T> = Box<dyn FnOnce(&mut [T], Option<io::Error>) + Send>;
+
+/// Something for a writer thread to do: write a chunk to each of its writers, or run a task on
+/// them, given the error from an earlier write if there was one.
+enum Task<T> {
+    Write(Arc<[u8]>),
+    Run(Run<T>),
+}
+
+/// A writer thread and the queue feeding it.
+struct Worker<T> {
+    tasks: SyncSender<Task<T>>,
+    thread: JoinHandle<(Vec<T>, Option<io::Error>)>,
+}
+
+/// Writes every chunk to a set of writers from threads that last as long as the pool, so that
+/// the source can be read while the last chunk is still being written. The writers are split
+/// evenly between up to `threads` threads, each writing to its own in order.
+///
+/// Chunks are queued, so an error writing one is returned by a later call. Once a write has
+/// failed, that thread writes nothing more.
+pub struct WriterPool<T: Send + 'static> {
+    workers: Vec<Worker<T>>,
+}
+
+/// Returns the error for a writer thread that has gone away, which only happens if it panicked.
+fn panicked() -> io::Error {
+    io::Error::other("A writer thread panicked")
+}
+
+/// Returns a copy of an error, for reporting it more than once.
+fn copy_error(error: &io::Error) -> io::Error {
+    io::Error::new(error.kind(), error.to_string())
+}
+
+/// The body of a writer thread: carry out tasks until the pool is finished with.
+fn work<T>(
+    mut items: Vec<T>,
+    tasks: Receiver<Task<T>>,
+    write: fn(&mut T, &[u8]) -> io::Result<()>,
+) -> (Vec<T>, Option<io::Error>) {
+    let mut error = None;
+    for task in tasks {
+        match task {
+            Task::Write(data) => {
+                if error.is_none() {
+                    error = items
+                        .iter_mut()
+                        .try_for_each(|item| write(item, &data))
+                        .err();
+                }
+            }
+            Task::Run(run) => run(&mut items, error.as_ref().map(copy_error)),
+        }
+    }
+    (items, error)
+}
+
+impl<T: Send + 'static> WriterPool<T> {
+    /// Start the writer threads.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The writers; with none, writing does nothing.
+    /// * `threads` - The most threads to write with.
+    /// * `write` - Writes a chunk to one writer.
+    pub fn new(
+        mut items: Vec<T>,
+        threads: usize,
+        write: fn(&mut T, &[u8]) -> io::Result<()>,
+    ) -> WriterPool<T> {
+        let per_thread = items.len().div_ceil(threads.max(1)).max(1);
+        let mut workers = Vec::new();
+        while !items.is_empty() {
+            let rest = items.split_off(per_thread.min(items.len()));
+            let group = mem::replace(&mut items, rest);
+            let (tasks, queue) = mpsc::sync_channel(QUEUE_DEPTH);
+            workers.push(Worker {
+                tasks,
+                thread: thread::spawn(move || work(group, queue, write)),
+            });
+        }
+        WriterPool { workers }
+    }
+
+    /// Queue a chunk to be written to every writer, waiting if a thread is too far behind.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` if a writer thread panicked
+    /// - `Ok` once the chunk is queued; write errors are returned by `each` and `into_inner`
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.workers.is_empty() {
+            return Ok(());
+        }
+        let data: Arc<[u8]> = Arc::from(data);
+        for worker in &self.workers {
+            worker
+                .tasks
+                .send(Task::Write(Arc::clone(&data)))
+                .map_err(|_| panicked())?;
+        }
+        Ok(())
+    }
+
+    /// Call `run` for every writer once everything queued before has been written, on the
+    /// writer threads.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` with the error of an earlier write, or the first error `run` returned
+    /// - `Ok` with what `run` returned for each writer, in order
+    pub fn each<R, F>(&mut self, run: F) -> io::Result<Vec<R>>
+    where
+        R: Send + 'static,
+        F: Fn(&mut T) -> io::Result<R> + Send + Sync + 'static,
+    {
+        let run = Arc::new(run);
+        let mut replies = Vec::new();
+        for worker in &self.workers {
+            let (reply, result) = mpsc::channel();
+            let run = Arc::clone(&run);
+            let task = Task::Run(Box::new(move |items: &mut [T], error| {
+                let _ = reply.send(match error {
+                    Some(error) => Err(error),
+                    None => items
+                        .iter_mut()
+                        .map(|item| run(item))
+                        .collect::<io::Result<Vec<R>>>(),
+                });
+            }));
+            worker.tasks.send(task).map_err(|_| panicked())?;
+            replies.push(result);
+        }
+
+        let mut results = Vec::new();
+        for result in replies {
+            results.extend(result.recv().map_err(|_| panicked())??);
+        }
+        Ok(results)
+    }
+
+    /// Wait for everything queued to be written and stop the threads.
+    ///
+    /// # Returns
+    ///
+    /// - `Err` with the first error writing a chunk, or if a writer thread panicked
+    /// - `Ok` with the writers, in order
+    pub fn into_inner(mut self) -> io::Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut first_error = None;
+        for worker in mem::take(&mut self.workers) {
+            drop(worker.tasks);
+            match worker.thread.join() {
+                Ok((group, error)) => {
+                    items.extend(group);
+                    first_error = first_error.or(error);
+                }
+                Err(_) => first_error = first_error.or_else(|| Some(panicked())),
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(items),
+        }
+    }
+}
+
+impl<T: Send + 'static> Drop for WriterPool<T> {
+    fn drop(&mut self) {
+        // Don't leave threads writing after an acquisition has given up
+        for worker in mem::take(&mut self.workers) {
+            drop(worker.tasks);
+            let _ = worker.thread.join();
+        }
+    }
+}
+
+/// Returns the read buffer sizes worth trying for a source with the given sector size: those
+/// that are a whole number of sectors.
+pub fn buffer_sizes(sector_size: u64) -> Vec<usize> {
+    READ_BUFFER_SIZES
+        .iter()
+        .cloned()
+        .filter(|size| (*size as u64).is_multiple_of(sector_size.max(1)))
+        .collect()
+}
+
+/// Measure how fast `buffer_size` bytes at a time can be read from `length` bytes of `source`
+/// starting at `start`.
+///
+/// # Returns
+///
+/// - `Err` if benchmarking was aborted
+/// - `Ok(None)` if the source couldn't be read, so the trial failed
+/// - `Ok` with the bytes read per second
+fn read_rate<D: BlockDevice>(
+    source: &D,
+    start: u64,
+    length: u64,
+    buffer_size: usize,
+    abort: &AtomicBool,
+) -> io::Result<Option<f64>> {
+    let mut buffer = vec![0_u8; buffer_size];
+    let started = Instant::now();
+    let mut done = 0;
+    while done < length.min(TRIAL_BYTES) && started.elapsed() < TRIAL_TIME {
+        if abort.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
+        let wanted = (length - done).min(buffer_size as u64) as usize;
+        let read = match source.read_at(&mut buffer[..wanted], start + done) {
+            Ok(0) => break,
+            Ok(read) => read,
+            // A failing drive is imaged however fast its unreadable areas are
+            Err(_) => return Ok(None),
+        };
+        done += read as u64;
+    }
+    Ok(Some(
+        done as f64 / started.elapsed().as_secs_f64().max(f64::EPSILON),
+    ))
+}
+
+/// Returns a filename for a trial's files that no other trial uses.
+fn trial_name() -> String {
+    format!(
+        ".grim-benchmark-{}-{}",
+        process::id(),
+        TRIALS_STARTED.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Measure how fast `buffer_size` bytes at a time can be written to a file in every directory
+/// in `dirs`, using `threads` threads, including syncing the files at the end. The files the
+/// trial created are removed afterwards.
+fn write_rate(
+    dirs: &[PathBuf],
+    buffer_size: usize,
+    threads: usize,
+    abort: &AtomicBool,
+) -> io::Result<f64> {
+    let name = trial_name();
+    let paths: Vec<PathBuf> = dirs.iter().map(|dir| dir.join(&name)).collect();
+    let mut created = Vec::new();
+    let result = write_files(&paths, &mut created, buffer_size, threads, abort);
+    for path in &created {
+        let _ = fs::remove_file(path);
+    }
+    result
+}
+
+/// Write the trial for `write_rate` to each of `paths`, through a `WriterPool` as the image is,
+/// adding each file to `created` once it has been created.
+fn write_files(
+    paths: &[PathBuf],
+    created: &mut Vec<PathBuf>,
+    buffer_size: usize,
+    threads: usize,
+    abort: &AtomicBool,
+) -> io::Result<f64> {
+    let mut files = Vec::new();
+    for path in paths {
+        files.push(OpenOptions::new().write(true).create_new(true).open(path)?);
+        created.push(path.clone());
+    }
+
+    let buffer = vec![0_u8; buffer_size];
+    let started = Instant::now();
+    let mut pool = WriterPool::new(files, threads, |file: &mut File, data| file.write_all(data));
+    let mut done = 0;
+    while done < TRIAL_BYTES && started.elapsed() < TRIAL_TIME {
+        if abort.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
+        pool.write(&buffer)?;
+        done += buffer_size as u64;
+    }
+    pool.each(|file: &mut File| file.sync_all())?;
+    pool.into_inner()?;
+    Ok(done as f64 / started.elapsed().as_secs_f64().max(f64::EPSILON))
+}
+
+/// Benchmark reading the source and writing to the destinations for a few seconds, trying every
+/// read buffer size with every number of writer threads worth trying, and pick the combination
+/// with the highest throughput. Each read buffer size reads its own part of the source, so the
+/// trials don't read data cached by earlier ones.
+///
+/// A read buffer size whose part of the source can't be read fails its trial and isn't
+/// considered. If no part can be read, the combinations are compared on writing alone.
+///
+/// # Arguments
+///
+/// * `source` - The source and the offset and length of the part to image, or `None` if the
+///   source is a stream that can't be read without consuming it; then only writing is
+///   benchmarked.
+/// * `dirs` - The directories the image will be written to.
+/// * `sector_size` - The sector size of the source; read buffers are a whole number of sectors.
+/// * `abort` - When set, benchmarking stops with `ErrorKind::Interrupted`.
+///
+/// # Returns
+///
+/// - `Err` if a destination couldn't be written, or benchmarking was aborted
+/// - `Ok` with the fastest combination; chunk sizes are tried by `benchmark_chunks`
+pub fn benchmark<D: BlockDevice>(
+    source: Option<(&D, u64, u64)>,
+    dirs: &[PathBuf],
+    sector_size: u64,
+    abort: &AtomicBool,
+) -> io::Result<Benchmark> {
+    let sizes = buffer_sizes(sector_size);
+    if sizes.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No read buffer size is a whole number of sectors",
+        ));
+    }
+    let mut thread_counts = vec![1];
+    if dirs.len() > 1 {
+        thread_counts.push(dirs.len());
+    }
+
+    let mut best: Option<Benchmark> = None;
+    let mut trials = 0;
+    let mut failed_trials = 0;
+    for (index, &size) in sizes.iter().enumerate() {
+        let read = match source {
+            Some((device, start, length)) => {
+                let part = length / sizes.len() as u64;
+                let offset = part * index as u64 / size as u64 * size as u64;
+                match read_rate(device, start + offset, length - offset, size, abort)? {
+                    Some(rate) => rate,
+                    None => {
+                        trials += 1;
+                        failed_trials += 1;
+                        continue;
+                    }
+                }
+            }
+            None => f64::INFINITY,
+        };
+
+        for &threads in &thread_counts {
+            let throughput = read.min(write_rate(dirs, size, threads, abort)?);
+            trials += 1;
+            if best
+                .as_ref()
+                .is_none_or(|best| throughput > best.throughput)
+            {
+                best = Some(Benchmark {
+                    read_buffer: size,
+                    writer_threads: threads,
+                    num_sectors: None,
+                    throughput,
+                    trials: 0,
+                    failed_trials: 0,
+                });
+            }
+        }
+    }
+
+    let mut best = match best {
+        Some(best) => best,
+        None => benchmark::<D>(None, dirs, sector_size, abort)?,
+    };
+    best.trials += trials;
+    best.failed_trials += failed_trials;
+    Ok(best)
+}
+
+/// Returns the first `CHUNK_SAMPLE` bytes of the source, with any part that can't be read left
+/// zeroed, or zeroes if the source is a stream.
+fn chunk_sample<D: BlockDevice>(source: Option<(&D, u64, u64)>) -> Vec<u8> {
+    let length = source.map_or(CHUNK_SAMPLE, |(_, _, length)| length.min(CHUNK_SAMPLE));
+    let mut sample = vec![0_u8; length.max(1) as usize];
+    if let Some((device, start, _)) = source {
+        let _ = device.read_at(&mut sample, start);
+    }
+    sample
+}
+
+/// Measure how fast ewfacquirestream writes an image of `sample`, over and over, with the
+/// settings in `args`, including finishing the image. The image is removed afterwards, unless
+/// something already had its name.
+///
+/// # Returns
+///
+/// - `Err` if benchmarking was aborted
+/// - `Ok(None)` if ewfacquirestream failed, so the trial failed
+/// - `Ok` with the bytes written per second
+fn chunk_rate(
+    args: &ArgsList,
+    dir: &Path,
+    sample: &[u8],
+    abort: &AtomicBool,
+) -> io::Result<Option<f64>> {
+    let filename = trial_name();
+    let first_segment = dir.join(format!("{}.E01", filename));
+    if first_segment.exists() {
+        return Ok(None);
+    }
+    let mut trial = args.clone();
+    trial.target_filename = Some(filename);
+
+    let result = write_ewf(&trial, dir, sample, abort);
+    for segment in image::ewf_segments(&first_segment) {
+        let _ = fs::remove_file(segment);
+    }
+    match result {
+        Ok(rate) => Ok(Some(rate)),
+        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => result.map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Write the trial for `chunk_rate`.
+fn write_ewf(args: &ArgsList, dir: &Path, sample: &[u8], abort: &AtomicBool) -> io::Result<f64> {
+    let mut writer = Box::new(EwfWriter::new(args, dir)?);
+    let started = Instant::now();
+    let mut done = 0;
+    while done < TRIAL_BYTES && started.elapsed() < TRIAL_TIME {
+        if abort.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
+        writer.write_chunk(sample)?;
+        done += sample.len() as u64;
+    }
+    writer.finish(&Digests::default())?;
+    Ok(done as f64 / started.elapsed().as_secs_f64().max(f64::EPSILON))
+}
+
+/// Benchmark ewfacquirestream writing an EWF image to the first destination with each of
+/// `CHUNK_SECTORS`, as the chunk size mostly changes how fast it compresses, and record the
+/// fastest in `benchmark`. Raw images have no chunks, so nothing is tried for them. A chunk
+/// size ewfacquirestream fails with fails its trial and isn't considered; if every one fails,
+/// the chunk size is left as chosen.
+///
+/// # Arguments
+///
+/// * `benchmark` - The result of `benchmark`, which the chunk sizes are tried with.
+/// * `args` - The arguments describing the image.
+/// * `source` - As for `benchmark`; the start of the source is used as the data to write.
+/// * `abort` - When set, benchmarking stops with `ErrorKind::Interrupted`.
+pub fn benchmark_chunks<D: BlockDevice>(
+    benchmark: &mut Benchmark,
+    args: &ArgsList,
+    source: Option<(&D, u64, u64)>,
+    abort: &AtomicBool,
+) -> io::Result<()> {
+    let dir = match args.target_dirs.first() {
+        Some(dir) if !args.ewf_format.is_raw() => PathBuf::from(dir),
+        _ => return Ok(()),
+    };
+    let sample = chunk_sample(source);
+
+    let mut best: Option<(NumSectors, f64)> = None;
+    for &num_sectors in CHUNK_SECTORS {
+        let mut trial = benchmark.apply(args);
+        trial.num_sectors = num_sectors;
+        benchmark.trials += 1;
+        match chunk_rate(&trial, &dir, &sample, abort)? {
+            Some(rate) if best.is_none_or(|(_, best)| rate > best) => {
+                best = Some((num_sectors, rate))
+            }
+            Some(_) => {}
+            None => benchmark.failed_trials += 1,
+        }
+    }
+
+    if let Some((num_sectors, rate)) = best {
+        benchmark.num_sectors = Some(num_sectors);
+        benchmark.throughput = benchmark.throughput.min(rate);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{faulty_device, TempDir};
+
+    const SECTOR: u64 = 512;
+
+    #[test]
+    fn trials_running_together_keep_to_their_own_files() {
+        let dir = TempDir::new("tuning-together");
+        let other = dir.path.join(".grim-benchmark-other");
+        fs::write(&other, b"another job's trial").unwrap();
+
+        let dirs = [dir.path.clone()];
+        let abort = AtomicBool::new(false);
+        thread::scope(|scope| {
+            let trials: Vec<_> = (0..2)
+                .map(|_| scope.spawn(|| write_rate(&dirs, 64 * 1024, 1, &abort)))
+                .collect();
+            for trial in trials {
+                trial.join().unwrap().unwrap();
+            }
+        });
+
+        let left: Vec<_> = fs::read_dir(&dir.path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(left, vec![other]);
+    }
+
+    #[test]
+    fn pool_writes_every_chunk_in_order() {
+        let mut pool = WriterPool::new(
+            vec![Vec::new(), Vec::new(), Vec::new()],
+            2,
+            |out: &mut Vec<u8>, data| {
+                out.extend_from_slice(data);
+                Ok(())
+            },
+        );
+        for chunk in 0..20_u8 {
+            pool.write(&[chunk; 3]).unwrap();
+        }
+        let lengths = pool.each(|out: &mut Vec<u8>| Ok(out.len())).unwrap();
+        assert_eq!(lengths, vec![60, 60, 60]);
+
+        let expected: Vec<u8> = (0..20_u8).flat_map(|chunk| vec![chunk; 3]).collect();
+        for out in pool.into_inner().unwrap() {
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn pool_reports_write_errors_later() {
+        let mut pool = WriterPool::new(vec![0_u32, 1], 2, |item: &mut u32, _| match item {
+            1 => Err(io::Error::other("disk full")),
+            _ => Ok(()),
+        });
+        pool.write(b"data").unwrap();
+        assert!(pool.each(|_: &mut u32| Ok(())).is_err());
+        // The error isn't lost once reported
+        assert!(pool.each(|_: &mut u32| Ok(())).is_err());
+        assert!(pool.into_inner().is_err());
+    }
+
+    #[test]
+    fn pool_without_writers_does_nothing() {
+        let mut pool = WriterPool::new(Vec::<File>::new(), 4, |file: &mut File, data| {
+            file.write_all(data)
+        });
+        pool.write(b"data").unwrap();
+        assert!(pool
+            .each(|file: &mut File| file.sync_all())
+            .unwrap()
+            .is_empty());
+        assert!(pool.into_inner().unwrap().is_empty());
+    }
+
+    #[test]
+    fn read_errors_fail_the_trial() {
+        let dir = TempDir::new("tuning-read");
+        let source = dir.path.join("source");
+        fs::write(&source, vec![0_u8; 64 * SECTOR as usize]).unwrap();
+        let device = faulty_device(&source, SECTOR, &[(40, 40)]);
+        let abort = AtomicBool::new(false);
+
+        let clean = read_rate(&device, 0, 32 * SECTOR, 4096, &abort).unwrap();
+        assert!(clean.is_some());
+        let failed = read_rate(&device, 0, 64 * SECTOR, 4096, &abort).unwrap();
+        assert!(failed.is_none());
+
+        abort.store(true, Ordering::Relaxed);
+        let aborted = read_rate(&device, 0, 64 * SECTOR, 4096, &abort);
+        assert_eq!(aborted.unwrap_err().kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn unreadable_source_is_tuned_for_writing() {
+        let dir = TempDir::new("tuning-failing");
+        let source = dir.path.join("source");
+        fs::write(&source, vec![0_u8; 1024 * 1024]).unwrap();
+        let device = faulty_device(&source, SECTOR, &[(0, 2047)]);
+
+        let benchmark = benchmark(
+            Some((&device, 0, 1024 * 1024)),
+            std::slice::from_ref(&dir.path),
+            SECTOR,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        let sizes = buffer_sizes(SECTOR).len();
+        assert_eq!(benchmark.failed_trials, sizes);
+        assert_eq!(benchmark.trials, sizes * 2);
+        assert!(benchmark.throughput.is_finite());
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, Local};
+
+use crate::acquire::Destination;
+use crate::ewfargs::{ArgsList, DigestType};
+use crate::hash::{Digests, Hasher};
+
+/// The outcome of verifying one copy of an image.
+#[derive(Clone)]
+pub struct Verification {
+    /// The digests calculated over the image data.
+    pub digests: Digests,
+    /// Whether the calculated digests match the expected ones.
+    pub matches: bool,
+}
+
+/// The digests reported by ewfverify.
+#[derive(Clone, Default)]
+pub struct EwfVerifyOutput {
+    /// The digests stored in the image; `md5` is blank if the image has no stored MD5.
+    pub stored: Digests,
+    /// The digests calculated over the image data; `md5` is blank if ewfverify did not finish.
+    pub calculated: Digests,
+    /// Whether ewfverify reported success.
+    pub success: bool,
+}
+
+impl EwfVerifyOutput {
+    /// Returns true if ewfverify succeeded and the calculated digests match the stored ones.
+    pub fn passed(&self) -> bool {
+        self.success && self.calculated.matches(&self.stored)
+    }
+}
+
+/// The result of verifying an existing image on its own, rather than as part of an acquisition.
+#[derive(Clone)]
+pub struct ImageVerification {
+    /// The first segment of the image verified.
+    pub image: PathBuf,
+    /// When verification started.
+    pub started: DateTime<Local>,
+    /// When verification finished.
+    pub finished: DateTime<Local>,
+    /// What ewfverify reported.
+    pub output: EwfVerifyOutput,
+}
+
+/// Returns the `Interrupted` error used when the user cancels.
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "Verification cancelled")
+}
+
+/// Parse the output of ewfverify.
+///
+/// # Arguments
+///
+/// * `output` - Everything ewfverify wrote to stdout.
+pub fn parse_ewfverify_output(output: &str) -> EwfVerifyOutput {
+    /// Returns the hash on a line, or `None` if ewfverify reported it as unavailable.
+    fn value(rest: &str) -> Option<String> {
+        let rest = rest.trim();
+        if rest.is_empty() || rest == "N/A" {
+            None
+        } else {
+            Some(rest.to_lowercase())
+        }
+    }
+
+    let mut parsed = EwfVerifyOutput::default();
+
+    for line in output.lines() {
+        let line = line.trim();
+        let (label, rest) = match line.find(':') {
+            Some(index) => (&line[..index], &line[index + 1..]),
+            None => continue,
+        };
+
+        match label {
+            "MD5 hash stored in file" => parsed.stored.md5 = value(rest).unwrap_or_default(),
+            "MD5 hash calculated over data" => {
+                parsed.calculated.md5 = value(rest).unwrap_or_default()
+            }
+            "SHA1 hash stored in file" => parsed.stored.sha1 = value(rest),
+            "SHA1 hash calculated over data" => parsed.calculated.sha1 = value(rest),
+            "SHA256 hash stored in file" => parsed.stored.sha256 = value(rest),
+            "SHA256 hash calculated over data" => parsed.calculated.sha256 = value(rest),
+            "ewfverify" => parsed.success = rest.trim() == "SUCCESS",
+            _ => (),
+        }
+    }
+
+    parsed
+}
+
+/// Parse an ewfverify status line such as `Status: at 12%.`, returning the percentage.
+pub fn parse_ewfverify_status(line: &str) -> Option<u64> {
+    let line = line.trim();
+    if !line.starts_with("Status: at ") {
+        return None;
+    }
+    line["Status: at ".len()..]
+        .trim_end_matches('.')
+        .trim_end_matches('%')
+        .parse()
+        .ok()
+}
+
+/// Run ewfverify against an EWF image.
+///
+/// # Arguments
+///
+/// * `first_segment` - The first segment file of the image.
+/// * `digest_type` - The digests to calculate besides MD5.
+/// * `progress` - Called with the percentage complete whenever ewfverify reports it.
+/// * `abort` - When set, ewfverify is killed and `ErrorKind::Interrupted` is returned.
+pub fn run_ewfverify<F>(
+    first_segment: &Path,
+    digest_type: DigestType,
+    mut progress: F,
+    abort: &AtomicBool,
+) -> io::Result<EwfVerifyOutput>
+where
+    F: FnMut(u64, u64),
+{
+    let mut command = Command::new("ewfverify");
+    let mut digests = Vec::new();
+    if digest_type.contains(DigestType::SHA1) {
+        digests.push("sha1");
+    }
+    if digest_type.contains(DigestType::SHA256) {
+        digests.push("sha256");
+    }
+    if !digests.is_empty() {
+        command.arg("-d").arg(digests.join(","));
+    }
+
+    let mut child = command
+        .arg(first_segment)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let mut output = String::new();
+    for line in BufReader::new(child.stdout.take().unwrap()).lines() {
+        if abort.load(Ordering::Relaxed) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(cancelled());
+        }
+
+        let line = line?;
+        if let Some(percent) = parse_ewfverify_status(&line) {
+            progress(percent, 100);
+        }
+        output.push_str(&line);
+        output.push('\n');
+    }
+    child.wait()?;
+
+    Ok(parse_ewfverify_output(&output))
+}
+
+/// Hash the data in a raw image by reading its files in order.
+///
+/// # Arguments
+///
+/// * `files` - The files making up the image, in order.
+/// * `digest_type` - The digests to calculate besides MD5.
+/// * `progress` - Called after every block with the bytes read so far and the total size of the files.
+/// * `abort` - When set, hashing stops and `ErrorKind::Interrupted` is returned.
+pub fn hash_raw_image<F>(
+    files: &[PathBuf],
+    digest_type: DigestType,
+    mut progress: F,
+    abort: &AtomicBool,
+) -> io::Result<Digests>
+where
+    F: FnMut(u64, u64),
+{
+    let mut total = 0;
+    for file in files {
+        total += file.metadata()?.len();
+    }
+
+    let mut hasher = Hasher::new(digest_type);
+    let mut buffer = vec![0_u8; 1024 * 1024];
+    let mut done = 0_u64;
+
+    for file in files {
+        let mut file = File::open(file)?;
+        loop {
+            if abort.load(Ordering::Relaxed) {
+                return Err(cancelled());
+            }
+            let read = match file.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            hasher.update(&buffer[..read]);
+            done += read as u64;
+            progress(done, total);
+        }
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Verify one copy of an image by reading it back and comparing its digests against `expected`.
+///
+/// # Arguments
+///
+/// * `args` - The arguments the image was written with.
+/// * `destination` - The copy of the image to verify.
+/// * `expected` - The digests calculated while reading the source.
+/// * `progress` - Called with the progress of the verification, as done and total.
+/// * `abort` - When set, verification stops and `ErrorKind::Interrupted` is returned.
+pub fn verify_destination<F>(
+    args: &ArgsList,
+    destination: &Destination,
+    expected: &Digests,
+    progress: F,
+    abort: &AtomicBool,
+) -> io::Result<Verification>
+where
+    F: FnMut(u64, u64),
+{
+    if args.ewf_format.is_raw() {
+        let digests = hash_raw_image(&destination.files, args.digest_type, progress, abort)?;
+        Ok(Verification {
+            matches: digests.matches(expected),
+            digests,
+        })
+    } else {
+        let first_segment = destination.files.first().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "No image files were written")
+        })?;
+        let output = run_ewfverify(first_segment, args.digest_type, progress, abort)?;
+        Ok(Verification {
+            matches: output.success && output.calculated.matches(expected),
+            digests: output.calculated,
+        })
+    }
+}